@@ -25,24 +25,27 @@ impl Env {
     }
 
     pub fn tick(&self, projectile: Projectile) -> Projectile {
-        Projectile {position: projectile.position + &projectile.velocity, velocity: projectile.velocity + &self.gravity + &self.wind}
+        Projectile {position: projectile.position + projectile.velocity, velocity: projectile.velocity + self.gravity + self.wind}
     }
 }
 
 fn main() {
-    println!("Running projectile!");
+    // info and up by default, as the old println output was; RUST_LOG overrides
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    log::info!("Running projectile!");
     let mut proj = Projectile::new(Tuple::point(0.0, 1.0, 0.0), Tuple::vector(1.0, 1.8, 0.0).normalize() * 11.25);
     let env = Env::new(Tuple::vector(0.0, -0.1, 0.0), Tuple::vector(-0.01, 0.0, 0.0));
     let mut canvas = Canvas::new(900, 550);
     loop {
         proj = env.tick(proj);
         let x = proj.position.x().round() as usize;
-        let y = canvas.width() as usize - proj.position.y().round() as usize;
-        canvas.write_pixel(x, y, Color::new(1.0, 0.0, 0.0));
-        println!("new proj: {:?}", proj);
+        // plotted with y up, flipped the right way round before saving
+        let y = proj.position.y().round() as usize;
+        canvas.write_pixel(x, y, Color::RED);
+        log::debug!("new proj: {:?}", proj);
         if proj.position.y() <= 0.0 {
             break;
         }
     }
-    canvas.save("canvas.ppm".to_string()).unwrap();
+    canvas.flip_vertical().save("canvas.ppm".to_string()).unwrap();
 }
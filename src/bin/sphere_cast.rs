@@ -1,44 +1,50 @@
-use libraytracer::ray::Ray;
 use libraytracer::color::Color;
 use libraytracer::sphere::Sphere;
-use libraytracer::canvas::Canvas;
+use libraytracer::camera::{self, Camera};
+use libraytracer::sampler::Sampler;
+use libraytracer::matrix4::Matrix4;
 use libraytracer::tuple::Tuple;
-use libraytracer::transform::TransformBuilder;
 use libraytracer::intersection::Intersect;
 use libraytracer::light::PointLight;
+use libraytracer::progress::Progress;
 
 fn main() {
+    // info and up by default, as the old println output was; RUST_LOG overrides
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    // shading panics are logged by the renderer, don't print them twice
+    camera::quiet_shading_panics();
     let canvas_dim = 100;
-    let mut canvas = Canvas::new(canvas_dim, canvas_dim);
     let mut s = Sphere::new(1);
     s.material.color = Color::new(1.0, 0.2, 1.0);
-    // s.set_transform(TransformBuilder::new(4).scale(0.5, 1.0, 1.0).shear(1.0, 0.0, 0.0, 0.0, 0.0, 0.0).build());
+    // s.set_transform(TransformBuilder::new4().scale(0.5, 1.0, 1.0).shear(1.0, 0.0, 0.0, 0.0, 0.0, 0.0).build());
     let light_position = Tuple::point(-10.0, 10.0, -10.0);
-    let light_color = Color::new(1.0, 1.0, 1.0);
+    let light_color = Color::WHITE;
     let light = PointLight::new(light_color, light_position);
-    let ray_origin = Tuple::point(0.0, 0.0, -5.0);
-    let wall_z = 10.0;
-    let wall_size = 7.0;
-    let half_wallsize = wall_size / 2.0;
-    println!("half: {}", half_wallsize);
-    let pixel_size = wall_size / canvas_dim as f32;
-    println!("pixel size: {}", pixel_size);
-    let red = Color::new(1.0, 0.0, 0.0);
-    for i in 0..canvas_dim {
-        let y = half_wallsize - i as f32 * pixel_size;
-        for j in 0..canvas_dim {
-            let x = -half_wallsize + pixel_size * j as f32;
-            let pos = Tuple::point(x, y, wall_z);
-            let r_direction = (pos - ray_origin).normalize();
-            let r = Ray::new(ray_origin.clone(), r_direction);
-            if let Some(h) = s.intersect(&r).hit() {
+    // a 7 unit wall 15 units away from the eye fills the view
+    let wall_distance = 15.0f32;
+    let half_wallsize = 7.0 / 2.0;
+    let field_of_view = 2.0 * (half_wallsize / wall_distance).atan();
+    let mut camera = Camera::new(canvas_dim, canvas_dim, field_of_view);
+    camera.set_transform(Matrix4::view_transform(&Tuple::point(0.0, 0.0, -5.0),
+        &Tuple::point(0.0, 0.0, 0.0), &Tuple::vector(0.0, 1.0, 0.0)));
+    camera.set_samples(4);
+    camera.set_sampler(Sampler::Jittered);
+    camera.set_adaptive_threshold(Some(0.05));
+    let mut report = |p: &Progress| {
+        let eta = p.eta().map(|d| d.as_secs_f32()).unwrap_or(0.0);
+        eprint!("\rrendering: {:5.1}% ({}/{} tiles, eta {:.1}s)", p.fraction() * 100.0, p.tiles_done, p.total_tiles, eta);
+    };
+    let canvas = camera.render_with_progress(|r| {
+        match s.intersect(r).hit() {
+            Some(h) => {
                 let point = r.position(h.point());
                 let normal = h.object().normal_at(point);
                 let eye = -(*r.direction());
-                let color = s.material.lighting(&light, &point, &eye, &normal);
-                canvas.write_pixel(j as usize, i as usize, color);
-            }
+                s.material.lighting(&light, &point, &eye, &normal)
+            },
+            None => Color::BLACK
         }
-    }
+    }, &mut report);
+    eprintln!();
     canvas.save("sphere_cast.ppm".to_string()).unwrap();
 }
\ No newline at end of file
@@ -0,0 +1,1399 @@
+use super::ray::Ray;
+use super::tuple::Tuple;
+use super::matrix4::Matrix4;
+use super::color::Color;
+use super::canvas::Canvas;
+use super::sampler::{self, Rng, Sampler};
+use super::tile::{self, PixelOrder, Tile, TileOrder};
+use super::progress::{NoProgress, Progress, RenderProgress};
+use super::cancel::CancellationToken;
+use super::watchdog;
+use std::any::Any;
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+const EPSILON: f32 = 1e-4;
+
+// fills pixels whose shading panicked so the failure is obvious in the image
+pub const ERROR_COLOR: Color = Color::MAGENTA;
+
+// how many times a tile is shaded before giving up on it; a shader that only
+// fails sometimes (e.g. on shared state) gets another chance
+const TILE_ATTEMPTS: usize = 2;
+
+thread_local! {
+    static SHADING: Cell<bool> = const { Cell::new(false) };
+}
+
+// Opt-in for applications: from now on, panics the renderer catches while
+// shading are not also printed by the panic hook, since the renderer logs
+// them itself. Wraps the hook installed at the time of the call, which still
+// sees every other panic, so call it after installing your own hook.
+// Without it, caught panics are printed as usual.
+pub fn quiet_shading_panics() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if !SHADING.with(|shading| shading.get()) {
+            previous(info);
+        }
+    }));
+}
+
+// runs `f`, catching a panic; marks the thread as shading meanwhile for
+// quiet_shading_panics()
+fn catch_shading_panic<R, F: FnOnce() -> R>(f: F) -> std::thread::Result<R> {
+    SHADING.with(|shading| shading.set(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    SHADING.with(|shading| shading.set(false));
+    return result;
+}
+
+fn panic_message(cause: &(dyn Any + Send)) -> &str {
+    if let Some(message) = cause.downcast_ref::<&str>() {
+        return message;
+    }
+    if let Some(message) = cause.downcast_ref::<String>() {
+        return message;
+    }
+    "unknown panic"
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    // parallel rays; view_width is the horizontal extent of the view in
+    // world units
+    Orthographic { view_width: f32 }
+}
+
+// region of interest for preview renders: pixels within `radius` of `centre`
+// get the camera's full N x N samples, which fall off smoothly over
+// `falloff` to `peripheral_samples` per side. Positions are fractions of the
+// image width and height (radius and falloff of the width), so the region
+// survives scaled() and overscanned() unchanged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Foveation {
+    pub centre: (f32, f32),
+    pub radius: f32,
+    pub falloff: f32,
+    pub peripheral_samples: u16
+}
+
+// something the shading hook reported while tracing one camera ray
+#[derive(Debug, PartialEq)]
+pub enum TraceEvent {
+    // an object was tested against a ray; t of its nearest hit, if any
+    ObjectTest {id: i32, t: Option<f32>},
+    // the hit that was shaded
+    Hit {id: i32, t: f32, point: Tuple, normal: Tuple},
+    ShadowRay {ray: Ray, occluded: bool},
+    // a reflected or refracted ray, depth 1 for the first bounce
+    Bounce {depth: usize, ray: Ray}
+}
+
+// Collects TraceEvents from a shading hook. A disabled log ignores them, so
+// the same hook can render normally at little cost.
+#[derive(Debug, PartialEq)]
+pub struct TraceLog {
+    enabled: bool,
+    events: Vec<TraceEvent>
+}
+
+impl TraceLog {
+    pub fn new() -> Self {
+        Self {enabled: true, events: Vec::new()}
+    }
+
+    pub fn disabled() -> Self {
+        Self {enabled: false, events: Vec::new()}
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn record(&mut self, event: TraceEvent) {
+        if self.enabled {
+            self.events.push(event);
+        }
+    }
+
+    pub fn object_test(&mut self, id: i32, t: Option<f32>) {
+        self.record(TraceEvent::ObjectTest {id, t});
+    }
+
+    pub fn hit(&mut self, id: i32, t: f32, point: Tuple, normal: Tuple) {
+        self.record(TraceEvent::Hit {id, t, point, normal});
+    }
+
+    pub fn shadow_ray(&mut self, ray: Ray, occluded: bool) {
+        self.record(TraceEvent::ShadowRay {ray, occluded});
+    }
+
+    pub fn bounce(&mut self, depth: usize, ray: Ray) {
+        self.record(TraceEvent::Bounce {depth, ray});
+    }
+
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+}
+
+impl Default for TraceLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// one camera ray traced for a pixel, the colour the hook gave it and what
+// the hook reported along the way
+#[derive(Debug, PartialEq)]
+pub struct TraceSample {
+    pub ray: Ray,
+    pub color: Color,
+    pub events: Vec<TraceEvent>
+}
+
+// everything done for one pixel: the rays cast, what each returned, and the
+// colour render() would write
+#[derive(Debug, PartialEq)]
+pub struct PixelTrace {
+    pub x: usize,
+    pub y: usize,
+    pub samples: Vec<TraceSample>,
+    pub color: Color
+}
+
+// where one sample of a pixel goes: sub-pixel offset, lens position and
+// shutter time
+#[derive(Debug, Clone, Copy)]
+struct PixelSample {
+    offset: (f32, f32),
+    lens: (f32, f32),
+    time: f32
+}
+
+#[derive(Debug, Clone)]
+pub struct Camera {
+    hsize: u16,
+    vsize: u16,
+    field_of_view: f32,
+    projection: Projection,
+    transform: Matrix4,
+    inverse: Matrix4,
+    half_width: f32,
+    half_height: f32,
+    pixel_size: f32,
+    samples: u16,
+    sampler: Sampler,
+    seed: u64,
+    adaptive_threshold: Option<f32>,
+    aperture: f32,
+    focal_distance: f32,
+    shutter: (f32, f32),
+    tile_size: usize,
+    tile_order: TileOrder,
+    pixel_order: PixelOrder,
+    cancellation: Option<CancellationToken>,
+    foveation: Option<Foveation>
+}
+
+impl Camera {
+    pub fn new(hsize: u16, vsize: u16, field_of_view: f32) -> Self {
+        let half_view = (field_of_view / 2.0).tan();
+        let aspect = hsize as f32 / vsize as f32;
+        let (half_width, half_height) = if aspect >= 1.0 {
+            (half_view, half_view / aspect)
+        } else {
+            (half_view * aspect, half_view)
+        };
+        let pixel_size = half_width * 2.0 / hsize as f32;
+        Self {hsize, vsize, field_of_view, projection: Projection::Perspective,
+            transform: Matrix4::identity(), inverse: Matrix4::identity(),
+            half_width, half_height, pixel_size, samples: 1,
+            sampler: Sampler::Uniform, seed: 0, adaptive_threshold: None,
+            aperture: 0.0, focal_distance: 1.0, shutter: (0.0, 0.0),
+            tile_size: 32, tile_order: TileOrder::Scanline,
+            pixel_order: PixelOrder::Scanline, cancellation: None, foveation: None}
+    }
+
+    pub fn orthographic(hsize: u16, vsize: u16, view_width: f32) -> Self {
+        let mut camera = Self::new(hsize, vsize, 0.0);
+        camera.projection = Projection::Orthographic {view_width};
+        camera.half_width = view_width / 2.0;
+        camera.half_height = camera.half_width * vsize as f32 / hsize as f32;
+        camera.pixel_size = view_width / hsize as f32;
+        return camera;
+    }
+
+    // same view and settings at a different resolution
+    pub fn scaled(&self, factor: f32) -> Self {
+        let hsize = ((self.hsize as f32 * factor).round() as u16).max(1);
+        let vsize = ((self.vsize as f32 * factor).round() as u16).max(1);
+        let resized = match self.projection {
+            Projection::Perspective => Self::new(hsize, vsize, self.field_of_view),
+            Projection::Orthographic {view_width} => Self::orthographic(hsize, vsize, view_width)
+        };
+        let mut camera = self.clone();
+        camera.hsize = hsize;
+        camera.vsize = vsize;
+        camera.half_width = resized.half_width;
+        camera.half_height = resized.half_height;
+        camera.pixel_size = resized.pixel_size;
+        return camera;
+    }
+
+    // the same view grown by `margin` pixels on every side; pixel (x, y) of
+    // this camera is pixel (x + margin, y + margin) of the result. None if the
+    // grown size doesn't fit in a u16
+    pub fn overscanned(&self, margin: u16) -> Option<Self> {
+        let grow = |size: u16| margin.checked_mul(2).and_then(|m| size.checked_add(m));
+        let mut camera = self.clone();
+        camera.hsize = grow(self.hsize)?;
+        camera.vsize = grow(self.vsize)?;
+        camera.half_width = self.half_width + margin as f32 * self.pixel_size;
+        camera.half_height = self.half_height + margin as f32 * self.pixel_size;
+        match self.projection {
+            Projection::Perspective => camera.field_of_view = 2.0 * camera.half_width.max(camera.half_height).atan(),
+            Projection::Orthographic {..} => camera.projection = Projection::Orthographic {view_width: 2.0 * camera.half_width}
+        }
+        return Some(camera);
+    }
+
+    pub fn hsize(&self) -> u16 {
+        self.hsize
+    }
+
+    pub fn vsize(&self) -> u16 {
+        self.vsize
+    }
+
+    pub fn field_of_view(&self) -> f32 {
+        self.field_of_view
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn pixel_size(&self) -> f32 {
+        self.pixel_size
+    }
+
+    pub fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    // None (leaving the camera unchanged) if the transform can't be inverted
+    pub fn set_transform(&mut self, transform: Matrix4) -> Option<()> {
+        self.inverse = transform.inverse()?;
+        self.transform = transform;
+        Some(())
+    }
+
+    // anti-aliasing: each pixel is sampled on an N x N grid of sub-pixels
+    pub fn samples(&self) -> u16 {
+        self.samples
+    }
+
+    pub fn set_samples(&mut self, samples: u16) {
+        self.samples = samples.max(1);
+    }
+
+    pub fn sampler(&self) -> Sampler {
+        self.sampler
+    }
+
+    pub fn set_sampler(&mut self, sampler: Sampler) {
+        self.sampler = sampler;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    // depth of field: rays start on a lens disk of this radius and converge
+    // on the plane focal_distance in front of the camera; 0 is a pinhole
+    pub fn aperture(&self) -> f32 {
+        self.aperture
+    }
+
+    pub fn set_aperture(&mut self, aperture: f32) {
+        self.aperture = aperture.max(0.0);
+    }
+
+    pub fn focal_distance(&self) -> f32 {
+        self.focal_distance
+    }
+
+    // clamped to just in front of the lens, since a focal plane on or behind
+    // it would make every lens ray degenerate
+    pub fn set_focal_distance(&mut self, focal_distance: f32) {
+        self.focal_distance = focal_distance.max(EPSILON);
+    }
+
+    // motion blur: samples get times spread over [open, close]; moving
+    // shapes are at their start at time 0 and their end at time 1
+    pub fn shutter(&self) -> (f32, f32) {
+        self.shutter
+    }
+
+    pub fn set_shutter(&mut self, open: f32, close: f32) {
+        self.shutter = (open, close.max(open));
+    }
+
+    // every pixel gets its own generator derived from the seed, so a pixel's
+    // samples don't depend on the order pixels are rendered in
+    pub fn pixel_rng(&self, px: usize, py: usize) -> Rng {
+        let pixel = (py as u64) * self.hsize as u64 + px as u64;
+        Rng::new(self.seed ^ Rng::mix(pixel))
+    }
+
+    pub fn tile_size(&self) -> usize {
+        self.tile_size
+    }
+
+    pub fn set_tile_size(&mut self, tile_size: usize) {
+        self.tile_size = tile_size.max(1);
+    }
+
+    pub fn tile_order(&self) -> TileOrder {
+        self.tile_order
+    }
+
+    pub fn set_tile_order(&mut self, order: TileOrder) {
+        self.tile_order = order;
+    }
+
+    // order of the pixels within each tile; a single image-sized tile with
+    // Morton order walks the whole frame along a Z-curve
+    pub fn pixel_order(&self) -> PixelOrder {
+        self.pixel_order
+    }
+
+    pub fn set_pixel_order(&mut self, order: PixelOrder) {
+        self.pixel_order = order;
+    }
+
+    // renders stop between tiles once the token is cancelled and return the
+    // partially rendered canvas
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancellation = token;
+    }
+
+    fn is_cancelled(&self) -> bool {
+        match &self.cancellation {
+            Some(token) => token.is_cancelled(),
+            None => false
+        }
+    }
+
+    // the buckets render() works through, in order
+    pub fn tiles(&self) -> Vec<Tile> {
+        tile::tiles(self.hsize as usize, self.vsize as usize, self.tile_size, self.tile_order)
+    }
+
+    // with a threshold set, only pixels that differ from a neighbour by more than it
+    // in some channel get the full N x N samples; the rest keep a single centre ray
+    pub fn adaptive_threshold(&self) -> Option<f32> {
+        self.adaptive_threshold
+    }
+
+    pub fn set_adaptive_threshold(&mut self, threshold: Option<f32>) {
+        self.adaptive_threshold = threshold;
+    }
+
+    pub fn foveation(&self) -> Option<Foveation> {
+        self.foveation
+    }
+
+    pub fn set_foveation(&mut self, foveation: Option<Foveation>) {
+        self.foveation = foveation;
+    }
+
+    // samples per side for a pixel, after foveation
+    pub fn samples_at(&self, px: usize, py: usize) -> u16 {
+        let fov = match self.foveation {
+            Some(fov) => fov,
+            None => return self.samples
+        };
+        let width = self.hsize as f32;
+        let dx = (px as f32 + 0.5) / width - fov.centre.0;
+        let dy = ((py as f32 + 0.5) / self.vsize as f32 - fov.centre.1) * self.vsize as f32 / width;
+        let distance = (dx * dx + dy * dy).sqrt() - fov.radius;
+        let t = if fov.falloff > 0.0 { (distance / fov.falloff).clamp(0.0, 1.0) } else if distance > 0.0 { 1.0 } else { 0.0 };
+        let t = t * t * (3.0 - 2.0 * t);
+        let full = self.samples as f32;
+        let peripheral = fov.peripheral_samples.clamp(1, self.samples) as f32;
+        return (full + (peripheral - full) * t).round() as u16;
+    }
+
+    // x and y are in canvas space, so (px + 0.5, py + 0.5) is the centre of a pixel
+    fn ray_through(&self, x: f32, y: f32) -> Ray {
+        let world_x = self.half_width - x * self.pixel_size;
+        let world_y = self.half_height - y * self.pixel_size;
+        if let Projection::Orthographic {..} = self.projection {
+            let origin = self.inverse * Tuple::point(world_x, world_y, 0.0);
+            let direction = (self.inverse * Tuple::vector(0.0, 0.0, -1.0)).normalize();
+            return Ray::new(origin, direction);
+        }
+        let pixel = self.inverse * Tuple::point(world_x, world_y, -1.0);
+        let origin = self.inverse * Tuple::point(0.0, 0.0, 0.0);
+        let direction = (pixel - origin).normalize();
+        return Ray::new(origin, direction);
+    }
+
+    // ray through canvas position (x, y) starting from lens position
+    // (u, v) in [0, 1)^2, mapped uniformly onto the aperture disk
+    fn lens_ray_through(&self, x: f32, y: f32, lens: (f32, f32)) -> Ray {
+        // an orthographic view has no lens to defocus through
+        if self.aperture == 0.0 || self.projection != Projection::Perspective {
+            return self.ray_through(x, y);
+        }
+        let world_x = self.half_width - x * self.pixel_size;
+        let world_y = self.half_height - y * self.pixel_size;
+        let focus = Tuple::point(world_x * self.focal_distance, world_y * self.focal_distance, -self.focal_distance);
+        let r = self.aperture * lens.0.sqrt();
+        let phi = 2.0 * std::f32::consts::PI * lens.1;
+        let lens_point = Tuple::point(r * phi.cos(), r * phi.sin(), 0.0);
+        let origin = self.inverse * lens_point;
+        let direction = (self.inverse * focus - origin).normalize();
+        return Ray::new(origin, direction);
+    }
+
+    // sub-pixel offsets, lens positions and times for the pixel's n x n
+    // samples; render() and render_progressive() share these so they agree
+    fn pixel_samples(&self, px: usize, py: usize, n: usize) -> Vec<PixelSample> {
+        (0..n * n).map(|index| self.pixel_sample(px, py, n, index)).collect()
+    }
+
+    // each sample draws from its own stream, so any one of them can be
+    // generated without the ones before it
+    fn pixel_sample(&self, px: usize, py: usize, n: usize, index: usize) -> PixelSample {
+        let pixel = self.pixel_rng(px, py).next_u64();
+        let mut rng = Rng::new(pixel ^ Rng::mix(index as u64));
+        let offset = self.sampler.sample(n, index, &mut rng);
+        let (open, close) = self.shutter;
+        let lens = if self.aperture > 0.0 { (rng.next_f32(), rng.next_f32()) } else { (0.0, 0.0) };
+        let time = if close > open { open + (close - open) * rng.next_f32() } else { open };
+        return PixelSample {offset, lens, time};
+    }
+
+    // inverse of ray_through: where a world point lands on the canvas, or None
+    // if it is behind the eye
+    pub fn project(&self, point: &Tuple) -> Option<(f32, f32)> {
+        let p = self.transform * *point;
+        if p.z() >= -EPSILON {
+            return None;
+        }
+        if let Projection::Orthographic {..} = self.projection {
+            return Some(((self.half_width - p.x()) / self.pixel_size, (self.half_height - p.y()) / self.pixel_size));
+        }
+        let x = (self.half_width + p.x() / p.z()) / self.pixel_size;
+        let y = (self.half_height + p.y() / p.z()) / self.pixel_size;
+        return Some((x, y));
+    }
+
+    pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
+        self.ray_through(px as f32 + 0.5, py as f32 + 0.5).as_primary()
+    }
+
+    fn sample_ray(&self, px: usize, py: usize, sample: &PixelSample) -> Ray {
+        let (dx, dy) = sample.offset;
+        self.lens_ray_through(px as f32 + dx, py as f32 + dy, sample.lens).with_time(sample.time).as_primary()
+    }
+
+    pub fn rays_for_pixel(&self, px: usize, py: usize) -> Vec<Ray> {
+        let samples = self.pixel_samples(px, py, self.samples_at(px, py) as usize);
+        let mut rays = Vec::with_capacity(samples.len());
+        for sample in samples {
+            rays.push(self.sample_ray(px, py, &sample));
+        }
+        return rays;
+    }
+
+    fn sample_pixel<F: Fn(&Ray) -> Color>(&self, px: usize, py: usize, color_at: &F) -> Color {
+        let rays = self.rays_for_pixel(px, py);
+        let mut color = Color::black();
+        for r in &rays {
+            let sample = color_at(r);
+            watchdog::check_color(px, py, &sample);
+            color += sample;
+        }
+        return color * (1.0 / rays.len() as f32);
+    }
+
+    // Re-traces a single pixel for debugging a black or speckled one. There
+    // is no world yet, so `shade` does the object tests, shadow rays and
+    // bounces, reporting them to the TraceLog it's given; render with
+    // |r| shade(r, &mut TraceLog::disabled()) for the matching image. Samples
+    // follow render(): foveation's per-pixel count, and just the centre ray
+    // for pixels adaptive sampling leaves alone. Panics in `shade` are not
+    // caught.
+    pub fn trace_pixel_debug<F: Fn(&Ray, &mut TraceLog) -> Color>(&self, px: usize, py: usize, shade: F) -> PixelTrace {
+        let trace_ray = |ray: Ray| {
+            let mut log = TraceLog::new();
+            let color = shade(&ray, &mut log);
+            TraceSample {ray, color, events: log.events}
+        };
+        if !self.refines(px, py, &|r: &Ray| shade(r, &mut TraceLog::disabled())) {
+            let sample = trace_ray(self.ray_for_pixel(px, py));
+            return PixelTrace {x: px, y: py, color: sample.color.clone(), samples: vec![sample]};
+        }
+        let samples: Vec<TraceSample> = self.rays_for_pixel(px, py).into_iter().map(trace_ray).collect();
+        let mut color = Color::black();
+        for sample in &samples {
+            color += &sample.color;
+        }
+        let color = color * (1.0 / samples.len() as f32);
+        return PixelTrace {x: px, y: py, samples, color};
+    }
+
+    // whether render() gives the pixel its full samples: always, unless
+    // adaptive sampling applies and the centre matches its 4-neighbours'
+    fn refines<F: Fn(&Ray) -> Color>(&self, px: usize, py: usize, color_at: &F) -> bool {
+        let threshold = match self.adaptive_threshold_in_use() {
+            Some(threshold) => threshold,
+            None => return true
+        };
+        let centre = color_at(&self.ray_for_pixel(px, py));
+        let (width, height) = (self.hsize as usize, self.vsize as usize);
+        let neighbours = [(px.wrapping_sub(1), py), (px + 1, py), (px, py.wrapping_sub(1)), (px, py + 1)];
+        return neighbours.iter().filter(|&&(x, y)| x < width && y < height)
+            .any(|&(x, y)| Self::exceeds_contrast(&centre, &color_at(&self.ray_for_pixel(x, y)), threshold));
+    }
+
+    fn exceeds_contrast(a: &Color, b: &Color, threshold: f32) -> bool {
+        (a.red() - b.red()).abs() > threshold
+            || (a.green() - b.green()).abs() > threshold
+            || (a.blue() - b.blue()).abs() > threshold
+    }
+
+    // the adaptive threshold if adaptive sampling applies to this camera
+    fn adaptive_threshold_in_use(&self) -> Option<f32> {
+        // with depth of field or motion blur a single pinhole centre can't stand in for a blurred pixel
+        let blurred = (self.aperture > 0.0 && self.projection == Projection::Perspective) || self.shutter.1 > self.shutter.0;
+        match self.adaptive_threshold {
+            Some(threshold) if self.samples > 1 && !blurred => Some(threshold),
+            _ => None
+        }
+    }
+
+    // marks the pixels whose centre colour differs too much from a 4-neighbour
+    fn needs_refinement(&self, centres: &[Color], threshold: f32) -> Vec<bool> {
+        let width = self.hsize as usize;
+        let height = self.vsize as usize;
+        let mut refine = vec![false; centres.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let idx = y * width + x;
+                if x + 1 < width && Self::exceeds_contrast(&centres[idx], &centres[idx + 1], threshold) {
+                    refine[idx] = true;
+                    refine[idx + 1] = true;
+                }
+                if y + 1 < height && Self::exceeds_contrast(&centres[idx], &centres[idx + width], threshold) {
+                    refine[idx] = true;
+                    refine[idx + width] = true;
+                }
+            }
+        }
+        return refine;
+    }
+
+    // adaptive sampling needs every centre colour before it can pick pixels
+    // to refine: the centres, and which pixels need their full samples. None
+    // if adaptive sampling is off, doesn't apply, or the render was cancelled
+    fn adaptive_centres<F: Fn(&Ray) -> Color>(&self, color_at: &F) -> Option<(Vec<Color>, Vec<bool>)> {
+        let threshold = self.adaptive_threshold_in_use()?;
+        let width = self.hsize as usize;
+        let mut centres = Vec::with_capacity(width * self.vsize as usize);
+        for y in 0..self.vsize as usize {
+            if self.is_cancelled() {
+                return None;
+            }
+            for x in 0..width {
+                let centre = match catch_shading_panic(|| color_at(&self.ray_for_pixel(x, y))) {
+                    Ok(centre) => centre,
+                    Err(cause) => {
+                        log::error!("shading pixel ({}, {}) panicked: {}", x, y, panic_message(cause.as_ref()));
+                        ERROR_COLOR
+                    }
+                };
+                watchdog::check_color(x, y, &centre);
+                centres.push(centre);
+            }
+        }
+        let refine = self.needs_refinement(&centres, threshold);
+        return Some((centres, refine));
+    }
+
+    // there is no world yet, so the caller decides what colour a ray sees
+    pub fn render<F: Fn(&Ray) -> Color>(&self, color_at: F) -> Canvas {
+        self.render_with_progress(color_at, &mut NoProgress)
+    }
+
+    pub fn render_with_progress<F: Fn(&Ray) -> Color>(&self, color_at: F, progress: &mut dyn RenderProgress) -> Canvas {
+        let start = Instant::now();
+        let mut image = Canvas::new(self.hsize, self.vsize);
+        let width = self.hsize as usize;
+        let adaptive = self.adaptive_centres(&color_at);
+        let tiles = self.tiles();
+        log::debug!("rendering {}x{} with {} samples per pixel in {} tiles{}", self.hsize, self.vsize, self.samples,
+            tiles.len(), if adaptive.is_some() { " (adaptive)" } else { "" });
+        let mut status = Progress {pixels_done: 0, total_pixels: width * self.vsize as usize,
+            tiles_done: 0, total_tiles: tiles.len(), elapsed: start.elapsed()};
+        for tile in tiles {
+            if self.is_cancelled() {
+                break;
+            }
+            // a panicking shader only loses its own tile: it is shaded again,
+            // and if that fails too it is filled with ERROR_COLOR while the
+            // rest of the frame renders
+            let current = Cell::new((tile.x, tile.y));
+            let shade_tile = || {
+                let mut colors = Vec::with_capacity(tile.width * tile.height);
+                for (x, y) in tile.pixels_in(self.pixel_order) {
+                    current.set((x, y));
+                    let color = match &adaptive {
+                        Some((centres, refine)) if !refine[y * width + x] => centres[y * width + x].clone(),
+                        _ => self.sample_pixel(x, y, &color_at)
+                    };
+                    colors.push((x, y, color));
+                }
+                colors
+            };
+            let mut shaded = None;
+            for attempt in 1..=TILE_ATTEMPTS {
+                match catch_shading_panic(shade_tile) {
+                    Ok(colors) => {
+                        shaded = Some(colors);
+                        break;
+                    },
+                    Err(cause) => {
+                        let (x, y) = current.get();
+                        let message = panic_message(cause.as_ref());
+                        if attempt < TILE_ATTEMPTS {
+                            log::warn!("shading pixel ({}, {}) panicked: {}; retrying tile at ({}, {})",
+                                x, y, message, tile.x, tile.y);
+                        } else {
+                            log::error!("shading pixel ({}, {}) panicked: {}; tile at ({}, {}) filled with the error colour",
+                                x, y, message, tile.x, tile.y);
+                        }
+                    }
+                }
+            }
+            match shaded {
+                Some(colors) => for (x, y, color) in colors {
+                    image.write_pixel(x, y, color);
+                },
+                None => for (x, y) in tile.pixels() {
+                    image.write_pixel(x, y, ERROR_COLOR);
+                }
+            }
+            status.pixels_done += tile.width * tile.height;
+            status.tiles_done += 1;
+            status.elapsed = start.elapsed();
+            log::trace!("tile at ({}, {}) done, {}/{}", tile.x, tile.y, status.tiles_done, status.total_tiles);
+            progress.update(&status);
+        }
+        log::debug!("rendered {}/{} tiles in {:?}", status.tiles_done, status.total_tiles, start.elapsed());
+        return image;
+    }
+
+    // renders `margin` extra pixels around the frame so `post` (blur, bloom,
+    // distortion) has real image data at the edges, then crops back to the
+    // camera's resolution. None if the margin is too large (see overscanned)
+    pub fn render_overscan<F: Fn(&Ray) -> Color, P: FnOnce(Canvas) -> Canvas>(&self, margin: u16, color_at: F, post: P) -> Option<Canvas> {
+        let image = post(self.overscanned(margin)?.render(color_at));
+        let frame = Tile {x: margin as usize, y: margin as usize, width: self.hsize as usize, height: self.vsize as usize};
+        return Some(image.crop(&frame));
+    }
+
+    // times `sample_tiles` tiles spread over the image and extrapolates to the
+    // whole frame; every sampled pixel gets the full N x N rays, so adaptive
+    // renders should finish under the estimate
+    pub fn estimate_render_time<F: Fn(&Ray) -> Color>(&self, color_at: F, sample_tiles: usize) -> Duration {
+        let tiles = self.tiles();
+        if tiles.is_empty() || sample_tiles == 0 {
+            return Duration::ZERO;
+        }
+        let stride = (tiles.len() / sample_tiles).max(1);
+        let start = Instant::now();
+        let mut pixels = 0;
+        for tile in tiles.iter().step_by(stride).take(sample_tiles) {
+            for (x, y) in tile.pixels() {
+                self.sample_pixel(x, y, &color_at);
+            }
+            pixels += tile.width * tile.height;
+        }
+        let total = self.hsize as usize * self.vsize as usize;
+        return start.elapsed().mul_f64(total as f64 / pixels as f64);
+    }
+
+    // renders one sample per pixel per pass and hands the running average to
+    // `on_pass` after each one; the sub-pixel positions are the ones render()
+    // would use, so passes == samples * samples ends on the same image. Each
+    // pixel visits its strata in its own shuffled order, so stopping early,
+    // or at a pass count that isn't a square, leaves no part of the pixel
+    // consistently unsampled. Foveation shrinks a pixel's grid in proportion to samples_at(), and
+    // pixels adaptive sampling leaves alone keep their centre colour; both
+    // stop refining once their own grid is used up
+    pub fn render_progressive<F, P>(&self, passes: usize, color_at: F, mut on_pass: P) -> Canvas
+    where F: Fn(&Ray) -> Color, P: FnMut(usize, &Canvas) {
+        let width = self.hsize as usize;
+        let mut image = Canvas::new(self.hsize, self.vsize);
+        let mut sums = vec![Color::black(); width * self.vsize as usize];
+        let n = (passes as f32).sqrt().ceil() as usize;
+        let adaptive = self.adaptive_centres(&color_at);
+        let mut grids = Vec::with_capacity(sums.len());
+        for y in 0..self.vsize as usize {
+            for x in 0..width {
+                let grid = match &adaptive {
+                    Some((centres, refine)) if !refine[y * width + x] => {
+                        image.write_pixel(x, y, centres[y * width + x].clone());
+                        0
+                    },
+                    _ => ((n * self.samples_at(x, y) as usize) as f32 / self.samples as f32).round().max(1.0) as usize
+                };
+                grids.push(grid);
+            }
+        }
+        let tiles = self.tiles();
+        for pass in 0..passes {
+            for tile in &tiles {
+                if self.is_cancelled() {
+                    return image;
+                }
+                for (x, y) in tile.pixels_in(self.pixel_order) {
+                    let idx = y * width + x;
+                    let grid = grids[idx];
+                    if pass >= grid * grid {
+                        continue;
+                    }
+                    let stratum = sampler::permute(pass, grid * grid, Rng::mix(self.pixel_rng(x, y).next_u64()));
+                    let sample = color_at(&self.sample_ray(x, y, &self.pixel_sample(x, y, grid, stratum)));
+                    watchdog::check_color(x, y, &sample);
+                    sums[idx] += sample;
+                    image.write_pixel(x, y, &sums[idx] * (1.0 / (pass + 1) as f32));
+                }
+            }
+            log::debug!("progressive pass {}/{} done", pass + 1, passes);
+            on_pass(pass + 1, &image);
+        }
+        return image;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::utils;
+    use super::super::transform::TransformBuilder;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_1_SQRT_2};
+
+    #[test]
+    fn test_new_camera() {
+        let c = Camera::new(160, 120, FRAC_PI_2);
+        assert_eq!(c.hsize(), 160);
+        assert_eq!(c.vsize(), 120);
+        assert_eq!(c.field_of_view(), FRAC_PI_2);
+        assert_eq!(*c.transform(), Matrix4::identity());
+        assert_eq!(c.samples(), 1);
+        assert_eq!(c.adaptive_threshold(), None);
+        assert_eq!(c.sampler(), Sampler::Uniform);
+        assert_eq!(c.tile_size(), 32);
+        assert_eq!(c.tile_order(), TileOrder::Scanline);
+        assert_eq!(c.pixel_order(), PixelOrder::Scanline);
+    }
+
+    #[test]
+    fn test_pixel_size() {
+        let c = Camera::new(200, 125, FRAC_PI_2);
+        assert!(utils::is_equal(c.pixel_size(), 0.01));
+        let c = Camera::new(125, 200, FRAC_PI_2);
+        assert!(utils::is_equal(c.pixel_size(), 0.01));
+    }
+
+    #[test]
+    fn test_ray_through_center() {
+        let c = Camera::new(201, 101, FRAC_PI_2);
+        let r = c.ray_for_pixel(100, 50);
+        assert_eq!(r.origin(), Tuple::point(0.0, 0.0, 0.0));
+        assert_eq!(r.direction(), Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_ray_through_corner() {
+        let c = Camera::new(201, 101, FRAC_PI_2);
+        let r = c.ray_for_pixel(0, 0);
+        assert_eq!(r.origin(), Tuple::point(0.0, 0.0, 0.0));
+        assert_eq!(r.direction(), Tuple::vector(0.66519, 0.33259, -0.66851));
+    }
+
+    #[test]
+    fn test_ray_transformed_camera() {
+        let mut c = Camera::new(201, 101, FRAC_PI_2);
+        c.set_transform(TransformBuilder::new4().translate(0.0, -2.0, 5.0).rotate_y(FRAC_PI_4).build());
+        let r = c.ray_for_pixel(100, 50);
+        assert_eq!(r.origin(), Tuple::point(0.0, 2.0, -5.0));
+        assert_eq!(r.direction(), Tuple::vector(FRAC_1_SQRT_2, 0.0, -FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn test_rays_for_pixel() {
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        assert_eq!(c.rays_for_pixel(5, 5), vec![c.ray_for_pixel(5, 5)]);
+        c.set_samples(0);
+        assert_eq!(c.samples(), 1);
+        c.set_samples(4);
+        let rays = c.rays_for_pixel(5, 5);
+        assert_eq!(rays.len(), 16);
+        // sub-pixel rays stay inside the pixel and straddle its centre
+        let centre = c.ray_for_pixel(5, 5);
+        for r in &rays {
+            assert!(r.direction().x().abs() < c.pixel_size() / 2.0);
+            assert!(r.direction().y().abs() < c.pixel_size() / 2.0);
+            assert_ne!(*r, centre);
+            assert!(r.is_primary());
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let c = Camera::new(4, 3, FRAC_PI_2);
+        let image = c.render(|_| Color::new(0.5, 0.25, 1.0));
+        assert_eq!(image.width(), 4);
+        assert_eq!(image.height(), 3);
+    }
+
+    #[test]
+    fn test_sample_pixel_antialiased_edge() {
+        // rays with positive x (the left of the image) see white, so the centre column straddles the edge
+        let mut c = Camera::new(3, 1, FRAC_PI_2);
+        let white = |r: &Ray| if r.direction().x() > 0.0 { Color::new(1.0, 1.0, 1.0) } else { Color::new(0.0, 0.0, 0.0) };
+        assert_eq!(c.sample_pixel(0, 0, &white), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(c.sample_pixel(2, 0, &white), Color::new(0.0, 0.0, 0.0));
+        c.set_samples(4);
+        assert_eq!(c.sample_pixel(0, 0, &white), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(c.sample_pixel(1, 0, &white), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(c.sample_pixel(2, 0, &white), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_needs_refinement() {
+        let c = Camera::new(3, 2, FRAC_PI_2);
+        let grey = Color::new(0.05, 0.05, 0.05);
+        let white = Color::new(1.0, 1.0, 1.0);
+        let centres = vec![Color::black(), grey, Color::black(),
+            Color::black(), Color::black(), white];
+        let refine = c.needs_refinement(&centres, 0.1);
+        assert_eq!(refine, vec![false, false, true,
+            false, true, true]);
+    }
+
+    #[test]
+    fn test_render_adaptive_counts_rays() {
+        use std::cell::Cell;
+        let mut c = Camera::new(3, 1, FRAC_PI_2);
+        c.set_samples(4);
+        c.set_adaptive_threshold(Some(0.1));
+        let rays = Cell::new(0);
+        let flat = |_: &Ray| { rays.set(rays.get() + 1); Color::new(0.2, 0.2, 0.2) };
+        c.render(flat);
+        // a flat image never needs more than the centre rays
+        assert_eq!(rays.get(), 3);
+        rays.set(0);
+        let edge = |r: &Ray| {
+            rays.set(rays.get() + 1);
+            if r.direction().x() > 0.0 { Color::new(1.0, 1.0, 1.0) } else { Color::black() }
+        };
+        c.render(edge);
+        // only the white pixel and its neighbour are supersampled
+        assert_eq!(rays.get(), 3 + 2 * 16);
+    }
+
+    #[test]
+    fn test_jittered_rays_are_reproducible() {
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_samples(3);
+        c.set_sampler(Sampler::Jittered);
+        c.set_seed(1234);
+        let rays = c.rays_for_pixel(5, 5);
+        assert_eq!(rays.len(), 9);
+        assert_eq!(rays, c.rays_for_pixel(5, 5));
+        assert_ne!(rays, c.rays_for_pixel(4, 5));
+        for r in &rays {
+            assert!(r.direction().x().abs() < c.pixel_size() / 2.0);
+            assert!(r.direction().y().abs() < c.pixel_size() / 2.0);
+        }
+        c.set_seed(4321);
+        assert_ne!(rays, c.rays_for_pixel(5, 5));
+    }
+
+    #[test]
+    fn test_render_visits_tiles_in_order() {
+        use std::cell::RefCell;
+        let mut c = Camera::new(4, 4, FRAC_PI_2);
+        c.set_tile_size(2);
+        c.set_tile_order(TileOrder::Morton);
+        assert_eq!(c.tiles().len(), 4);
+        let first = c.ray_for_pixel(0, 0);
+        let after_first_tile = c.ray_for_pixel(2, 0);
+        let visited = RefCell::new(Vec::new());
+        c.render(|r| { visited.borrow_mut().push(*r.direction()); Color::black() });
+        let visited = visited.into_inner();
+        assert_eq!(visited.len(), 16);
+        assert_eq!(visited[0], *first.direction());
+        // the whole 2 x 2 top-left tile is done before moving right
+        assert_eq!(visited[4], *after_first_tile.direction());
+    }
+
+    #[test]
+    fn test_render_progressive() {
+        use std::cell::Cell;
+        let mut c = Camera::new(3, 1, FRAC_PI_2);
+        c.set_samples(2);
+        let rays = Cell::new(0);
+        let white = |r: &Ray| {
+            rays.set(rays.get() + 1);
+            if r.direction().x() > 0.0 { Color::new(1.0, 1.0, 1.0) } else { Color::black() }
+        };
+        let mut passes = Vec::new();
+        let image = c.render_progressive(4, white, |pass, canvas| passes.push((pass, canvas.gen_ppm_body())));
+        assert_eq!(rays.get(), 12);
+        assert_eq!(passes.iter().map(|p| p.0).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert_eq!(passes[3].1, image.gen_ppm_body());
+        assert_eq!(image.gen_ppm_body(), c.render(white).gen_ppm_body());
+        // the first pass sees only one side of the centre pixel
+        assert_ne!(passes[0].1, image.gen_ppm_body());
+    }
+
+    #[test]
+    fn test_progressive_spreads_a_non_square_pass_count() {
+        use std::cell::RefCell;
+        let mut c = Camera::new(4, 4, FRAC_PI_2);
+        c.set_samples(3);
+        let rays = RefCell::new(Vec::new());
+        c.render_progressive(5, |r| { rays.borrow_mut().push(*r.direction()); Color::black() }, |_, _| {});
+        let rays = rays.into_inner();
+        assert_eq!(rays.len(), 16 * 5);
+        let mut rows_used = [0; 3];
+        for y in 0..4 {
+            for x in 0..4 {
+                let strata: Vec<usize> = c.rays_for_pixel(x, y).iter()
+                    .enumerate().filter(|(_, r)| rays.contains(r.direction())).map(|(k, _)| k).collect();
+                assert_eq!(strata.len(), 5);
+                for k in strata {
+                    rows_used[k / 3] += 1;
+                }
+            }
+        }
+        // 5 of 9 strata in order would never reach the bottom row
+        assert!(rows_used.iter().all(|&n| n > 0));
+    }
+
+    #[test]
+    fn test_render_reports_progress() {
+        let mut c = Camera::new(5, 3, FRAC_PI_2);
+        c.set_tile_size(2);
+        let mut updates = Vec::new();
+        c.render_with_progress(|_| Color::black(), &mut |p: &Progress| updates.push(p.clone()));
+        assert_eq!(updates.len(), 6);
+        assert_eq!(updates[0].pixels_done, 4);
+        assert_eq!(updates[0].total_tiles, 6);
+        assert_eq!(updates[2].pixels_done, 10);
+        let last = updates.last().unwrap();
+        assert_eq!(last.tiles_done, 6);
+        assert_eq!(last.pixels_done, last.total_pixels);
+        assert_eq!(last.fraction(), 1.0);
+    }
+
+    #[test]
+    fn test_cancelled_render_stops_between_tiles() {
+        use std::cell::Cell;
+        let mut c = Camera::new(4, 4, FRAC_PI_2);
+        c.set_tile_size(2);
+        let token = CancellationToken::new();
+        c.set_cancellation_token(Some(token.clone()));
+        let rays = Cell::new(0);
+        let mut tiles_done = 0;
+        c.render_with_progress(|_| { rays.set(rays.get() + 1); Color::new(1.0, 1.0, 1.0) }, &mut |p: &Progress| {
+            tiles_done = p.tiles_done;
+            if p.tiles_done == 1 {
+                token.cancel();
+            }
+        });
+        assert_eq!(tiles_done, 1);
+        assert_eq!(rays.get(), 4);
+        // an already cancelled token renders nothing
+        rays.set(0);
+        c.render(|_| { rays.set(rays.get() + 1); Color::black() });
+        assert_eq!(rays.get(), 0);
+        let mut passes = 0;
+        c.render_progressive(4, |_| Color::black(), |_, _| passes += 1);
+        assert_eq!(passes, 0);
+    }
+
+    #[test]
+    fn test_render_morton_pixel_order() {
+        use std::cell::RefCell;
+        let mut c = Camera::new(4, 4, FRAC_PI_2);
+        c.set_pixel_order(PixelOrder::Morton);
+        let expected: Vec<Tuple> = [(0, 0), (1, 0), (0, 1), (1, 1), (2, 0)].iter()
+            .map(|&(x, y)| *c.ray_for_pixel(x, y).direction()).collect();
+        let visited = RefCell::new(Vec::new());
+        c.render(|r| { visited.borrow_mut().push(*r.direction()); Color::black() });
+        assert_eq!(visited.into_inner()[..5], expected[..]);
+    }
+
+    #[test]
+    fn test_scaled_camera() {
+        let mut c = Camera::new(200, 100, FRAC_PI_2);
+        c.set_samples(3);
+        c.set_transform(TransformBuilder::new4().translate(0.0, 1.0, 0.0).build());
+        let half = c.scaled(0.5);
+        assert_eq!((half.hsize(), half.vsize()), (100, 50));
+        assert_eq!(half.samples(), 3);
+        assert_eq!(half.transform(), c.transform());
+        assert!(utils::is_equal(half.pixel_size(), c.pixel_size() * 2.0));
+        assert_eq!(half.ray_for_pixel(50, 25), c.ray_through(101.0, 51.0).as_primary());
+        assert_eq!(c.scaled(0.0).hsize(), 1);
+    }
+
+    #[test]
+    fn test_estimate_render_time_samples_few_tiles() {
+        use std::cell::Cell;
+        let mut c = Camera::new(8, 8, FRAC_PI_2);
+        c.set_tile_size(2);
+        c.set_samples(2);
+        let rays = Cell::new(0);
+        let estimate = c.estimate_render_time(|_| { rays.set(rays.get() + 1); Color::black() }, 3);
+        assert_eq!(rays.get(), 3 * 4 * 4);
+        assert!(estimate < Duration::from_secs(1));
+        assert_eq!(c.estimate_render_time(|_| Color::black(), 0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_panicking_tile_is_isolated() {
+        let mut c = Camera::new(8, 4, FRAC_PI_2);
+        c.set_tile_size(4);
+        // rays through the left half of the image have positive x
+        let canvas = c.render(|r| {
+            if r.direction().x() > 0.0 {
+                panic!("bad shader");
+            }
+            Color::WHITE
+        });
+        assert_eq!(canvas.pixel_at(0, 0), Some(&ERROR_COLOR));
+        assert_eq!(canvas.pixel_at(3, 3), Some(&ERROR_COLOR));
+        assert_eq!(canvas.pixel_at(4, 0), Some(&Color::WHITE));
+        assert_eq!(canvas.pixel_at(7, 3), Some(&Color::WHITE));
+    }
+
+    #[test]
+    fn test_panicking_tile_is_retried() {
+        let mut c = Camera::new(8, 4, FRAC_PI_2);
+        c.set_tile_size(4);
+        // only the very first pixel shaded fails, and only once
+        let failed = Cell::new(false);
+        let canvas = c.render(|_| {
+            if !failed.replace(true) {
+                panic!("flaky shader");
+            }
+            Color::WHITE
+        });
+        assert!(failed.get());
+        assert_eq!(canvas.pixel_at(0, 0), Some(&Color::WHITE));
+        assert_eq!(canvas.pixel_at(3, 3), Some(&Color::WHITE));
+    }
+
+    #[test]
+    fn test_panic_in_adaptive_prepass() {
+        let mut c = Camera::new(4, 4, FRAC_PI_2);
+        c.set_samples(2);
+        c.set_adaptive_threshold(Some(0.1));
+        c.set_tile_size(2);
+        let canvas = c.render(|r| {
+            if r.direction().x() > 0.0 && r.direction().y() > 0.0 {
+                panic!("bad shader");
+            }
+            Color::WHITE
+        });
+        assert_eq!(canvas.pixel_at(0, 0), Some(&ERROR_COLOR));
+        assert_eq!(canvas.pixel_at(3, 3), Some(&Color::WHITE));
+    }
+
+    #[test]
+    fn test_overscanned_rays_line_up() {
+        let mut c = Camera::new(20, 10, FRAC_PI_2);
+        c.set_transform(TransformBuilder::new4().rotate_y(0.3).translate(1.0, 2.0, 3.0).build());
+        let o = c.overscanned(4).unwrap();
+        assert_eq!((o.hsize(), o.vsize()), (28, 18));
+        assert!(c.overscanned(u16::MAX / 2).is_none());
+        assert!(c.overscanned(u16::MAX).is_none());
+        assert!(utils::is_equal(o.pixel_size(), c.pixel_size()));
+        for &(x, y) in [(0, 0), (19, 9), (7, 3)].iter() {
+            let expected = c.ray_for_pixel(x, y);
+            let r = o.ray_for_pixel(x + 4, y + 4);
+            assert_eq!(r.origin(), expected.origin());
+            assert_eq!(r.direction(), expected.direction());
+        }
+        // the wider view is reflected in the field of view
+        assert!(o.field_of_view() > c.field_of_view());
+        assert!(utils::is_equal(Camera::new(28, 18, o.field_of_view()).pixel_size(), o.pixel_size()));
+    }
+
+    #[test]
+    fn test_render_overscan() {
+        let c = Camera::new(6, 4, FRAC_PI_2);
+        let mut seen = (0, 0);
+        let image = c.render_overscan(2, |_| Color::WHITE, |canvas| {
+            seen = (canvas.width(), canvas.height());
+            canvas
+        }).unwrap();
+        assert_eq!(seen, (10, 8));
+        assert_eq!((image.width(), image.height()), (6, 4));
+        assert_eq!(image.pixel_at(5, 3), Some(&Color::WHITE));
+        assert!(c.render_overscan(u16::MAX, |_| Color::WHITE, |canvas| canvas).is_none());
+    }
+
+    #[test]
+    fn test_depth_of_field_rays_converge_on_focal_plane() {
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_samples(3);
+        c.set_sampler(Sampler::Jittered);
+        c.set_aperture(0.2);
+        c.set_focal_distance(4.0);
+        let pinhole = c.ray_through(3.5, 7.5);
+        let focus = pinhole.position(4.0 / -pinhole.direction().z());
+        let rays = c.rays_for_pixel(3, 7);
+        assert_eq!(rays.len(), 9);
+        let mut spread = false;
+        for r in &rays {
+            let o = r.origin();
+            assert!(o.z().abs() < 1e-5 && (o.x() * o.x() + o.y() * o.y()).sqrt() <= 0.2 + 1e-5);
+            spread |= o.x().abs() > 1e-3;
+            // jitter moves each ray within the pixel, so compare on the
+            // focal plane within a pixel's footprint there
+            let p = r.position(4.0 / -r.direction().z());
+            assert!((p.x() - focus.x()).abs() <= 4.0 * c.pixel_size() && (p.y() - focus.y()).abs() <= 4.0 * c.pixel_size());
+        }
+        assert!(spread);
+    }
+
+    #[test]
+    fn test_depth_of_field_with_uniform_samples_hits_focus_exactly() {
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_aperture(0.5);
+        c.set_focal_distance(2.0);
+        let pinhole = c.ray_for_pixel(5, 5);
+        let r = &c.rays_for_pixel(5, 5)[0];
+        assert_eq!(r.position(2.0 / -r.direction().z()), pinhole.position(2.0 / -pinhole.direction().z()));
+        // closing the aperture gives the pinhole ray back
+        c.set_aperture(0.0);
+        let r = &c.rays_for_pixel(5, 5)[0];
+        assert_eq!(r.origin(), pinhole.origin());
+        assert_eq!(r.direction(), pinhole.direction());
+    }
+
+    #[test]
+    fn test_singular_view_transform_is_rejected() {
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        let view = Matrix4::translation(0.0, 0.0, -5.0);
+        assert!(c.set_transform(view).is_some());
+        assert!(c.set_transform(Matrix4::scaling(1.0, 0.0, 1.0)).is_none());
+        assert_eq!(c.transform(), &view);
+    }
+
+    #[test]
+    fn test_focal_distance_stays_positive() {
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        c.set_aperture(0.5);
+        for distance in [0.0, -2.0, f32::NAN] {
+            c.set_focal_distance(distance);
+            assert!(c.focal_distance() > 0.0);
+            let r = &c.rays_for_pixel(5, 5)[0];
+            assert!(r.direction().magnitude().is_finite());
+        }
+    }
+
+    #[test]
+    fn test_progressive_matches_render_with_depth_of_field() {
+        let mut c = Camera::new(6, 4, FRAC_PI_2);
+        c.set_samples(2);
+        c.set_sampler(Sampler::Random);
+        c.set_aperture(0.3);
+        c.set_focal_distance(3.0);
+        let shade = |r: &Ray| Color::new(r.origin().x().abs(), r.direction().y().abs(), 0.5);
+        let full = c.render(shade);
+        let progressive = c.render_progressive(4, shade, |_, _| {});
+        assert_eq!(full.gen_ppm_body(), progressive.gen_ppm_body());
+    }
+
+    #[test]
+    fn test_progressive_matches_adaptive_and_foveated_render() {
+        use std::cell::Cell;
+        let mut c = Camera::new(12, 8, FRAC_PI_2);
+        c.set_samples(3);
+        c.set_sampler(Sampler::Jittered);
+        let shade = |r: &Ray| if r.direction().x() > 0.1 { Color::WHITE } else { Color::new(0.2, 0.4, 0.6) };
+        c.set_adaptive_threshold(Some(0.1));
+        assert_eq!(c.render(shade).gen_ppm_body(), c.render_progressive(9, shade, |_, _| {}).gen_ppm_body());
+        c.set_adaptive_threshold(None);
+        c.set_foveation(Some(Foveation {centre: (0.5, 0.5), radius: 0.1, falloff: 0.2, peripheral_samples: 1}));
+        assert_eq!(c.render(shade).gen_ppm_body(), c.render_progressive(9, shade, |_, _| {}).gen_ppm_body());
+        // peripheral pixels stop after their single sample
+        let rays = Cell::new(0);
+        c.render_progressive(9, |_| { rays.set(rays.get() + 1); Color::black() }, |_, _| {});
+        let expected: usize = (0..8).flat_map(|y| (0..12).map(move |x| (x, y)))
+            .map(|(x, y)| (c.samples_at(x, y) as usize).pow(2)).sum();
+        assert_eq!(rays.get(), expected);
+    }
+
+    #[test]
+    fn test_shutter_spreads_ray_times() {
+        let mut c = Camera::new(5, 5, FRAC_PI_2);
+        c.set_samples(4);
+        let rays = c.rays_for_pixel(2, 2);
+        assert!(rays.iter().all(|r| r.time() == 0.0));
+        c.set_shutter(0.0, 1.0);
+        let times: Vec<f32> = c.rays_for_pixel(2, 2).iter().map(|r| r.time()).collect();
+        assert!(times.iter().all(|t| (0.0..1.0).contains(t)));
+        assert!(times.iter().any(|t| *t != times[0]));
+        c.set_shutter(0.5, 0.2);
+        assert_eq!(c.shutter(), (0.5, 0.5));
+    }
+
+    #[test]
+    fn test_motion_blur_averages_over_shutter() {
+        use super::super::sphere::Sphere;
+        use super::super::intersection::Intersect;
+        let mut c = Camera::new(21, 21, FRAC_PI_2);
+        c.set_transform(Matrix4::view_transform(&Tuple::point(0.0, 0.0, -5.0),
+            &Tuple::point(0.0, 0.0, 0.0), &Tuple::vector(0.0, 1.0, 0.0)));
+        c.set_samples(8);
+        c.set_sampler(Sampler::Jittered);
+        c.set_shutter(0.0, 1.0);
+        let mut s = Sphere::new(1);
+        s.set_transform(TransformBuilder::new4().translate(-2.0, 0.0, 0.0).build());
+        s.set_motion(TransformBuilder::new4().translate(2.0, 0.0, 0.0).build());
+        let image = c.render(|r| if s.intersect(r).hit().is_some() { Color::WHITE } else { Color::black() });
+        // the centre is covered for part of the shutter only, so it is grey
+        let centre = image.pixel_at(10, 10).unwrap().red();
+        assert!(centre > 0.1 && centre < 0.9);
+    }
+
+    #[test]
+    fn test_orthographic_rays_are_parallel() {
+        let c = Camera::orthographic(20, 10, 4.0);
+        assert_eq!(c.projection(), Projection::Orthographic {view_width: 4.0});
+        assert!(utils::is_equal(c.pixel_size(), 0.2));
+        let centre = c.ray_through(10.0, 5.0);
+        assert_eq!(centre.origin(), Tuple::point(0.0, 0.0, 0.0));
+        assert_eq!(centre.direction(), Tuple::vector(0.0, 0.0, -1.0));
+        let corner = c.ray_for_pixel(0, 0);
+        assert_eq!(corner.origin(), Tuple::point(1.9, 0.9, 0.0));
+        assert_eq!(corner.direction(), Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_orthographic_transformed_and_projected() {
+        let mut c = Camera::orthographic(11, 11, 2.0);
+        c.set_transform(Matrix4::view_transform(&Tuple::point(0.0, 0.0, -5.0),
+            &Tuple::point(0.0, 0.0, 0.0), &Tuple::vector(0.0, 1.0, 0.0)));
+        let r = c.ray_for_pixel(2, 7);
+        assert_eq!(r.direction(), Tuple::vector(0.0, 0.0, 1.0));
+        // size on screen doesn't change with distance
+        let near = c.project(&r.position(1.0)).unwrap();
+        let far = c.project(&r.position(30.0)).unwrap();
+        assert!((near.0 - 2.5).abs() < 1e-3 && (near.1 - 7.5).abs() < 1e-3);
+        assert!((far.0 - near.0).abs() < 1e-3 && (far.1 - near.1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_orthographic_scaled_and_overscanned() {
+        let c = Camera::orthographic(20, 10, 4.0);
+        let half = c.scaled(0.5);
+        assert_eq!(half.projection(), Projection::Orthographic {view_width: 4.0});
+        assert!(utils::is_equal(half.pixel_size(), 0.4));
+        let o = c.overscanned(5).unwrap();
+        assert_eq!(o.projection(), Projection::Orthographic {view_width: 6.0});
+        assert_eq!(o.ray_for_pixel(5, 5).origin(), c.ray_for_pixel(0, 0).origin());
+    }
+
+    #[test]
+    fn test_foveated_sample_counts() {
+        let mut c = Camera::new(20, 10, FRAC_PI_2);
+        c.set_samples(4);
+        assert_eq!(c.samples_at(0, 0), 4);
+        c.set_foveation(Some(Foveation {centre: (0.5, 0.5), radius: 0.1, falloff: 0.2, peripheral_samples: 1}));
+        assert_eq!(c.samples_at(10, 5), 4);
+        assert_eq!(c.samples_at(0, 0), 1);
+        assert_eq!(c.rays_for_pixel(0, 0).len(), 1);
+        assert_eq!(c.rays_for_pixel(10, 5).len(), 16);
+        // counts step down smoothly across the falloff
+        let counts: Vec<u16> = (10..20).map(|x| c.samples_at(x, 5)).collect();
+        assert!(counts.windows(2).all(|w| w[0] >= w[1]));
+        assert!(counts.iter().any(|&n| n > 1 && n < 4));
+        // peripheral counts never exceed the full sample count
+        c.set_foveation(Some(Foveation {centre: (0.0, 0.0), radius: 0.0, falloff: 0.0, peripheral_samples: 9}));
+        assert_eq!(c.samples_at(19, 9), 4);
+    }
+
+    #[test]
+    fn test_foveated_render_traces_fewer_rays() {
+        use std::cell::Cell;
+        let mut c = Camera::new(8, 8, FRAC_PI_2);
+        c.set_samples(3);
+        let rays = Cell::new(0);
+        let count = |_: &Ray| { rays.set(rays.get() + 1); Color::WHITE };
+        c.render(count);
+        assert_eq!(rays.get(), 64 * 9);
+        rays.set(0);
+        c.set_foveation(Some(Foveation {centre: (0.5, 0.5), radius: 0.2, falloff: 0.1, peripheral_samples: 1}));
+        let image = c.render(count);
+        assert!(rays.get() < 64 * 9 && rays.get() > 64);
+        assert_eq!(image.pixel_at(0, 0), Some(&Color::WHITE));
+    }
+
+    #[test]
+    fn test_trace_pixel_debug() {
+        let mut c = Camera::new(3, 1, FRAC_PI_2);
+        c.set_samples(2);
+        // a stand-in integrator: one object test, then a shadow ray for hits;
+        // the centre column stays dark so only its neighbour on the left differs
+        let shade = |r: &Ray, log: &mut TraceLog| {
+            if r.direction().x() > 0.1 {
+                log.object_test(1, Some(2.0));
+                log.hit(1, 2.0, r.position(2.0), Tuple::vector(0.0, 0.0, -1.0));
+                log.shadow_ray(Ray::new(r.position(2.0), Tuple::vector(0.0, 1.0, 0.0)), false);
+                return Color::WHITE;
+            }
+            log.object_test(1, None);
+            Color::BLACK
+        };
+        let trace = c.trace_pixel_debug(1, 0, shade);
+        assert_eq!((trace.x, trace.y), (1, 0));
+        assert_eq!(trace.samples.len(), 4);
+        assert_eq!(trace.samples[0].ray, c.rays_for_pixel(1, 0)[0]);
+        let lit: Vec<&TraceSample> = trace.samples.iter().filter(|s| s.color == Color::WHITE).collect();
+        assert_eq!(lit.len(), 2);
+        assert_eq!(lit[0].events.len(), 3);
+        assert!(matches!(lit[0].events[2], TraceEvent::ShadowRay {occluded: false, ..}));
+        // the same colour render() writes
+        let render = |c: &Camera| c.render(|r| shade(r, &mut TraceLog::disabled()));
+        assert_eq!(Some(&trace.color), render(&c).pixel_at(1, 0));
+        // a flat pixel under adaptive sampling only gets its centre ray
+        c.set_adaptive_threshold(Some(0.1));
+        let trace = c.trace_pixel_debug(2, 0, shade);
+        assert_eq!(trace.samples.len(), 1);
+        assert_eq!(trace.samples[0].ray, c.ray_for_pixel(2, 0));
+        assert_eq!(Some(&trace.color), render(&c).pixel_at(2, 0));
+        assert_eq!(c.trace_pixel_debug(1, 0, shade).samples.len(), 4);
+    }
+}
\ No newline at end of file
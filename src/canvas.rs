@@ -1,16 +1,137 @@
-use super::color::Color;
+use super::color::{Color, Encoding};
+use super::tile::Tile;
 use std::fs;
+use std::io::{Error, ErrorKind, Read};
+use std::path::Path;
+
+// Splits a PPM header into whitespace separated tokens, skipping comments
+struct PpmTokens<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> PpmTokens<'a> {
+    fn next_token(&mut self) -> std::io::Result<&'a str> {
+        loop {
+            match self.data.get(self.pos) {
+                Some(b'#') => {
+                    while self.pos < self.data.len() && self.data[self.pos] != b'\n' {
+                        self.pos += 1;
+                    }
+                },
+                Some(c) if c.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+                None => return Err(Error::new(ErrorKind::UnexpectedEof, "PPM data ended early"))
+            }
+        }
+        let start = self.pos;
+        while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "invalid PPM token"))
+    }
+
+    fn next_number<T: std::str::FromStr>(&mut self) -> std::io::Result<T> {
+        let token = self.next_token()?;
+        token.parse().map_err(|_| Error::new(ErrorKind::InvalidData, format!("invalid number '{}' in PPM", token)))
+    }
+}
+
+// how blit() combines a source pixel with the one already on the canvas;
+// the draw's alpha fades the effect in from "leave the canvas unchanged"
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BlendMode {
+    Over,
+    Add,
+    Multiply
+}
+
+impl BlendMode {
+    pub fn blend(&self, dst: &Color, src: &Color, alpha: f32) -> Color {
+        match self {
+            BlendMode::Over => dst * (1.0 - alpha) + src * alpha,
+            BlendMode::Add => dst + src * alpha,
+            BlendMode::Multiply => dst * (1.0 - alpha) + dst * src * alpha
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ResizeFilter {
+    // averages every source pixel under the destination pixel, the right
+    // choice for downscaling supersampled renders
+    Box,
+    // interpolates the four nearest source pixels
+    Bilinear
+}
 
 pub struct Canvas {
     width: u16,
     height: u16,
-    pixels: Vec<Color>
+    pixels: Vec<Color>,
+    encoding: Encoding
 }
 
 impl Canvas {
     pub fn new(width: u16, height: u16) -> Self {
-        let pixels = vec![Color::new(0.0, 0.0, 0.0); height as usize * width as usize];
-        Self {width, height, pixels}
+        let pixels = vec![Color::BLACK; height as usize * width as usize];
+        Self {width, height, pixels, encoding: Encoding::Srgb}
+    }
+
+    // Parses P3 (ASCII) or P6 (binary) PPM, scaling samples by the max value
+    pub fn from_ppm<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let mut tokens = PpmTokens {data: &data, pos: 0};
+        let magic = tokens.next_token()?;
+        if magic != "P3" && magic != "P6" {
+            return Err(Error::new(ErrorKind::InvalidData, format!("unsupported PPM magic '{}'", magic)));
+        }
+        let binary = magic == "P6";
+        let width: u16 = tokens.next_number()?;
+        let height: u16 = tokens.next_number()?;
+        let max_value: u16 = tokens.next_number()?;
+        if max_value == 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "PPM max value must be positive"));
+        }
+        let scale = max_value as f32;
+        let num_samples = width as usize * height as usize * 3;
+        let mut samples = Vec::with_capacity(num_samples);
+        if binary {
+            // a single whitespace byte separates the header from the raster
+            let start = tokens.pos + 1;
+            let sample_size = if max_value < 256 { 1 } else { 2 };
+            let raster = data.get(start..start + num_samples * sample_size)
+                .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "PPM data ended early"))?;
+            for sample in raster.chunks(sample_size) {
+                let value = if sample_size == 1 { sample[0] as u16 } else { u16::from_be_bytes([sample[0], sample[1]]) };
+                samples.push(value as f32 / scale);
+            }
+        } else {
+            for _ in 0..num_samples {
+                let value: u16 = tokens.next_number()?;
+                samples.push(value as f32 / scale);
+            }
+        }
+        let pixels = samples.chunks(3).map(|rgb| Color::new(rgb[0], rgb[1], rgb[2])).collect();
+        log::debug!("decoded {}x{} {} PPM, max value {}", width, height, magic, max_value);
+        // keep the stored values as they are so saving reproduces the file
+        Ok(Self {width, height, pixels, encoding: Encoding::Linear})
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    // Pixels hold linear colors; 8 and 16-bit outputs are encoded with this
+    // (sRGB by default). Use Encoding::Linear to write the raw values.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
+    pub(crate) fn pixels(&self) -> &[Color] {
+        &self.pixels
     }
 
     pub fn width(&self) -> u16 {
@@ -21,10 +142,136 @@ impl Canvas {
         self.height
     }
 
+    pub fn pixel_at(&self, x: usize, y: usize) -> Option<&Color> {
+        if x >= self.width as usize || y >= self.height as usize {
+            return None;
+        }
+        self.pixels.get(y * self.width as usize + x)
+    }
+
+    // top to bottom, each row left to right
+    pub fn rows(&self) -> impl Iterator<Item = &[Color]> {
+        self.pixels.chunks(self.width.max(1) as usize)
+    }
+
+    // (x, y, colour) in row-major order
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (usize, usize, &Color)> {
+        let width = self.width.max(1) as usize;
+        self.pixels.iter().enumerate().map(move |(idx, c)| (idx % width, idx / width, c))
+    }
+
+    pub fn enumerate_pixels_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut Color)> {
+        let width = self.width.max(1) as usize;
+        self.pixels.iter_mut().enumerate().map(move |(idx, c)| (idx % width, idx / width, c))
+    }
+
+    // copies `other` onto this canvas with its top-left corner at (x, y)
+    pub fn blit(&mut self, other: &Canvas, x: isize, y: isize) {
+        self.blit_blended(other, x, y, BlendMode::Over, 1.0);
+    }
+
+    // parts of `other` that fall outside this canvas are dropped
+    pub fn blit_blended(&mut self, other: &Canvas, x: isize, y: isize, mode: BlendMode, alpha: f32) {
+        for (sx, sy, src) in other.enumerate_pixels() {
+            let (dx, dy) = (x + sx as isize, y + sy as isize);
+            if dx < 0 || dy < 0 || dx >= self.width as isize || dy >= self.height as isize {
+                continue;
+            }
+            let idx = dy as usize * self.width as usize + dx as usize;
+            self.pixels[idx] = mode.blend(&self.pixels[idx], src, alpha);
+        }
+    }
+
+    // builds a canvas of the given size from a function of the destination
+    // pixel, keeping this canvas's encoding
+    fn remap<F: Fn(usize, usize) -> Color>(&self, width: u16, height: u16, f: F) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        canvas.encoding = self.encoding;
+        for (x, y, c) in canvas.enumerate_pixels_mut() {
+            *c = f(x, y);
+        }
+        return canvas;
+    }
+
+    fn at(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width as usize + x].clone()
+    }
+
+    // the part of `rect` that lies on the canvas
+    pub fn crop(&self, rect: &Tile) -> Canvas {
+        let x0 = rect.x.min(self.width as usize);
+        let y0 = rect.y.min(self.height as usize);
+        let width = rect.width.min(self.width as usize - x0);
+        let height = rect.height.min(self.height as usize - y0);
+        return self.remap(width as u16, height as u16, |x, y| self.at(x0 + x, y0 + y));
+    }
+
+    pub fn flip_horizontal(&self) -> Canvas {
+        let last = (self.width as usize).saturating_sub(1);
+        return self.remap(self.width, self.height, |x, y| self.at(last - x, y));
+    }
+
+    pub fn flip_vertical(&self) -> Canvas {
+        let last = (self.height as usize).saturating_sub(1);
+        return self.remap(self.width, self.height, |x, y| self.at(x, last - y));
+    }
+
+    // 90 degrees clockwise; the result is height x width
+    pub fn rotate_cw(&self) -> Canvas {
+        let last = (self.height as usize).saturating_sub(1);
+        return self.remap(self.height, self.width, |x, y| self.at(y, last - x));
+    }
+
+    pub fn rotate_ccw(&self) -> Canvas {
+        let last = (self.width as usize).saturating_sub(1);
+        return self.remap(self.height, self.width, |x, y| self.at(last - y, x));
+    }
+
+    pub fn rotate_180(&self) -> Canvas {
+        let (last_x, last_y) = ((self.width as usize).saturating_sub(1), (self.height as usize).saturating_sub(1));
+        return self.remap(self.width, self.height, |x, y| self.at(last_x - x, last_y - y));
+    }
+
+    pub fn resize(&self, width: u16, height: u16, filter: ResizeFilter) -> Canvas {
+        if self.pixels.is_empty() {
+            return self.remap(width, height, |_, _| Color::black());
+        }
+        let sx = self.width as f32 / width as f32;
+        let sy = self.height as f32 / height as f32;
+        match filter {
+            ResizeFilter::Box => self.remap(width, height, |x, y| {
+                let x0 = (x as f32 * sx) as usize;
+                let y0 = (y as f32 * sy) as usize;
+                // at least one source pixel, so upscaling repeats pixels
+                let x1 = (((x + 1) as f32 * sx).ceil() as usize).clamp(x0 + 1, self.width as usize);
+                let y1 = (((y + 1) as f32 * sy).ceil() as usize).clamp(y0 + 1, self.height as usize);
+                let mut sum = Color::black();
+                for j in y0..y1 {
+                    for i in x0..x1 {
+                        sum += self.at(i, j);
+                    }
+                }
+                sum / ((x1 - x0) * (y1 - y0)) as f32
+            }),
+            ResizeFilter::Bilinear => self.remap(width, height, |x, y| {
+                // pixel centres line up between the two canvases
+                let fx = ((x as f32 + 0.5) * sx - 0.5).max(0.0).min(self.width as f32 - 1.0);
+                let fy = ((y as f32 + 0.5) * sy - 0.5).max(0.0).min(self.height as f32 - 1.0);
+                let (x0, y0) = (fx as usize, fy as usize);
+                let x1 = (x0 + 1).min(self.width as usize - 1);
+                let y1 = (y0 + 1).min(self.height as usize - 1);
+                let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+                let top = self.at(x0, y0) * (1.0 - tx) + self.at(x1, y0) * tx;
+                let bottom = self.at(x0, y1) * (1.0 - tx) + self.at(x1, y1) * tx;
+                top * (1.0 - ty) + bottom * ty
+            })
+        }
+    }
+
     pub fn write_pixel(&mut self, x: usize, y: usize, color: Color) {
-        let pixel_idx : usize = (y * self.width as usize + x).into();
+        let pixel_idx : usize = y * self.width as usize + x;
         if pixel_idx >= self.pixels.len() {
-            println!("Attempt to update pixel index {} for width: {}, height: {}, x: {}, y: {}. Ignoring",
+            log::warn!("Attempt to update pixel index {} for width: {}, height: {}, x: {}, y: {}. Ignoring",
             pixel_idx, self.width, self.height, x, y);
             return;
         }
@@ -35,18 +282,95 @@ impl Canvas {
         format!("P3\n{} {}\n255\n", self.width, self.height)
     }
 
+    // big-endian, as PNG expects for 16-bit samples
+    #[cfg_attr(not(feature = "png"), allow(dead_code))]
+    fn rgb16_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 6);
+        for c in &self.pixels {
+            let (r, g, b) = c.scale_color_16(self.encoding);
+            bytes.extend_from_slice(&r.to_be_bytes());
+            bytes.extend_from_slice(&g.to_be_bytes());
+            bytes.extend_from_slice(&b.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn gen_ppm_binary(&self) -> Vec<u8> {
+        let mut contents = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        contents.extend(self.rgb8_bytes());
+        contents
+    }
+
+    // uncompressed true-color TGA with a top-left origin
+    pub fn gen_tga(&self) -> Vec<u8> {
+        let mut contents = vec![0u8; 18];
+        contents[2] = 2;
+        contents[12..14].copy_from_slice(&self.width.to_le_bytes());
+        contents[14..16].copy_from_slice(&self.height.to_le_bytes());
+        contents[16] = 24;
+        contents[17] = 0x20;
+        for rgb in self.rgb8_bytes().chunks(3) {
+            contents.extend_from_slice(&[rgb[2], rgb[1], rgb[0]]);
+        }
+        contents
+    }
+
+    // 24-bit BMP; rows are stored bottom-up and padded to 4 bytes
+    pub fn gen_bmp(&self) -> Vec<u8> {
+        let row_size = (self.width as usize * 3).div_ceil(4) * 4;
+        let image_size = row_size * self.height as usize;
+        let mut contents = Vec::with_capacity(54 + image_size);
+        contents.extend_from_slice(b"BM");
+        contents.extend_from_slice(&(54 + image_size as u32).to_le_bytes());
+        contents.extend_from_slice(&[0, 0, 0, 0]);
+        contents.extend_from_slice(&54u32.to_le_bytes());
+        contents.extend_from_slice(&40u32.to_le_bytes());
+        contents.extend_from_slice(&(self.width as i32).to_le_bytes());
+        contents.extend_from_slice(&(self.height as i32).to_le_bytes());
+        contents.extend_from_slice(&1u16.to_le_bytes());
+        contents.extend_from_slice(&24u16.to_le_bytes());
+        contents.extend_from_slice(&0u32.to_le_bytes());
+        contents.extend_from_slice(&(image_size as u32).to_le_bytes());
+        // 2835 pixels per meter is 72 DPI
+        contents.extend_from_slice(&2835i32.to_le_bytes());
+        contents.extend_from_slice(&2835i32.to_le_bytes());
+        contents.extend_from_slice(&[0; 8]);
+        let rgb = self.rgb8_bytes();
+        let row_len = self.width as usize * 3;
+        for row in rgb.chunks(row_len.max(1)).rev() {
+            for pixel in row.chunks(3) {
+                contents.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+            }
+            contents.resize(contents.len() + row_size - row_len, 0);
+        }
+        contents
+    }
+
+    // Radiance RGBE: a shared exponent keeps the full linear range instead
+    // of clamping to [0, 1] like the 8-bit formats
+    pub fn gen_hdr(&self) -> Vec<u8> {
+        let mut contents = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.height, self.width).into_bytes();
+        contents.reserve(self.pixels.len() * 4);
+        for c in &self.pixels {
+            contents.extend_from_slice(&c.to_rgbe());
+        }
+        contents
+    }
+
     pub fn gen_ppm_body(&self) -> String {
         let mut lines : Vec<String> = Vec::new();
         for i in (0..self.pixels.len()).step_by(self.width as usize) {
             let pixels_row = self.pixels.get(i..i + self.width as usize).unwrap();
             let mut output_row : String = "".to_string();
             for c in pixels_row {
-                let color_str : String = format!("{}", c);
+                let (r, g, b) = c.scale_color_encoded(self.encoding);
+                let color_str : String = format!("{} {} {}", r, g, b);
                 if output_row.len() + color_str.len() + 1 > 70 {
                     lines.push(output_row);
                     output_row = color_str;
                 } else {
-                    if output_row.len() != 0 {
+                    if !output_row.is_empty() {
                         output_row.push(' ');
                     }
                     output_row.push_str(color_str.as_str());
@@ -58,14 +382,88 @@ impl Canvas {
         return lines.join("\n");
     }
 
+    fn rgb8_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 3);
+        for c in &self.pixels {
+            let (r, g, b) = c.scale_color_encoded(self.encoding);
+            bytes.extend_from_slice(&[r, g, b]);
+        }
+        bytes
+    }
+
+    /// Picks the encoder from the file extension, falling back to ASCII PPM
+    /// for anything it does not recognize.
     pub fn save(&self, path: String) -> std::io::Result<()> {
+        let extension = Path::new(&path).extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        log::debug!("saving {}x{} canvas to {}", self.width, self.height, path);
+        match extension.as_deref() {
+            Some("png") => self.save_png(path),
+            Some("hdr") => self.save_hdr(path),
+            Some("tga") => self.save_tga(path),
+            Some("bmp") => self.save_bmp(path),
+            _ => self.save_ppm(path)
+        }
+    }
+
+    pub fn save_ppm(&self, path: String) -> std::io::Result<()> {
         let mut contents = self.gen_ppm_header();
         contents.push_str(self.gen_ppm_body().as_str());
         fs::write(path, contents)
     }
+
+    pub fn save_ppm_binary(&self, path: String) -> std::io::Result<()> {
+        fs::write(path, self.gen_ppm_binary())
+    }
+
+    pub fn save_tga(&self, path: String) -> std::io::Result<()> {
+        fs::write(path, self.gen_tga())
+    }
+
+    pub fn save_bmp(&self, path: String) -> std::io::Result<()> {
+        fs::write(path, self.gen_bmp())
+    }
+
+    pub fn save_hdr(&self, path: String) -> std::io::Result<()> {
+        fs::write(path, self.gen_hdr())
+    }
+
+    #[cfg(feature = "png")]
+    pub fn save_png(&self, path: String) -> std::io::Result<()> {
+        self.write_png(path, png::BitDepth::Eight, self.rgb8_bytes())
+    }
+
+    // 16 bits per channel avoids banding in smooth gradients
+    #[cfg(feature = "png")]
+    pub fn save_png_16(&self, path: String) -> std::io::Result<()> {
+        self.write_png(path, png::BitDepth::Sixteen, self.rgb16_bytes())
+    }
+
+    #[cfg(feature = "png")]
+    fn write_png(&self, path: String, depth: png::BitDepth, data: Vec<u8>) -> std::io::Result<()> {
+        let file = fs::File::create(path)?;
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(depth);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "png"))]
+    pub fn save_png(&self, _path: String) -> std::io::Result<()> {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "PNG output requires the `png` feature"))
+    }
+
+    #[cfg(not(feature = "png"))]
+    pub fn save_png_16(&self, _path: String) -> std::io::Result<()> {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "PNG output requires the `png` feature"))
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
     use std::str;
@@ -104,6 +502,7 @@ mod tests {
     #[test]
     fn test_ppm_body() {
         let mut canvas = Canvas::new(5, 3);
+        canvas.set_encoding(Encoding::Linear);
         let c1 = Color::new(1.5, 0.0, 0.0);
         let c2 = Color::new(0.0, 0.5, 0.0);
         let c3 = Color::new(-0.5, 0.0, 1.0);
@@ -116,9 +515,21 @@ mod tests {
         assert_eq!(expected_body, ppm_body);
     }
 
+    #[test]
+    fn test_ppm_body_srgb() {
+        let mut canvas = Canvas::new(3, 1);
+        canvas.write_pixel(0, 0, Color::new(0.5, 0.2, 1.5));
+        canvas.write_pixel(1, 0, Color::new(0.002, -0.5, 1.0));
+        assert_eq!(canvas.encoding(), Encoding::Srgb);
+        assert_eq!(canvas.gen_ppm_body(), "188 124 255 7 0 255 0 0 0\n");
+        canvas.set_encoding(Encoding::Gamma(2.0));
+        assert_eq!(canvas.gen_ppm_body(), "180 114 255 11 0 255 0 0 0\n");
+    }
+
     #[test]
     fn test_split_long_ppm_lines() {
         let mut canvas = Canvas::new(10, 2);
+        canvas.set_encoding(Encoding::Linear);
         for i in 0..20 {
             canvas.pixels[i] = Color::new(1.0, 0.8, 0.6);
         }
@@ -127,6 +538,162 @@ mod tests {
         assert_eq!(expected_body, ppm_body);
     }
 
+    #[test]
+    fn test_rgb8_bytes() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set_encoding(Encoding::Linear);
+        canvas.write_pixel(0, 0, Color::new(1.5, 0.0, 0.5));
+        canvas.write_pixel(1, 0, Color::new(0.0, 1.0, -0.5));
+        assert_eq!(canvas.rgb8_bytes(), vec![255, 0, 128, 0, 255, 0]);
+    }
+
+    #[test]
+    fn test_ppm_binary() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.set_encoding(Encoding::Linear);
+        canvas.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0));
+        canvas.write_pixel(1, 1, Color::new(0.0, 0.5, 1.0));
+        let ppm = canvas.gen_ppm_binary();
+        let header = b"P6\n2 2\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(&ppm[header.len()..], &[255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 128, 255]);
+    }
+
+    #[test]
+    fn test_from_ppm_wrong_magic() {
+        let ppm = "P32\n1 1\n255\n0 0 0\n";
+        assert_eq!(Canvas::from_ppm(ppm.as_bytes()).err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_from_ppm_dimensions() {
+        let ppm = "P3\n10 2\n255\n";
+        assert!(Canvas::from_ppm(ppm.as_bytes()).is_err());
+        let ppm = format!("P3\n10 2\n255\n{}", "0 0 0\n".repeat(20));
+        let canvas = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(canvas.width(), 10);
+        assert_eq!(canvas.height(), 2);
+    }
+
+    #[test]
+    fn test_from_ppm_pixels() {
+        let ppm = "P3\n4 3\n255\n255 127 0  0 127 255  127 255 0  255 255 255\n0 0 0  255 0 0  0 255 0  0 0 255\n255 255 0  0 255 255  255 0 255  127 127 127\n";
+        let canvas = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(canvas.pixels[0], Color::new(1.0, 0.49804, 0.0));
+        assert_eq!(canvas.pixels[1], Color::new(0.0, 0.49804, 1.0));
+        assert_eq!(canvas.pixels[3], Color::new(1.0, 1.0, 1.0));
+        assert_eq!(canvas.pixels[5], Color::new(1.0, 0.0, 0.0));
+        assert_eq!(canvas.pixels[11], Color::new(0.49804, 0.49804, 0.49804));
+    }
+
+    #[test]
+    fn test_from_ppm_comments_and_whitespace() {
+        let ppm = "P3\n# this is a comment\n2 1\n# this, too\n255\n# another comment\n255 255 255\n# oh, no, comments in the pixel data!\n255 0\n255\n";
+        let canvas = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(canvas.pixels[0], Color::new(1.0, 1.0, 1.0));
+        assert_eq!(canvas.pixels[1], Color::new(1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_from_ppm_max_value_scaling() {
+        let ppm = "P3\n2 2\n100\n100 100 100  50 50 50\n75 50 25  0 0 0\n";
+        let canvas = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(canvas.pixels[2], Color::new(0.75, 0.5, 0.25));
+    }
+
+    #[test]
+    fn test_from_ppm_binary() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set_encoding(Encoding::Linear);
+        canvas.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0));
+        canvas.write_pixel(2, 1, Color::new(0.2, 0.4, 0.6));
+        let loaded = Canvas::from_ppm(canvas.gen_ppm_binary().as_slice()).unwrap();
+        assert_eq!((loaded.width(), loaded.height()), (3, 2));
+        assert_eq!(loaded.pixels, canvas.pixels);
+
+        let mut wide = b"P6 1 1 65535\n".to_vec();
+        wide.extend_from_slice(&[0xff, 0xff, 0x80, 0x00, 0x00, 0x00]);
+        let loaded = Canvas::from_ppm(wide.as_slice()).unwrap();
+        assert_eq!(loaded.pixels[0], Color::new(1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn test_tga() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set_encoding(Encoding::Linear);
+        canvas.write_pixel(0, 0, Color::new(1.0, 0.5, 0.0));
+        let tga = canvas.gen_tga();
+        assert_eq!(&tga[..18], &[0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0, 24, 0x20]);
+        assert_eq!(&tga[18..], &[0, 128, 255, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_bmp() {
+        let mut canvas = Canvas::new(1, 2);
+        canvas.set_encoding(Encoding::Linear);
+        canvas.write_pixel(0, 0, Color::new(1.0, 0.5, 0.0));
+        let bmp = canvas.gen_bmp();
+        assert_eq!(&bmp[..2], b"BM");
+        assert_eq!(bmp.len(), 54 + 8);
+        assert_eq!(&bmp[2..6], &62u32.to_le_bytes());
+        assert_eq!(&bmp[18..22], &1i32.to_le_bytes());
+        assert_eq!(&bmp[22..26], &2i32.to_le_bytes());
+        // bottom row first, each row padded from 3 to 4 bytes
+        assert_eq!(&bmp[54..], &[0, 0, 0, 0, 0, 128, 255, 0]);
+    }
+
+    #[test]
+    fn test_hdr() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(0, 0, Color::new(1.0, 0.5, 0.25));
+        canvas.write_pixel(1, 0, Color::new(12.0, 0.0, -1.0));
+        let hdr = canvas.gen_hdr();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n";
+        assert_eq!(&hdr[..header.len()], header);
+        assert_eq!(&hdr[header.len()..], &[128, 64, 32, 129, 192, 0, 0, 132]);
+    }
+
+    #[test]
+    fn test_rgb16_bytes() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set_encoding(Encoding::Linear);
+        canvas.write_pixel(0, 0, Color::new(1.5, 0.0, 0.5));
+        canvas.write_pixel(1, 0, Color::new(0.0, 1.0, -0.5));
+        assert_eq!(canvas.rgb16_bytes(), vec![255, 255, 0, 0, 128, 0, 0, 0, 255, 255, 0, 0]);
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_save_png_16() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.write_pixel(1, 0, Color::new(0.25, 0.5, 1.0));
+        let path = std::env::temp_dir().join("raytracer_test_save_png_16.png");
+        canvas.save_png_16(path.to_str().unwrap().to_string()).unwrap();
+        let decoder = png::Decoder::new(fs::File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+        assert_eq!(&buf[..info.buffer_size()], canvas.rgb16_bytes().as_slice());
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_save_png() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.write_pixel(1, 1, Color::new(1.0, 0.8, 0.6));
+        let path = std::env::temp_dir().join("raytracer_test_save_png.png");
+        canvas.save(path.to_str().unwrap().to_string()).unwrap();
+        let decoder = png::Decoder::new(fs::File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((info.width, info.height), (3, 2));
+        assert_eq!(&buf[..info.buffer_size()], canvas.rgb8_bytes().as_slice());
+    }
+
     #[test]
     fn test_terminating_new_line() {
         let c = Canvas::new(5, 3);
@@ -134,4 +701,148 @@ mod tests {
         assert_eq!(str::ends_with(body.as_str(), "\n"), true);
     }
 
+    #[test]
+    fn test_pixel_at() {
+        let mut canvas = Canvas::new(4, 3);
+        canvas.write_pixel(3, 1, Color::RED);
+        assert_eq!(canvas.pixel_at(3, 1), Some(&Color::RED));
+        assert_eq!(canvas.pixel_at(0, 2), Some(&Color::BLACK));
+        // x past the edge must not wrap onto the next row
+        assert_eq!(canvas.pixel_at(4, 0), None);
+        assert_eq!(canvas.pixel_at(0, 3), None);
+    }
+
+    #[test]
+    fn test_rows() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.write_pixel(1, 1, Color::BLUE);
+        let rows: Vec<&[Color]> = canvas.rows().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].len(), 3);
+        assert_eq!(rows[1][1], Color::BLUE);
+    }
+
+    #[test]
+    fn test_enumerate_pixels() {
+        let mut canvas = Canvas::new(3, 2);
+        for (x, y, c) in canvas.enumerate_pixels_mut() {
+            *c = Color::new(x as f32, y as f32, 0.0);
+        }
+        let pixels: Vec<(usize, usize, &Color)> = canvas.enumerate_pixels().collect();
+        assert_eq!(pixels.len(), 6);
+        assert_eq!(pixels[4], (1, 1, &Color::new(1.0, 1.0, 0.0)));
+        assert_eq!(canvas.pixel_at(2, 1), Some(&Color::new(2.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_blend_modes() {
+        let dst = Color::new(0.5, 0.5, 0.5);
+        let src = Color::new(1.0, 0.0, 0.5);
+        assert_eq!(BlendMode::Over.blend(&dst, &src, 1.0), src);
+        assert_eq!(BlendMode::Over.blend(&dst, &src, 0.5), Color::new(0.75, 0.25, 0.5));
+        assert_eq!(BlendMode::Add.blend(&dst, &src, 1.0), Color::new(1.5, 0.5, 1.0));
+        assert_eq!(BlendMode::Multiply.blend(&dst, &src, 1.0), Color::new(0.5, 0.0, 0.25));
+        for mode in [BlendMode::Over, BlendMode::Add, BlendMode::Multiply].iter() {
+            assert_eq!(mode.blend(&dst, &src, 0.0), dst);
+        }
+    }
+
+    #[test]
+    fn test_blit() {
+        let mut canvas = Canvas::new(4, 4);
+        let mut layer = Canvas::new(2, 2);
+        for (_, _, c) in layer.enumerate_pixels_mut() {
+            *c = Color::RED;
+        }
+        canvas.blit(&layer, 1, 2);
+        assert_eq!(canvas.pixel_at(1, 2), Some(&Color::RED));
+        assert_eq!(canvas.pixel_at(2, 3), Some(&Color::RED));
+        assert_eq!(canvas.pixel_at(0, 2), Some(&Color::BLACK));
+        // partly off the canvas
+        canvas.blit_blended(&layer, -1, -1, BlendMode::Add, 0.5);
+        assert_eq!(canvas.pixel_at(0, 0), Some(&Color::new(0.5, 0.0, 0.0)));
+        assert_eq!(canvas.pixel_at(1, 0), Some(&Color::BLACK));
+    }
+
+    // 3x2 canvas with distinct pixels, red = x and green = y
+    fn numbered() -> Canvas {
+        let mut canvas = Canvas::new(3, 2);
+        for (x, y, c) in canvas.enumerate_pixels_mut() {
+            *c = Color::new(x as f32, y as f32, 0.0);
+        }
+        canvas
+    }
+
+    fn coords(canvas: &Canvas, x: usize, y: usize) -> (f32, f32) {
+        let c = canvas.pixel_at(x, y).unwrap();
+        (c.red(), c.green())
+    }
+
+    #[test]
+    fn test_crop() {
+        let canvas = numbered();
+        let cropped = canvas.crop(&Tile {x: 1, y: 1, width: 2, height: 1});
+        assert_eq!((cropped.width(), cropped.height()), (2, 1));
+        assert_eq!(coords(&cropped, 0, 0), (1.0, 1.0));
+        // clipped to the canvas
+        let clipped = canvas.crop(&Tile {x: 2, y: 0, width: 10, height: 10});
+        assert_eq!((clipped.width(), clipped.height()), (1, 2));
+        assert_eq!(canvas.crop(&Tile {x: 5, y: 5, width: 1, height: 1}).width(), 0);
+    }
+
+    #[test]
+    fn test_flips() {
+        let canvas = numbered();
+        assert_eq!(coords(&canvas.flip_horizontal(), 0, 1), (2.0, 1.0));
+        assert_eq!(coords(&canvas.flip_vertical(), 0, 0), (0.0, 1.0));
+        assert_eq!(coords(&canvas.rotate_180(), 0, 0), (2.0, 1.0));
+    }
+
+    #[test]
+    fn test_rotations() {
+        let mut canvas = numbered();
+        canvas.set_encoding(Encoding::Linear);
+        let cw = canvas.rotate_cw();
+        assert_eq!((cw.width(), cw.height()), (2, 3));
+        assert_eq!(cw.encoding(), Encoding::Linear);
+        // the bottom-left corner moves to the top-left
+        assert_eq!(coords(&cw, 0, 0), (0.0, 1.0));
+        assert_eq!(coords(&cw, 1, 2), (2.0, 0.0));
+        let ccw = canvas.rotate_ccw();
+        assert_eq!(coords(&ccw, 0, 0), (2.0, 0.0));
+        assert_eq!(canvas.rotate_cw().rotate_ccw().gen_ppm_body(), canvas.gen_ppm_body());
+        assert_eq!(Canvas::new(0, 3).rotate_cw().width(), 3);
+    }
+
+    #[test]
+    fn test_resize_box_downscale() {
+        let mut canvas = Canvas::new(4, 2);
+        canvas.write_pixel(0, 0, Color::WHITE);
+        canvas.write_pixel(3, 1, Color::new(0.0, 0.0, 0.4));
+        let small = canvas.resize(2, 1, ResizeFilter::Box);
+        assert_eq!((small.width(), small.height()), (2, 1));
+        assert_eq!(small.pixel_at(0, 0), Some(&Color::new(0.25, 0.25, 0.25)));
+        assert_eq!(small.pixel_at(1, 0), Some(&Color::new(0.0, 0.0, 0.1)));
+    }
+
+    #[test]
+    fn test_resize_box_upscale_repeats() {
+        let canvas = numbered();
+        let big = canvas.resize(6, 4, ResizeFilter::Box);
+        assert_eq!(coords(&big, 5, 3), (2.0, 1.0));
+        assert_eq!(coords(&big, 2, 1), (1.0, 0.0));
+    }
+
+    #[test]
+    fn test_resize_bilinear() {
+        let canvas = numbered();
+        // same size is the identity
+        assert_eq!(canvas.resize(3, 2, ResizeFilter::Bilinear).gen_ppm_body(), canvas.gen_ppm_body());
+        let big = canvas.resize(6, 4, ResizeFilter::Bilinear);
+        // edges clamp, interiors interpolate
+        assert_eq!(coords(&big, 0, 0), (0.0, 0.0));
+        assert_eq!(coords(&big, 1, 1), (0.25, 0.25));
+        assert_eq!(coords(&big, 5, 3), (2.0, 1.0));
+        assert_eq!(Canvas::new(0, 0).resize(2, 2, ResizeFilter::Bilinear).pixel_at(1, 1), Some(&Color::BLACK));
+    }
 }
\ No newline at end of file
@@ -1,8 +1,57 @@
-use std::ops::{Add, Sub, Mul};
+use std::ops::{Add, Sub, Mul, Div, AddAssign, SubAssign, MulAssign};
 use std::fmt::{Display, Debug, Formatter, Result as FmtResult};
 use std::cmp;
 use super::utils;
 
+// Transfer function applied when quantizing linear colors for 8/16-bit
+// output. Linear keeps the old behavior of writing the raw values.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    Linear,
+    Srgb,
+    Gamma(f32)
+}
+
+impl Encoding {
+    pub fn encode(&self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            Encoding::Linear => value,
+            Encoding::Srgb => {
+                if value <= 0.0031308 {
+                    12.92 * value
+                } else {
+                    1.055 * value.powf(1.0 / 2.4) - 0.055
+                }
+            },
+            Encoding::Gamma(gamma) => value.powf(1.0 / gamma)
+        }
+    }
+
+    pub fn decode(&self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            Encoding::Linear => value,
+            Encoding::Srgb => {
+                if value <= 0.04045 {
+                    value / 12.92
+                } else {
+                    ((value + 0.055) / 1.055).powf(2.4)
+                }
+            },
+            Encoding::Gamma(gamma) => value.powf(*gamma)
+        }
+    }
+
+    // Linear output rounds up to match the original PPM writer
+    fn quantize(&self, value: f32, max: f32) -> f32 {
+        match self {
+            Encoding::Linear => (value * max).ceil(),
+            _ => (self.encode(value) * max).round()
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Color {
     red: f32,
@@ -15,8 +64,38 @@ impl Color {
         Self {red, green, blue}
     }
 
+    pub const BLACK: Color = Color {red: 0.0, green: 0.0, blue: 0.0};
+    pub const WHITE: Color = Color {red: 1.0, green: 1.0, blue: 1.0};
+    pub const RED: Color = Color {red: 1.0, green: 0.0, blue: 0.0};
+    pub const GREEN: Color = Color {red: 0.0, green: 1.0, blue: 0.0};
+    pub const BLUE: Color = Color {red: 0.0, green: 0.0, blue: 1.0};
+    pub const YELLOW: Color = Color {red: 1.0, green: 1.0, blue: 0.0};
+    pub const CYAN: Color = Color {red: 0.0, green: 1.0, blue: 1.0};
+    pub const MAGENTA: Color = Color {red: 1.0, green: 0.0, blue: 1.0};
+    pub const GREY: Color = Color {red: 0.5, green: 0.5, blue: 0.5};
+
     pub fn black() -> Self {
-        Self {red: 0.0, green: 0.0, blue: 0.0}
+        Self::BLACK
+    }
+
+    // 8-bit channels as written by image editors, mapped to [0, 1] linearly
+    pub fn from_u8(red: u8, green: u8, blue: u8) -> Self {
+        Self {red: red as f32 / 255.0, green: green as f32 / 255.0, blue: blue as f32 / 255.0}
+    }
+
+    // "#rrggbb" or "#rgb", the leading # is optional
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+        match digits.len() {
+            6 => Some(Self::from_u8(channel(&digits[0..2])?, channel(&digits[2..4])?, channel(&digits[4..6])?)),
+            // each digit is repeated, so "f80" is "ff8800"
+            3 => Some(Self::from_u8(channel(&digits[0..1])? * 17, channel(&digits[1..2])? * 17, channel(&digits[2..3])? * 17)),
+            _ => None
+        }
     }
 
     pub fn red(&self) -> f32 {
@@ -31,32 +110,61 @@ impl Color {
         self.blue
     }
 
+    pub fn encode(&self, encoding: Encoding) -> Self {
+        Self {red: encoding.encode(self.red), green: encoding.encode(self.green), blue: encoding.encode(self.blue)}
+    }
+
+    pub fn decode(&self, encoding: Encoding) -> Self {
+        Self {red: encoding.decode(self.red), green: encoding.decode(self.green), blue: encoding.decode(self.blue)}
+    }
+
     fn scale_color(&self) -> (u8, u8, u8) {
-        let scaled_red = cmp::min(255, (self.red * 255f32).ceil() as u8);
-        let scaled_green = cmp::min(255, (self.green * 255f32).ceil() as u8);
-        let scaled_blue = cmp::min(255, (self.blue * 255f32).ceil() as u8);
+        self.scale_color_encoded(Encoding::Linear)
+    }
+
+    pub(crate) fn scale_color_encoded(&self, encoding: Encoding) -> (u8, u8, u8) {
+        let scaled_red = cmp::min(255, encoding.quantize(self.red, 255f32) as u8);
+        let scaled_green = cmp::min(255, encoding.quantize(self.green, 255f32) as u8);
+        let scaled_blue = cmp::min(255, encoding.quantize(self.blue, 255f32) as u8);
         return (scaled_red, scaled_green, scaled_blue);
     }
-}
 
-impl Add<Color> for Color {
-    type Output = Color;
-    fn add(self, _rhs: Color) -> Self {
-        Self {red: self.red + _rhs.red, green: self.green + _rhs.green, blue: self.blue + _rhs.blue}
+    pub(crate) fn scale_color_16(&self, encoding: Encoding) -> (u16, u16, u16) {
+        let scaled_red = encoding.quantize(self.red, 65535f32) as u16;
+        let scaled_green = encoding.quantize(self.green, 65535f32) as u16;
+        let scaled_blue = encoding.quantize(self.blue, 65535f32) as u16;
+        return (scaled_red, scaled_green, scaled_blue);
+    }
+
+    // shared exponent encoding used by the Radiance HDR format
+    pub(crate) fn to_rgbe(&self) -> [u8; 4] {
+        // NaN goes to 0 and anything too large for the 8-bit exponent
+        // (including infinity) to the largest encodable value
+        let clamp = |v: f32| if v.is_nan() { 0.0 } else { v.clamp(0.0, 1e38) };
+        let red = clamp(self.red);
+        let green = clamp(self.green);
+        let blue = clamp(self.blue);
+        let max = red.max(green).max(blue);
+        if max < 1e-32 {
+            return [0, 0, 0, 0];
+        }
+        let exponent = max.log2().floor() as i32 + 1;
+        let scale = 256.0 / 2f32.powi(exponent);
+        [(red * scale) as u8, (green * scale) as u8, (blue * scale) as u8, (exponent + 128) as u8]
     }
 }
 
-impl Sub<Color> for Color {
+impl Add<&Color> for &Color {
     type Output = Color;
-    fn sub(self, _rhs: Color) -> Self {
-        Self {red: self.red - _rhs.red, green: self.green - _rhs.green, blue: self.blue - _rhs.blue}
+    fn add(self, _rhs: &Color) -> Color {
+        Color {red: self.red + _rhs.red, green: self.green + _rhs.green, blue: self.blue + _rhs.blue}
     }
 }
 
-impl Mul<Color> for Color {
+impl Sub<&Color> for &Color {
     type Output = Color;
-    fn mul(self, _rhs: Color) -> Self {
-        Self {red: self.red * _rhs.red, green: self.green * _rhs.green, blue: self.blue * _rhs.blue}
+    fn sub(self, _rhs: &Color) -> Color {
+        Color {red: self.red - _rhs.red, green: self.green - _rhs.green, blue: self.blue - _rhs.blue}
     }
 }
 
@@ -67,12 +175,9 @@ impl Mul<&Color> for &Color {
     }
 }
 
-impl Mul<f32> for Color {
-    type Output = Color;
-    fn mul(self, _rhs: f32) -> Self {
-        Self {red: self.red * _rhs, green: self.green * _rhs, blue: self.blue * _rhs}
-    }
-}
+forward_binop!(impl Add, add for Color, Color => Color);
+forward_binop!(impl Sub, sub for Color, Color => Color);
+forward_binop!(impl Mul, mul for Color, Color => Color);
 
 impl Mul<f32> for &Color {
     type Output = Color;
@@ -81,6 +186,22 @@ impl Mul<f32> for &Color {
     }
 }
 
+impl Div<f32> for &Color {
+    type Output = Color;
+    fn div(self, _rhs: f32) -> Color {
+        Color {red: self.red / _rhs, green: self.green / _rhs, blue: self.blue / _rhs}
+    }
+}
+
+forward_scalar_binop!(impl Mul, mul for Color);
+forward_scalar_binop!(impl Div, div for Color);
+scalar_mul_left!(Color);
+
+forward_assign_op!(impl AddAssign, add_assign for Color, Color, +);
+forward_assign_op!(impl SubAssign, sub_assign for Color, Color, -);
+forward_assign_op!(impl MulAssign, mul_assign for Color, Color, *);
+forward_scalar_assign_op!(impl MulAssign, mul_assign for Color, *);
+
 impl Display for Color {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
         let (r, g, b) = self.scale_color();
@@ -105,6 +226,7 @@ impl PartialEq for Color {
 
 
 #[cfg(test)]
+#[allow(clippy::unnecessary_cast)]
 mod tests {
     use super::*;
 
@@ -116,6 +238,32 @@ mod tests {
         assert_eq!(c.blue(), 3.0);
     }
 
+    #[test]
+    fn test_srgb_encoding() {
+        assert_eq!(Encoding::Srgb.encode(0.0), 0.0);
+        assert!(utils::is_equal(Encoding::Srgb.encode(1.0), 1.0));
+        assert!(utils::is_equal(Encoding::Srgb.encode(0.002), 0.02584));
+        assert!(utils::is_equal(Encoding::Srgb.encode(0.5), 0.735357));
+        assert!(utils::is_equal(Encoding::Srgb.encode(2.0), 1.0));
+        assert!((Encoding::Srgb.decode(Encoding::Srgb.encode(0.2)) - 0.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_gamma_encoding() {
+        let gamma = Encoding::Gamma(2.0);
+        assert_eq!(gamma.encode(0.25), 0.5);
+        assert_eq!(gamma.decode(0.5), 0.25);
+        let c = Color::new(0.25, 0.0, 1.0).encode(gamma);
+        assert_eq!(c, Color::new(0.5, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_scale_color_encoded() {
+        let c = Color::new(0.5, 0.2, 1.5);
+        assert_eq!(c.scale_color_encoded(Encoding::Linear), (128, 51, 255));
+        assert_eq!(c.scale_color_encoded(Encoding::Srgb), (188, 124, 255));
+    }
+
     #[test]
     fn test_color_add() {
         let c1 = Color::new(0.9, 0.6, 0.75);
@@ -146,4 +294,65 @@ mod tests {
         let result = c1 * 5 as f32;
         assert_eq!(result, Color::new(1.0, 2.0, 4.0))
     }
+
+    #[test]
+    fn test_color_reference_operators() {
+        let a = Color::new(0.9, 0.6, 0.75);
+        let b = Color::new(0.7, 0.1, 0.25);
+        assert_eq!(&a + &b, Color::new(1.6, 0.7, 1.0));
+        assert_eq!(&a - b.clone(), Color::new(0.2, 0.5, 0.5));
+        assert_eq!(a.clone() * &b, &a * &b);
+    }
+
+    #[test]
+    fn test_color_division_and_scalar_on_left() {
+        let c = Color::new(0.2, 0.3, 0.4);
+        assert_eq!(&c / 2.0, Color::new(0.1, 0.15, 0.2));
+        assert_eq!(2.0 * &c, Color::new(0.4, 0.6, 0.8));
+        assert_eq!(0.5 * c.clone(), c / 2.0);
+    }
+
+    #[test]
+    fn test_color_assign_operators() {
+        let mut c = Color::new(0.5, 0.5, 0.5);
+        c += Color::new(0.25, 0.0, 0.5);
+        assert_eq!(c, Color::new(0.75, 0.5, 1.0));
+        c -= &Color::new(0.25, 0.25, 0.25);
+        assert_eq!(c, Color::new(0.5, 0.25, 0.75));
+        c *= Color::new(2.0, 2.0, 0.0);
+        assert_eq!(c, Color::new(1.0, 0.5, 0.0));
+        c *= 0.5;
+        assert_eq!(c, Color::new(0.5, 0.25, 0.0));
+    }
+
+    #[test]
+    fn test_color_constants() {
+        assert_eq!(Color::WHITE, Color::new(1.0, 1.0, 1.0));
+        assert_eq!(Color::black(), Color::BLACK);
+        assert_eq!(Color::RED + Color::GREEN, Color::YELLOW);
+    }
+
+    #[test]
+    fn test_from_u8() {
+        assert_eq!(Color::from_u8(255, 0, 51), Color::new(1.0, 0.0, 0.2));
+    }
+
+    #[test]
+    fn test_from_hex() {
+        assert_eq!(Color::from_hex("#ff8800"), Some(Color::from_u8(255, 136, 0)));
+        assert_eq!(Color::from_hex("FF8800"), Some(Color::from_u8(255, 136, 0)));
+        assert_eq!(Color::from_hex("#f80"), Some(Color::from_u8(255, 136, 0)));
+        assert_eq!(Color::from_hex("#ff880"), None);
+        assert_eq!(Color::from_hex("#gg8800"), None);
+        assert_eq!(Color::from_hex("#+f8800"), None);
+        assert_eq!(Color::from_hex("#ééé"), None);
+    }
+
+    #[test]
+    fn test_rgbe_non_finite() {
+        assert_eq!(Color::new(0.5, 0.0, 0.0).to_rgbe(), [128, 0, 0, 128]);
+        let rgbe = Color::new(f32::INFINITY, 0.0, 0.0).to_rgbe();
+        assert!(rgbe[0] > 0 && rgbe[3] == 255);
+        assert_eq!(Color::new(f32::NAN, 0.5, f32::NEG_INFINITY).to_rgbe(), [0, 128, 0, 128]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,106 @@
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind};
+use super::canvas::Canvas;
+use super::color::{Color, Encoding};
+
+pub struct Report {
+    pub total_pixels: usize,
+    // pixels where some channel differs by more than the tolerance
+    pub differing_pixels: usize,
+    pub max_delta: f32,
+    // per-pixel largest channel difference, shown in red
+    pub heat_map: Canvas
+}
+
+impl Report {
+    pub fn matches(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+pub fn compare_canvases(a: &Canvas, b: &Canvas, tolerance: f32) -> std::io::Result<Report> {
+    if a.width() != b.width() || a.height() != b.height() {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("cannot compare a {}x{} image with a {}x{} one",
+            a.width(), a.height(), b.width(), b.height())));
+    }
+    let mut heat_map = Canvas::new(a.width(), a.height());
+    heat_map.set_encoding(Encoding::Linear);
+    let mut differing_pixels = 0;
+    let mut max_delta: f32 = 0.0;
+    let width = a.width() as usize;
+    for (idx, (pa, pb)) in a.pixels().iter().zip(b.pixels()).enumerate() {
+        let delta = (pa.red() - pb.red()).abs()
+            .max((pa.green() - pb.green()).abs())
+            .max((pa.blue() - pb.blue()).abs());
+        if delta > tolerance {
+            differing_pixels += 1;
+        }
+        max_delta = max_delta.max(delta);
+        heat_map.write_pixel(idx % width, idx / width, Color::new(delta.min(1.0), 0.0, 0.0));
+    }
+    Ok(Report {total_pixels: a.pixels().len(), differing_pixels, max_delta, heat_map})
+}
+
+// compares two PPM files channel by channel; tolerance is on the 0..1 scale
+pub fn compare_images(path_a: String, path_b: String, tolerance: f32) -> std::io::Result<Report> {
+    let a = Canvas::from_ppm(BufReader::new(File::open(path_a)?))?;
+    let b = Canvas::from_ppm(BufReader::new(File::open(path_b)?))?;
+    compare_canvases(&a, &b, tolerance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canvas(colors: &[Color]) -> Canvas {
+        let mut c = Canvas::new(colors.len() as u16, 1);
+        for (x, color) in colors.iter().enumerate() {
+            c.write_pixel(x, 0, color.clone());
+        }
+        c
+    }
+
+    #[test]
+    fn test_identical_canvases() {
+        let a = canvas(&[Color::new(0.1, 0.2, 0.3), Color::new(1.0, 1.0, 1.0)]);
+        let report = compare_canvases(&a, &a, 0.0).unwrap();
+        assert!(report.matches());
+        assert_eq!(report.total_pixels, 2);
+        assert_eq!(report.max_delta, 0.0);
+    }
+
+    #[test]
+    fn test_differences_and_tolerance() {
+        let a = canvas(&[Color::new(0.5, 0.5, 0.5), Color::new(0.5, 0.5, 0.5), Color::black()]);
+        let b = canvas(&[Color::new(0.5, 0.5, 0.5), Color::new(0.5, 0.52, 0.5), Color::new(0.0, 0.0, 0.75)]);
+        let report = compare_canvases(&a, &b, 0.01).unwrap();
+        assert_eq!(report.differing_pixels, 2);
+        assert_eq!(report.max_delta, 0.75);
+        assert_eq!(compare_canvases(&a, &b, 0.05).unwrap().differing_pixels, 1);
+        assert!(report.heat_map.gen_ppm_body().starts_with("0 0 0 6 0 0 192 0 0"));
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let err = compare_canvases(&Canvas::new(2, 1), &Canvas::new(1, 2), 0.0).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_compare_images_round_trip() {
+        let dir = std::env::temp_dir();
+        let path_a = dir.join("libraytracer_compare_a.ppm").to_str().unwrap().to_string();
+        let path_b = dir.join("libraytracer_compare_b.ppm").to_str().unwrap().to_string();
+        let mut a = canvas(&[Color::new(1.0, 0.0, 0.0), Color::new(0.0, 1.0, 0.0)]);
+        a.save_ppm(path_a.clone()).unwrap();
+        a.write_pixel(1, 0, Color::new(0.0, 0.0, 1.0));
+        a.save_ppm(path_b.clone()).unwrap();
+        let report = compare_images(path_a.clone(), path_a.clone(), 0.0).unwrap();
+        assert!(report.matches());
+        let report = compare_images(path_a.clone(), path_b.clone(), 0.0).unwrap();
+        assert_eq!(report.differing_pixels, 1);
+        assert_eq!(report.max_delta, 1.0);
+        std::fs::remove_file(path_a).unwrap();
+        std::fs::remove_file(path_b).unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,96 @@
+use super::color::Color;
+
+// how much of the scene survives `distance` units of fog
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FogFalloff {
+    // clear up to start, fully fogged from end on
+    Linear { start: f32, end: f32 },
+    Exponential { density: f32 },
+    ExponentialSquared { density: f32 }
+}
+
+// Homogeneous fog applied by distance. There is no world to hang this on,
+// so a color_at closure applies it to what a ray hit, passing the hit's t
+// (camera rays are normalized, so t is the distance travelled).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fog {
+    pub color: Color,
+    pub falloff: FogFalloff
+}
+
+impl Fog {
+    pub fn new(color: Color, falloff: FogFalloff) -> Self {
+        Self {color, falloff}
+    }
+
+    // fraction of the surface colour left after `distance`, from 1 down to 0
+    pub fn transmittance(&self, distance: f32) -> f32 {
+        let distance = distance.max(0.0);
+        let t = match self.falloff {
+            FogFalloff::Linear {start, end} => {
+                if end <= start {
+                    if distance < start { 1.0 } else { 0.0 }
+                } else {
+                    (end - distance) / (end - start)
+                }
+            },
+            // no fog at all, even at an infinite distance where 0 * inf is NaN
+            FogFalloff::Exponential {density} | FogFalloff::ExponentialSquared {density} if density <= 0.0 => 1.0,
+            FogFalloff::Exponential {density} => (-density * distance).exp(),
+            FogFalloff::ExponentialSquared {density} => (-(density * distance).powi(2)).exp()
+        };
+        return t.clamp(0.0, 1.0);
+    }
+
+    // infinite distances (rays that missed everything) come back as the fog colour
+    pub fn apply(&self, color: &Color, distance: f32) -> Color {
+        let t = self.transmittance(distance);
+        return color * t + &self.color * (1.0 - t);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::utils;
+
+    #[test]
+    fn test_linear_fog() {
+        let fog = Fog::new(Color::GREY, FogFalloff::Linear {start: 10.0, end: 20.0});
+        assert_eq!(fog.transmittance(0.0), 1.0);
+        assert_eq!(fog.transmittance(10.0), 1.0);
+        assert!(utils::is_equal(fog.transmittance(15.0), 0.5));
+        assert_eq!(fog.transmittance(25.0), 0.0);
+        assert_eq!(fog.apply(&Color::RED, 5.0), Color::RED);
+        assert_eq!(fog.apply(&Color::RED, f32::INFINITY), Color::GREY);
+    }
+
+    #[test]
+    fn test_exponential_fog() {
+        let fog = Fog::new(Color::WHITE, FogFalloff::Exponential {density: 0.1});
+        assert!(utils::is_equal(fog.transmittance(10.0), (-1.0f32).exp()));
+        let squared = Fog::new(Color::WHITE, FogFalloff::ExponentialSquared {density: 0.1});
+        assert!(utils::is_equal(squared.transmittance(20.0), (-4.0f32).exp()));
+        // squared fog stays clearer close up and thickens faster further out
+        assert!(squared.transmittance(5.0) > fog.transmittance(5.0));
+        assert!(squared.transmittance(30.0) < fog.transmittance(30.0));
+        assert_eq!(fog.apply(&Color::BLACK, 0.0), Color::BLACK);
+        assert_eq!(fog.apply(&Color::BLACK, f32::INFINITY), Color::WHITE);
+    }
+
+    #[test]
+    fn test_degenerate_linear_fog_is_a_wall() {
+        let fog = Fog::new(Color::WHITE, FogFalloff::Linear {start: 5.0, end: 5.0});
+        assert_eq!(fog.transmittance(4.9), 1.0);
+        assert_eq!(fog.transmittance(5.0), 0.0);
+    }
+
+    #[test]
+    fn test_zero_density_fog_is_clear() {
+        for falloff in [FogFalloff::Exponential {density: 0.0}, FogFalloff::ExponentialSquared {density: 0.0}] {
+            let fog = Fog::new(Color::WHITE, falloff);
+            assert_eq!(fog.transmittance(f32::INFINITY), 1.0);
+            assert_eq!(fog.apply(&Color::RED, f32::INFINITY), Color::RED);
+        }
+    }
+}
\ No newline at end of file
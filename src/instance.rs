@@ -0,0 +1,175 @@
+use std::sync::Arc;
+use super::ray::Ray;
+use super::tuple::Tuple;
+use super::matrix4::Matrix4;
+use super::material::Material;
+use super::intersection::{Intersect, Intersection, Intersections, Shape};
+use super::sampler;
+
+// A placement of a shared shape: many instances can reference one mesh
+// through the Arc, each with its own transform (applied on top of the
+// shape's) and optionally its own material. Hits are reported against the
+// instance, so ids and normals resolve through it.
+#[derive(Debug, Clone)]
+pub struct Instance<S> {
+    shape: Arc<S>,
+    transform: Matrix4,
+    inverse: Matrix4,
+    pub material: Option<Material>,
+    id: i32
+}
+
+impl<S: Intersect<S> + Shape> Instance<S> {
+    pub fn new(id: i32, shape: Arc<S>) -> Self {
+        Self {id, shape, transform: Matrix4::identity(), inverse: Matrix4::identity(), material: None}
+    }
+
+    // None (leaving the instance unchanged) if the transform can't be inverted
+    pub fn set_transform(&mut self, transform: Matrix4) -> Option<()> {
+        self.inverse = transform.inverse()?;
+        self.transform = transform;
+        Some(())
+    }
+
+    pub fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    pub fn shape(&self) -> &Arc<S> {
+        &self.shape
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    // per-instance value in [0, 1) for varying materials and textures
+    pub fn random_value(&self, seed: u64) -> f32 {
+        sampler::instance_random(self.id, seed)
+    }
+
+    // the override if there is one, otherwise whatever `shared` (the shape's
+    // own material lookup) returns
+    pub fn material_or<'a>(&'a self, shared: &'a Material) -> &'a Material {
+        self.material.as_ref().unwrap_or(shared)
+    }
+
+    // a world space point in the shared shape's space, e.g. for the shape's
+    // own material_at
+    pub fn shape_point(&self, point: &Tuple) -> Tuple {
+        self.inverse * *point
+    }
+
+    fn to_world_normal(&self, local_normal: Tuple) -> Tuple {
+        let world_normal = self.inverse.transpose() * local_normal;
+        let world_normal_vector = Tuple::vector(world_normal.x(), world_normal.y(), world_normal.z());
+        return world_normal_vector.normalize();
+    }
+}
+
+impl<S: Intersect<S> + Shape> Shape for Instance<S> {
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl<S: Intersect<S> + Shape> Intersect<Self> for Instance<S> {
+    // the transformed direction isn't renormalized, so t is the same on both sides
+    fn intersect_range(&self, ray: &Ray, t_min: f32, t_max: f32) -> Intersections<'_, Self> {
+        let local_ray = ray.transform(&self.inverse);
+        let inner = self.shape.intersect_range(&local_ray, t_min, t_max);
+        let mut xs = Intersections::with_capacity(inner.len());
+        for i in 0..inner.len() {
+            xs.add_point(inner[i].for_object(self));
+        }
+        return xs;
+    }
+
+    fn intersect_batch(&self, rays: &[Ray]) -> Vec<Intersections<'_, Self>> {
+        let local_rays: Vec<Ray> = rays.iter().map(|ray| ray.transform(&self.inverse)).collect();
+        let mut batch = Vec::with_capacity(rays.len());
+        for inner in self.shape.intersect_batch(&local_rays) {
+            let mut xs = Intersections::with_capacity(inner.len());
+            for i in 0..inner.len() {
+                xs.add_point(inner[i].for_object(self));
+            }
+            batch.push(xs);
+        }
+        return batch;
+    }
+
+    fn normal_at(&self, point: Tuple) -> Tuple {
+        self.normal_at_time(point, 0.0)
+    }
+
+    fn normal_at_time(&self, point: Tuple, time: f32) -> Tuple {
+        let local_normal = self.shape.normal_at_time(self.inverse * point, time);
+        return self.to_world_normal(local_normal);
+    }
+
+    fn normal_at_hit(&self, hit: &Intersection<'_, Self>, point: Tuple) -> Tuple {
+        let local_normal = self.shape.normal_at_hit(&hit.for_object(&*self.shape), self.inverse * point);
+        return self.to_world_normal(local_normal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::sphere::Sphere;
+    use super::super::color::Color;
+
+    #[test]
+    fn test_instances_share_one_shape() {
+        let sphere = Arc::new(Sphere::new(1));
+        let mut left = Instance::new(10, Arc::clone(&sphere));
+        left.set_transform(Matrix4::translation(-3.0, 0.0, 0.0));
+        let mut right = Instance::new(11, Arc::clone(&sphere));
+        right.set_transform(Matrix4::translation(3.0, 0.0, 0.0));
+        assert_eq!(Arc::strong_count(&sphere), 3);
+        let r = Ray::new(Tuple::point(3.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(left.intersect(&r).is_empty());
+        let xs = right.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].point(), 4.0);
+        // hits resolve to the instance, not the shared sphere
+        assert_eq!(xs.hit().unwrap().object_id(), 11);
+    }
+
+    #[test]
+    fn test_instance_normals_and_scale() {
+        let mut sphere = Sphere::new(1);
+        sphere.set_transform(Matrix4::translation(0.0, 1.0, 0.0));
+        let mut instance = Instance::new(2, Arc::new(sphere));
+        instance.set_transform(Matrix4::scaling(2.0, 2.0, 2.0));
+        // the instance transform applies on top of the sphere's own
+        let r = Ray::new(Tuple::point(0.0, 2.0, -10.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = instance.intersect(&r);
+        assert_eq!(xs[0].point(), 8.0);
+        let n = instance.normal_at(Tuple::point(0.0, 2.0, -2.0));
+        assert_eq!(n, Tuple::vector(0.0, 0.0, -1.0));
+        assert_eq!(instance.shape_point(&Tuple::point(0.0, 2.0, -2.0)), Tuple::point(0.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn test_singular_transform_is_rejected() {
+        let mut instance = Instance::new(2, Arc::new(Sphere::new(1)));
+        let scale = Matrix4::scaling(2.0, 2.0, 2.0);
+        assert!(instance.set_transform(scale).is_some());
+        assert!(instance.set_transform(Matrix4::scaling(0.0, 1.0, 1.0)).is_none());
+        assert_eq!(instance.transform(), &scale);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -10.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(instance.intersect(&r)[0].point(), 8.0);
+    }
+
+    #[test]
+    fn test_material_override() {
+        let sphere = Arc::new(Sphere::new(1));
+        let mut instance = Instance::new(2, Arc::clone(&sphere));
+        assert_eq!(instance.material_or(&sphere.material), &sphere.material);
+        let mut red = Material::new();
+        red.color = Color::RED;
+        instance.material = Some(red.clone());
+        assert_eq!(instance.material_or(&sphere.material), &red);
+    }
+}
\ No newline at end of file
@@ -1,35 +1,79 @@
 use std::cmp::{Ord, PartialEq, PartialOrd, Ordering, Eq, Reverse};
 use std::ops::Index;
 use std::collections::BinaryHeap;
-use super::ray::Ray;
+use super::ray::{Ray, RayPacket};
 use super::tuple::Tuple;
 use super::utils;
 
-pub trait Intersect<T: PartialEq + Clone> {
-    fn intersect(&self, ray: &Ray) -> Intersections<T>;
+// anything rays can hit; ids let callers tell hits apart and look up per
+// object data without comparing whole objects
+pub trait Shape {
+    fn id(&self) -> i32;
+}
+
+pub trait Intersect<T: Shape> {
+    // only intersections with t_min <= t <= t_max are reported
+    fn intersect_range(&self, ray: &Ray, t_min: f32, t_max: f32) -> Intersections<'_, T>;
     fn normal_at(&self, point: Tuple) -> Tuple;
+
+    // normal at the moment `time`; only moving shapes need to override this
+    fn normal_at_time(&self, point: Tuple, _time: f32) -> Tuple {
+        self.normal_at(point)
+    }
+
+    // normal at a hit this shape reported, at world space `point`; shapes
+    // that record where on their surface a hit landed override this to use
+    // it instead of searching for it
+    fn normal_at_hit(&self, _hit: &Intersection<'_, T>, point: Tuple) -> Tuple {
+        self.normal_at(point)
+    }
+
+    fn intersect(&self, ray: &Ray) -> Intersections<'_, T> {
+        self.intersect_range(ray, f32::NEG_INFINITY, f32::INFINITY)
+    }
+
+    // one Intersections per ray, in order; shapes override this to share
+    // per-shape work such as inverting the transform across the batch
+    fn intersect_batch(&self, rays: &[Ray]) -> Vec<Intersections<'_, T>> {
+        rays.iter().map(|ray| self.intersect(ray)).collect()
+    }
+
+    fn intersect_packet(&self, packet: &RayPacket) -> Vec<Intersections<'_, T>> {
+        self.intersect_batch(&packet.rays())
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
-pub struct Intersection<'a, T: PartialEq + Clone> {
+#[derive(Debug)]
+pub struct Intersection<'a, T: Shape> {
     object: &'a T,
-    point: f32
+    point: f32,
+    // face index and barycentric (u, v) for shapes made of triangles
+    face: Option<(usize, f32, f32)>
 }
 
-pub struct Intersections<'a, T: PartialEq + Clone> {
+// manual impls: the derives would needlessly require T: Clone
+impl<'a, T: Shape> Clone for Intersection<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T: Shape> Copy for Intersection<'a, T> {}
+
+pub struct Intersections<'a, T: Shape> {
     pos_intersections: BinaryHeap<Reverse<Intersection<'a, T>>>,
     neg_intersections: BinaryHeap<Reverse<Intersection<'a, T>>>,
     merged_intersections: Vec<Intersection<'a, T>>
 
 }
 
-impl<'a, T: PartialEq + Clone> Intersections<'a, T> {
+impl<'a, T: Shape> Intersections<'a, T> {
     pub fn new(intersections: Vec<Intersection<'a, T>>) -> Self {
         let mut pos_intersections = BinaryHeap::with_capacity(intersections.len());
         let mut neg_intersections = BinaryHeap::with_capacity(intersections.len());
         let mut merged_intersections = Vec::with_capacity(intersections.len());
         for i in intersections {
-            merged_intersections.push(i.clone());
+            merged_intersections.push(i);
             if i.point > 0.0 {
                 pos_intersections.push(Reverse(i));
             } else {
@@ -52,7 +96,7 @@ impl<'a, T: PartialEq + Clone> Intersections<'a, T> {
     }
 
     pub fn add_point(&mut self, intersection: Intersection<'a, T>) {
-        self.merged_intersections.push(intersection.clone());
+        self.merged_intersections.push(intersection);
         if intersection.point > 0.0 {
             self.pos_intersections.push(Reverse(intersection));
         } else {
@@ -70,16 +114,20 @@ impl<'a, T: PartialEq + Clone> Intersections<'a, T> {
     pub fn len(&self) -> usize {
         self.merged_intersections.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.merged_intersections.is_empty()
+    }
 }
 
-impl<'a, T: PartialEq + Clone> Index<usize> for Intersections<'a, T> {
+impl<'a, T: Shape> Index<usize> for Intersections<'a, T> {
     type Output = Intersection<'a, T>;
     fn index(&self, idx: usize) -> &Self::Output {
         &self.merged_intersections[idx]
     }
 }
 
-impl<'a, T: PartialEq + Clone> Ord for Intersection<'a, T> {
+impl<'a, T: Shape> Ord for Intersection<'a, T> {
     fn cmp(&self, other: &Self) -> Ordering {
         if utils::is_equal(self.point, other.point) {
             return Ordering::Equal;
@@ -92,42 +140,58 @@ impl<'a, T: PartialEq + Clone> Ord for Intersection<'a, T> {
     }
 }
 
-impl<'a, T: PartialEq + Clone> PartialOrd for Intersection<'a, T> {
+impl<'a, T: Shape> PartialOrd for Intersection<'a, T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if utils::is_equal(self.point, other.point) {
-            return Some(Ordering::Equal);
-        }
-        if self.point < other.point {
-            return Some(Ordering::Less);
-        } else {
-            return Some(Ordering::Greater);
-        }
+        Some(self.cmp(other))
     }
 }
 
-impl<'a, T: PartialEq + Clone> PartialEq for Intersection<'a, T> {
+impl<'a, T: Shape> PartialEq for Intersection<'a, T> {
     fn eq(&self, other: &Self) -> bool {
-        self.object == other.object && self.point == other.point
+        // identity, not value: two identical spheres are still different hits
+        std::ptr::eq(self.object, other.object) && self.point == other.point
     }
 }
 
 
-impl<'a, T: PartialEq + Clone> Intersection<'a, T> {
+impl<'a, T: Shape> Intersection<'a, T> {
 
     pub fn new(object: &'a T, point: f32) -> Self {
-        Self {object, point}
+        Self {object, point, face: None}
+    }
+
+    pub fn on_face(object: &'a T, point: f32, face: usize, u: f32, v: f32) -> Self {
+        Self {object, point, face: Some((face, u, v))}
+    }
+
+    // the same hit reported against another object, e.g. an instance
+    // wrapping the shape that was hit
+    pub fn for_object<'b, U: Shape>(&self, object: &'b U) -> Intersection<'b, U> {
+        Intersection {object, point: self.point, face: self.face}
     }
 
     pub fn object(&self) -> &T {
         self.object
     }
 
+    pub fn object_id(&self) -> i32 {
+        self.object.id()
+    }
+
     pub fn point(&self) -> f32 {
         self.point
     }
+
+    pub fn face(&self) -> Option<usize> {
+        self.face.map(|(face, _, _)| face)
+    }
+
+    pub fn barycentric(&self) -> Option<(f32, f32)> {
+        self.face.map(|(_, u, v)| (u, v))
+    }
 }
 
-impl<'a, T: PartialEq + Clone> Eq for Intersection<'a, T> {}
+impl<'a, T: Shape> Eq for Intersection<'a, T> {}
 
 #[cfg(test)]
 mod tests {
@@ -147,7 +211,7 @@ mod tests {
         let s = Sphere::new(1);
         let i1 = Intersection::new(&s, 1.0);
         let i2 = Intersection::new(&s, 2.0);
-        let xs = Intersections::new(vec![i1.clone(), i2]);
+        let xs = Intersections::new(vec![i1, i2]);
         let h = xs.hit().unwrap();
         assert_eq!(*h, i1);
     }
@@ -157,7 +221,7 @@ mod tests {
         let s = Sphere::new(1);
         let i1 = Intersection::new(&s, -11.0);
         let i2 = Intersection::new(&s, 1.0);
-        let xs = Intersections::new(vec![i1.clone(), i2.clone()]);
+        let xs = Intersections::new(vec![i1, i2]);
         let h = xs.hit().unwrap();
         assert_eq!(*h, i2);
     }
@@ -167,7 +231,7 @@ mod tests {
         let s = Sphere::new(1);
         let i1 = Intersection::new(&s, -2.0);
         let i2 = Intersection::new(&s, -1.0);
-        let xs = Intersections::new(vec![i1.clone(), i2.clone()]);
+        let xs = Intersections::new(vec![i1, i2]);
         let h = xs.hit();
         assert_eq!(h, None);
     }
@@ -179,10 +243,19 @@ mod tests {
         let i2 = Intersection::new(&s, 7.0);
         let i3 = Intersection::new(&s, -3.0);
         let i4 = Intersection::new(&s, 2.0);
-        let xs = Intersections::new(vec![i3.clone(), i4.clone(), i1.clone(), i2.clone()]);
+        let xs = Intersections::new(vec![i3, i4, i1, i2]);
         let h = xs.hit().unwrap();
         assert_eq!(*h, i4);
 
     }
 
+    #[test]
+    fn test_intersections_compare_by_identity() {
+        let a = Sphere::new(1);
+        let b = Sphere::new(1);
+        assert_ne!(Intersection::new(&a, 2.0), Intersection::new(&b, 2.0));
+        assert_eq!(Intersection::new(&a, 2.0), Intersection::new(&a, 2.0));
+        let c = Sphere::new(7);
+        assert_eq!(Intersection::new(&c, 1.0).object_id(), 7);
+    }
 }
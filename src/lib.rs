@@ -1,7 +1,11 @@
+#[macro_use]
+mod ops;
+
 pub mod tuple;
 pub mod color;
 pub mod canvas;
 pub mod matrix;
+pub mod matrix4;
 pub mod utils;
 pub mod transform;
 pub mod ray;
@@ -9,14 +13,49 @@ pub mod sphere;
 pub mod intersection;
 pub mod light;
 pub mod material;
+pub mod voxel;
+pub mod volume;
+pub mod sky;
+pub mod camera;
+pub mod sampler;
+pub mod tile;
+pub mod progress;
+pub mod cancel;
+pub mod quality;
+pub mod watchdog;
+pub mod preview;
+pub mod compare;
+pub mod texture;
+pub mod turntable;
+pub mod fog;
+pub mod presets;
+pub mod mesh;
+pub mod instance;
+pub mod octree;
+pub mod shading;
 
 pub use tuple::Tuple;
-pub use color::Color;
-pub use canvas::Canvas;
-pub use matrix::Matrix;
+pub use color::{Color, Encoding};
+pub use canvas::{BlendMode, Canvas, ResizeFilter};
+pub use matrix::{Matrix, MatrixError};
+pub use matrix4::{Matrix2, Matrix3, Matrix4};
 pub use transform::TransformBuilder;
-pub use ray::Ray;
+pub use ray::{Ray, RayPacket};
 pub use sphere::Sphere;
-pub use intersection::Intersect;
+pub use intersection::{Intersect, Shape};
 pub use light::PointLight;
-pub use material::Material;
\ No newline at end of file
+pub use material::{Material, MaterialModel, PbrMaterial, Sheen, SpecularModel, ThinFilm};
+pub use voxel::VoxelGrid;
+pub use mesh::TriangleMesh;
+pub use instance::Instance;
+pub use octree::Octree;
+pub use shading::{Toon, ToonMaterial, Wireframe};
+pub use volume::{ConstantMedium, DensityGrid};
+pub use sky::{Sky, SolarTime};
+pub use camera::{Camera, Foveation, PixelTrace, Projection, TraceEvent, TraceLog, TraceSample};
+pub use sampler::{Rng, Sampler};
+pub use tile::{PixelOrder, Tile, TileOrder};
+pub use progress::{Progress, RenderProgress};
+pub use cancel::CancellationToken;
+pub use quality::Quality;
+pub use fog::{Fog, FogFalloff};
\ No newline at end of file
@@ -1,6 +1,116 @@
 use super::color::Color;
 use super::light::PointLight;
+use super::sampler::Rng;
 use super::tuple::Tuple;
+use std::f32::consts::PI;
+
+// representative wavelengths (nm) for the red, green and blue channels
+const WAVELENGTHS: [f32; 3] = [650.0, 510.0, 475.0];
+
+// a thin transparent coating (soap bubble, oil slick) whose interference
+// tints the specular highlight depending on the viewing angle
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ThinFilm {
+    // film thickness in nanometres
+    pub thickness: f32,
+    pub ior: f32
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f32, ior: f32) -> Self {
+        Self {thickness, ior}
+    }
+
+    // reflected fraction per channel for light hitting the film with
+    // cos_theta between the view direction and the normal
+    pub fn tint(&self, cos_theta: f32) -> Color {
+        let sin2_i = 1.0 - cos_theta * cos_theta;
+        let cos_t = (1.0 - sin2_i / (self.ior * self.ior)).max(0.0).sqrt();
+        // path difference between the rays reflected at the two surfaces;
+        // the half-wave shift at the top surface turns cos into sin
+        let opd = 2.0 * self.ior * self.thickness * cos_t;
+        let channel = |wavelength: f32| (PI * opd / wavelength).sin().powi(2);
+        Color::new(channel(WAVELENGTHS[0]), channel(WAVELENGTHS[1]), channel(WAVELENGTHS[2]))
+    }
+}
+
+// velvet-style retroreflective fuzz: brightens the silhouette of cloth
+// instead of producing a sharp highlight
+#[derive(Debug, PartialEq, Clone)]
+pub struct Sheen {
+    pub color: Color,
+    // higher values push the sheen further out towards the edges
+    pub falloff: f32
+}
+
+impl Sheen {
+    pub fn new(color: Color, falloff: f32) -> Self {
+        Self {color, falloff}
+    }
+
+    // strength of the lobe for a lit point seen with cos_theta between the
+    // view direction and the normal
+    pub fn factor(&self, cos_theta: f32) -> f32 {
+        (1.0 - cos_theta.abs().min(1.0)).powf(self.falloff)
+    }
+}
+
+// A reflectance model that can be lit directly or sampled by a path tracer.
+// Material (Phong) and PbrMaterial (GGX) both implement it, so shading code
+// can take either.
+pub trait MaterialModel {
+    // colour reflected from ambient light of unit intensity
+    fn ambient(&self) -> Color;
+
+    // Light reflected towards wo per unit of light arriving from wi (both
+    // pointing away from the surface): pi * f * (wi . normal) for a textbook
+    // BRDF f, so a Lambertian surface returns its albedo times the cosine
+    // with no 1 / pi. lighting() is then exactly ambient + intensity * eval
+    // and a white matte surface lit head on reflects all of it.
+    fn eval(&self, wi: &Tuple, wo: &Tuple, normal: &Tuple) -> Color;
+
+    // Picks an incoming direction wi for the outgoing direction wo. Returns
+    // wi and its probability density (per steradian), or None if the sample
+    // fell below the surface.
+    fn sample(&self, wo: &Tuple, normal: &Tuple, rng: &mut Rng) -> Option<(Tuple, f32)>;
+
+    // density with which sample() returns wi
+    fn pdf(&self, wi: &Tuple, wo: &Tuple, normal: &Tuple) -> f32;
+
+    // Whether the back of a surface is lit like its front (for planes and
+    // other open surfaces seen from behind). Single-sided shading uses the
+    // normal as given, which is the default to keep existing scenes unchanged.
+    fn double_sided(&self) -> bool {
+        false
+    }
+
+    fn lighting(&self, light: &PointLight, position: &Tuple, eyev: &Tuple, normalv: &Tuple) -> Color {
+        let ambient = self.ambient() * light.intensity();
+        let lightv = (light.position() - position).normalize();
+        let normalv = if self.double_sided() { face_forward(normalv, eyev) } else { *normalv };
+        return ambient + light.intensity() * self.eval(&lightv, eyev, &normalv);
+    }
+}
+
+// the normal flipped if needed to point to the same side as the eye
+pub fn face_forward(normal: &Tuple, eye: &Tuple) -> Tuple {
+    if normal.dot(eye) < 0.0 {
+        return -normal;
+    }
+    return *normal;
+}
+
+// shape of the highlight
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SpecularModel {
+    // (reflected light . eye) ^ shininess
+    Phong,
+    // (half vector . normal) ^ shininess; keeps an elongated highlight at
+    // grazing angles where Phong's gets clipped. The same shininess gives a
+    // wider highlight than Phong, roughly matching Phong at a quarter of it
+    BlinnPhong,
+    None
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Material {
@@ -8,35 +118,297 @@ pub struct Material {
     pub ambient: f32,
     pub diffuse: f32,
     pub specular: f32,
-    pub shininess: f32
+    pub shininess: f32,
+    pub specular_model: SpecularModel,
+    pub double_sided: bool,
+    pub thin_film: Option<ThinFilm>,
+    pub sheen: Option<Sheen>
 }
 
 impl Material {
     pub fn new() -> Self {
-        Self {color: Color::new(1.0, 1.0, 1.0),
+        Self {color: Color::WHITE,
             ambient: 0.1,
             diffuse: 0.9,
             specular: 0.9,
-            shininess: 200.0}
+            shininess: 200.0,
+            specular_model: SpecularModel::Phong,
+            double_sided: false,
+            thin_film: None,
+            sheen: None}
+    }
+
+    // a copy with the hue rotated by up to +/- hue_range radians and the
+    // shininess scaled by up to +/- shininess_range, both driven by `random`
+    // in [0, 1) (e.g. Sphere::random_value) so instances vary consistently
+    pub fn vary(&self, random: f32, hue_range: f32, shininess_range: f32) -> Material {
+        let t = 2.0 * random - 1.0;
+        let mut material = self.clone();
+        material.color = rotate_hue(&self.color, t * hue_range);
+        material.shininess = self.shininess * (1.0 + t * shininess_range);
+        return material;
     }
 
     pub fn lighting(&self, light: &PointLight, position: &Tuple, eyev: &Tuple, normalv: &Tuple) -> Color {
-        let effective_color = &self.color * light.intensity();
+        let ambient = &self.color * light.intensity() * self.ambient;
         let lightv = (light.position() - position).normalize();
-        let ambient = &effective_color * self.ambient;
-        let light_dot_normal = lightv.dot(normalv);
-        let mut diffuse = Color::black();
-        let mut specular = Color::black();
-        if light_dot_normal >= 0.0 {
-            diffuse = effective_color * self.diffuse * light_dot_normal;
-            let reflectv = -lightv.reflect(normalv);
-            let reflect_dot_eye = eyev.dot(&reflectv);
-            if reflect_dot_eye > 0.0 {
-                let factor = reflect_dot_eye.powf(self.shininess);
-                specular = light.intensity() * self.specular * factor;
+        let normalv = if self.double_sided { face_forward(normalv, eyev) } else { *normalv };
+        return ambient + light.intensity() * self.eval(&lightv, eyev, &normalv);
+    }
+
+    // see MaterialModel::eval
+    pub fn eval(&self, wi: &Tuple, wo: &Tuple, normal: &Tuple) -> Color {
+        let light_dot_normal = wi.dot(normal);
+        if light_dot_normal < 0.0 {
+            return Color::black();
+        }
+        let mut result = &self.color * self.diffuse * light_dot_normal;
+        let highlight = self.highlight(wi, wo, normal);
+        if highlight > 0.0 {
+            let mut specular = Color::WHITE * self.specular * highlight.powf(self.shininess);
+            if let Some(film) = &self.thin_film {
+                specular *= film.tint(wo.dot(normal).abs());
             }
+            result += specular;
+        }
+        if let Some(sheen) = &self.sheen {
+            result += &sheen.color * (sheen.factor(wo.dot(normal)) * light_dot_normal);
         }
-        return ambient + diffuse + specular;
+        return result;
+    }
+
+    // Picks an incoming direction wi for the outgoing direction wo, cosine
+    // weighted for the diffuse lobe, around the mirror direction for the
+    // Phong lobe and by half vector for Blinn-Phong. Returns wi and its
+    // probability density (per steradian), or None if the sample fell below
+    // the surface.
+    pub fn sample(&self, wo: &Tuple, normal: &Tuple, rng: &mut Rng) -> Option<(Tuple, f32)> {
+        let (u1, u2) = (rng.next_f32(), rng.next_f32());
+        let wi = if rng.next_f32() < self.specular_weight() {
+            let cos_alpha = u1.powf(1.0 / (self.shininess + 1.0));
+            if self.specular_model == SpecularModel::BlinnPhong {
+                let half = from_local(normal, cos_alpha, 2.0 * PI * u2);
+                (-wo).reflect(&half)
+            } else {
+                let mirror = (-wo).reflect(normal);
+                from_local(&mirror, cos_alpha, 2.0 * PI * u2)
+            }
+        } else {
+            from_local(normal, (1.0 - u1).sqrt(), 2.0 * PI * u2)
+        };
+        if wi.dot(normal) <= 0.0 {
+            return None;
+        }
+        Some((wi, self.pdf(&wi, wo, normal)))
+    }
+
+    // density with which sample() returns wi
+    pub fn pdf(&self, wi: &Tuple, wo: &Tuple, normal: &Tuple) -> f32 {
+        let cos_theta = wi.dot(normal);
+        if cos_theta <= 0.0 {
+            return 0.0;
+        }
+        let weight = self.specular_weight();
+        let lobe = |cos_alpha: f32| (self.shininess + 1.0) / (2.0 * PI) * cos_alpha.max(0.0).powf(self.shininess);
+        let specular = match self.specular_model {
+            SpecularModel::Phong => lobe(wi.dot(&(-wo).reflect(normal))),
+            // change of variables from the half vector to wi
+            SpecularModel::BlinnPhong => {
+                let half = (wi + wo).normalize();
+                let wo_dot_half = wo.dot(&half);
+                if wo_dot_half <= 0.0 { 0.0 } else { lobe(half.dot(normal)) / (4.0 * wo_dot_half) }
+            },
+            SpecularModel::None => 0.0
+        };
+        return (1.0 - weight) * cos_theta / PI + weight * specular;
+    }
+
+    // the cosine the specular model raises to the shininess
+    fn highlight(&self, wi: &Tuple, wo: &Tuple, normal: &Tuple) -> f32 {
+        match self.specular_model {
+            SpecularModel::Phong => wo.dot(&-wi.reflect(normal)),
+            SpecularModel::BlinnPhong => (wi + wo).normalize().dot(normal),
+            SpecularModel::None => 0.0
+        }
+    }
+
+    // chance of sampling the specular lobe rather than the diffuse one
+    fn specular_weight(&self) -> f32 {
+        if self.specular_model == SpecularModel::None {
+            return 0.0;
+        }
+        let diffuse = self.diffuse * (self.color.red() + self.color.green() + self.color.blue()) / 3.0;
+        let total = diffuse + self.specular;
+        if total <= 0.0 {
+            return 0.0;
+        }
+        self.specular / total
+    }
+}
+
+impl MaterialModel for Material {
+    fn ambient(&self) -> Color {
+        &self.color * self.ambient
+    }
+
+    fn double_sided(&self) -> bool {
+        self.double_sided
+    }
+
+    fn eval(&self, wi: &Tuple, wo: &Tuple, normal: &Tuple) -> Color {
+        Material::eval(self, wi, wo, normal)
+    }
+
+    fn sample(&self, wo: &Tuple, normal: &Tuple, rng: &mut Rng) -> Option<(Tuple, f32)> {
+        Material::sample(self, wo, normal, rng)
+    }
+
+    fn pdf(&self, wi: &Tuple, wo: &Tuple, normal: &Tuple) -> f32 {
+        Material::pdf(self, wi, wo, normal)
+    }
+
+    fn lighting(&self, light: &PointLight, position: &Tuple, eyev: &Tuple, normalv: &Tuple) -> Color {
+        Material::lighting(self, light, position, eyev, normalv)
+    }
+}
+
+// Physically based metal/roughness material: a Lambertian base under a GGX
+// microfacet specular lobe with Schlick Fresnel and Smith shadowing, as in
+// most modern renderers' "principled" materials
+#[derive(Debug, PartialEq, Clone)]
+pub struct PbrMaterial {
+    // albedo for dielectrics, reflectance at normal incidence for metals
+    pub color: Color,
+    // 0 is a perfect mirror, 1 fully rough
+    pub roughness: f32,
+    // 0 for dielectrics, 1 for metals; values between blend the two
+    pub metallic: f32,
+    pub ambient: f32,
+    pub double_sided: bool
+}
+
+impl PbrMaterial {
+    pub fn new(color: Color, roughness: f32, metallic: f32) -> Self {
+        Self {color, roughness, metallic, ambient: 0.1, double_sided: false}
+    }
+
+    // GGX alpha; clamped so a roughness of 0 doesn't divide by zero
+    fn alpha(&self) -> f32 {
+        (self.roughness * self.roughness).max(1e-3)
+    }
+
+    // reflectance at normal incidence: 4% for dielectrics, the base colour for metals
+    fn f0(&self) -> Color {
+        let metallic = self.metallic.clamp(0.0, 1.0);
+        return Color::WHITE * (0.04 * (1.0 - metallic)) + &self.color * metallic;
+    }
+
+    fn fresnel(&self, cos_theta: f32) -> Color {
+        let f0 = self.f0();
+        let weight = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+        return &f0 + (Color::WHITE - &f0) * weight;
+    }
+
+    fn distribution(&self, n_dot_h: f32) -> f32 {
+        let a2 = self.alpha() * self.alpha();
+        let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+        return a2 / (PI * d * d);
+    }
+
+    // Smith-Schlick masking for one direction
+    fn masking(&self, cos_theta: f32) -> f32 {
+        let k = self.alpha() / 2.0;
+        return cos_theta / (cos_theta * (1.0 - k) + k);
+    }
+
+    fn specular_weight(&self) -> f32 {
+        (1.0 + self.metallic.clamp(0.0, 1.0)) / 2.0
+    }
+}
+
+impl MaterialModel for PbrMaterial {
+    fn ambient(&self) -> Color {
+        &self.color * self.ambient
+    }
+
+    fn double_sided(&self) -> bool {
+        self.double_sided
+    }
+
+    fn eval(&self, wi: &Tuple, wo: &Tuple, normal: &Tuple) -> Color {
+        let n_dot_l = wi.dot(normal);
+        let n_dot_v = wo.dot(normal);
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return Color::black();
+        }
+        let half = (wi + wo).normalize();
+        let fresnel = self.fresnel(wo.dot(&half));
+        let d = self.distribution(half.dot(normal).max(0.0));
+        let g = self.masking(n_dot_l) * self.masking(n_dot_v);
+        // pi * (D G F / (4 n.l n.v)) * n.l
+        let specular = &fresnel * (PI * d * g / (4.0 * n_dot_v));
+        let diffuse = (Color::WHITE - &fresnel) * &self.color * ((1.0 - self.metallic.clamp(0.0, 1.0)) * n_dot_l);
+        return diffuse + specular;
+    }
+
+    // cosine weighted for the diffuse lobe, GGX half vectors for the specular one
+    fn sample(&self, wo: &Tuple, normal: &Tuple, rng: &mut Rng) -> Option<(Tuple, f32)> {
+        let (u1, u2) = (rng.next_f32(), rng.next_f32());
+        let wi = if rng.next_f32() < self.specular_weight() {
+            let a2 = self.alpha() * self.alpha();
+            let cos_h = ((1.0 - u1) / (1.0 + (a2 - 1.0) * u1)).sqrt();
+            let half = from_local(normal, cos_h, 2.0 * PI * u2);
+            (-wo).reflect(&half)
+        } else {
+            from_local(normal, (1.0 - u1).sqrt(), 2.0 * PI * u2)
+        };
+        if wi.dot(normal) <= 0.0 {
+            return None;
+        }
+        Some((wi, self.pdf(&wi, wo, normal)))
+    }
+
+    fn pdf(&self, wi: &Tuple, wo: &Tuple, normal: &Tuple) -> f32 {
+        let cos_theta = wi.dot(normal);
+        if cos_theta <= 0.0 {
+            return 0.0;
+        }
+        let weight = self.specular_weight();
+        let half = (wi + wo).normalize();
+        let wo_dot_half = wo.dot(&half);
+        let n_dot_h = half.dot(normal);
+        let specular = if wo_dot_half <= 0.0 || n_dot_h <= 0.0 {
+            0.0
+        } else {
+            self.distribution(n_dot_h) * n_dot_h / (4.0 * wo_dot_half)
+        };
+        return (1.0 - weight) * cos_theta / PI + weight * specular;
+    }
+}
+
+// rotates the colour around the grey axis r = g = b, keeping its brightness
+fn rotate_hue(color: &Color, angle: f32) -> Color {
+    let (c, s) = (angle.cos(), angle.sin());
+    let k = (1.0 - c) / 3.0;
+    let q = s / 3f32.sqrt();
+    let (r, g, b) = (color.red(), color.green(), color.blue());
+    Color::new(r * (c + k) + g * (k - q) + b * (k + q),
+        r * (k + q) + g * (c + k) + b * (k - q),
+        r * (k - q) + g * (k + q) + b * (c + k))
+}
+
+// direction at angle acos(cos_theta) from `axis`, rotated by phi around it
+fn from_local(axis: &Tuple, cos_theta: f32, phi: f32) -> Tuple {
+    let helper = if axis.x().abs() > 0.9 { Tuple::vector(0.0, 1.0, 0.0) } else { Tuple::vector(1.0, 0.0, 0.0) };
+    let tangent = helper.cross(axis).normalize();
+    let bitangent = axis.cross(&tangent);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    return tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + axis * cos_theta;
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -100,5 +472,257 @@ mod tests {
         let result = m.lighting(&light, &position, &eyev, &normalv);
         assert_eq!(result, Color::new(0.1, 0.1, 0.1));
     }
+
+    #[test]
+    fn thin_film_tint_depends_on_angle() {
+        let film = ThinFilm::new(300.0, 1.33);
+        let head_on = film.tint(1.0);
+        let grazing = film.tint(0.2);
+        assert_ne!(head_on, grazing);
+        // channels interfere differently, so the highlight is coloured
+        assert_ne!(head_on.red(), head_on.green());
+        // a film much thinner than visible wavelengths reflects almost nothing
+        let tint = ThinFilm::new(1.0, 1.33).tint(1.0);
+        assert!(tint.red() < 1e-3 && tint.blue() < 1e-3);
+    }
+
+    #[test]
+    fn lighting_with_thin_film_tints_specular() {
+        let mut m = Material::new();
+        m.thin_film = Some(ThinFilm::new(300.0, 1.33));
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let val = 2f32.sqrt() / 2.0;
+        let eyev = Tuple::vector(0.0, -val, -val);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Color::new(1.0, 1.0, 1.0), Tuple::point(0.0, 10.0, -10.0));
+        let plain = Material::new().lighting(&light, &position, &eyev, &normalv);
+        let result = m.lighting(&light, &position, &eyev, &normalv);
+        // ambient + diffuse are unchanged, only the 0.9 specular is scaled
+        let tint = ThinFilm::new(300.0, 1.33).tint(val);
+        let expected = plain - Color::new(0.9, 0.9, 0.9) * (Color::new(1.0, 1.0, 1.0) - tint);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn sheen_brightens_edges() {
+        let sheen = Sheen::new(Color::WHITE, 2.0);
+        assert_eq!(sheen.factor(1.0), 0.0);
+        assert_eq!(sheen.factor(0.0), 1.0);
+        assert!(sheen.factor(0.3) > sheen.factor(0.7));
+    }
+
+    #[test]
+    fn lighting_with_sheen() {
+        let mut m = Material::new();
+        m.specular = 0.0;
+        m.sheen = Some(Sheen::new(Color::new(0.5, 0.5, 0.5), 1.0));
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Color::WHITE, Tuple::point(0.0, 0.0, -10.0));
+        // looking straight on there is no sheen
+        let head_on = m.lighting(&light, &position, &Tuple::vector(0.0, 0.0, -1.0), &normalv);
+        assert_eq!(head_on, Color::new(1.0, 1.0, 1.0));
+        // at a grazing view the edge picks up 0.5 * (1 - 0.1)
+        let grazing = Tuple::vector(0.0, 0.99f32.sqrt(), -0.1);
+        let edge = m.lighting(&light, &position, &grazing, &normalv);
+        assert_eq!(edge, Color::new(1.45, 1.45, 1.45));
+    }
+
+    #[test]
+    fn eval_matches_lighting() {
+        let m = Material::new();
+        let val = 2f32.sqrt() / 2.0;
+        let wi = Tuple::vector(0.0, val, -val);
+        let wo = Tuple::vector(0.0, -val, -val);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Color::WHITE, Tuple::point(0.0, 10.0, -10.0));
+        let lit = m.lighting(&light, &Tuple::point(0.0, 0.0, 0.0), &wo, &normalv);
+        assert_eq!(lit, Color::new(0.1, 0.1, 0.1) + m.eval(&wi, &wo, &normalv));
+        assert_eq!(m.eval(&-wi, &wo, &normalv), Color::black());
+    }
+
+    #[test]
+    fn samples_stay_above_the_surface() {
+        let m = Material::new();
+        let normal = Tuple::vector(0.0, 1.0, 0.0);
+        let wo = Tuple::vector(1.0, 1.0, 0.0).normalize();
+        let mut rng = Rng::new(3);
+        for _ in 0..100 {
+            if let Some((wi, pdf)) = m.sample(&wo, &normal, &mut rng) {
+                assert!(wi.dot(&normal) > 0.0);
+                assert!((wi.magnitude() - 1.0).abs() < 1e-4);
+                assert!(pdf > 0.0);
+                assert_eq!(pdf, m.pdf(&wi, &wo, &normal));
+            }
+        }
+    }
+
+    #[test]
+    fn diffuse_sampling_is_cosine_weighted() {
+        let mut m = Material::new();
+        m.specular = 0.0;
+        let normal = Tuple::vector(0.0, 0.0, 1.0);
+        let wo = normal;
+        let mut rng = Rng::new(11);
+        let n = 4000;
+        let mut mean_cos = 0.0;
+        for _ in 0..n {
+            let (wi, pdf) = m.sample(&wo, &normal, &mut rng).unwrap();
+            assert!((pdf - wi.z() / PI).abs() < 1e-4);
+            mean_cos += wi.z() / n as f32;
+        }
+        // E[cos] under a cosine-weighted hemisphere is 2/3
+        assert!((mean_cos - 2.0 / 3.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn rotate_hue_around_grey() {
+        let third = 2.0 * PI / 3.0;
+        assert_eq!(rotate_hue(&Color::RED, third), Color::GREEN);
+        assert_eq!(rotate_hue(&Color::GREY, 1.0), Color::GREY);
+        assert_eq!(rotate_hue(&Color::new(0.2, 0.6, 0.1), 0.0), Color::new(0.2, 0.6, 0.1));
+    }
+
+    #[test]
+    fn vary_material_per_instance() {
+        let mut m = Material::new();
+        m.color = Color::RED;
+        // random = 0.5 is the centre of the range: no change
+        assert_eq!(m.vary(0.5, 1.0, 0.5), m);
+        let varied = m.vary(1.0, 2.0 * PI / 3.0, 0.5);
+        assert_eq!(varied.color, Color::GREEN);
+        assert_eq!(varied.shininess, 300.0);
+        assert_eq!(m.vary(0.0, 0.0, 0.5).shininess, 100.0);
+    }
+
+    #[test]
+    fn blinn_phong_keeps_grazing_highlights() {
+        let mut m = Material::new();
+        m.shininess = 10.0;
+        let val = 2f32.sqrt() / 2.0;
+        let wi = Tuple::vector(0.0, 0.0, -1.0);
+        let wo = Tuple::vector(0.0, val, -val);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let phong = m.eval(&wi, &wo, &normalv);
+        m.specular_model = SpecularModel::BlinnPhong;
+        let blinn = m.eval(&wi, &wo, &normalv);
+        // the half vector is only 22.5 degrees off the normal
+        assert_eq!(blinn, Color::WHITE * (0.9 + 0.9 * (PI / 8.0).cos().powf(10.0)));
+        assert!(blinn.red() > phong.red());
+        // both peak at the mirror direction
+        let mirror = Tuple::vector(0.0, -val, -val);
+        let light = PointLight::new(Color::WHITE, Tuple::point(0.0, 10.0, -10.0));
+        let lit = m.lighting(&light, &Tuple::point(0.0, 0.0, 0.0), &mirror, &normalv);
+        assert_eq!(lit, Color::new(1.6364, 1.6364, 1.6364));
+    }
+
+    #[test]
+    fn no_specular_model() {
+        let mut m = Material::new();
+        m.specular_model = SpecularModel::None;
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Color::WHITE, Tuple::point(0.0, 0.0, -10.0));
+        let lit = m.lighting(&light, &Tuple::point(0.0, 0.0, 0.0), &normalv, &normalv);
+        assert_eq!(lit, Color::new(1.0, 1.0, 1.0));
+        assert_eq!(m.specular_weight(), 0.0);
+    }
+
+    #[test]
+    fn blinn_phong_sampling_matches_pdf() {
+        let mut m = Material::new();
+        m.diffuse = 0.0;
+        m.shininess = 10.0;
+        m.specular_model = SpecularModel::BlinnPhong;
+        let normal = Tuple::vector(0.0, 0.0, 1.0);
+        let wo = normal;
+        let mut rng = Rng::new(5);
+        for _ in 0..100 {
+            if let Some((wi, pdf)) = m.sample(&wo, &normal, &mut rng) {
+                assert!(wi.dot(&normal) > 0.0);
+                assert!(pdf > 0.0);
+            }
+        }
+        // the pdf integrates to one over the hemisphere (uniform estimate)
+        let n = 20000;
+        let mut integral = 0.0;
+        for _ in 0..n {
+            let wi = from_local(&normal, rng.next_f32(), 2.0 * PI * rng.next_f32());
+            integral += m.pdf(&wi, &wo, &normal) * 2.0 * PI / n as f32;
+        }
+        assert!((integral - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn pbr_dielectric_and_metal() {
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+        // a rough white dielectric lit head on is close to a matte surface
+        let plastic = PbrMaterial::new(Color::WHITE, 1.0, 0.0);
+        let lit = plastic.eval(&normal, &normal, &normal);
+        assert!(lit.red() > 0.9 && lit.red() < 1.1);
+        // metals have no diffuse term and tint their reflections
+        let gold = PbrMaterial::new(Color::new(1.0, 0.78, 0.34), 0.3, 1.0);
+        let val = 2f32.sqrt() / 2.0;
+        let off_peak = gold.eval(&Tuple::vector(0.0, val, -val), &Tuple::vector(0.0, val, -val), &normal);
+        assert!(off_peak.red() < 0.1);
+        let peak = gold.eval(&normal, &normal, &normal);
+        assert!(peak.red() > peak.green() && peak.green() > peak.blue());
+        // smoother surfaces concentrate the highlight
+        let polished = PbrMaterial::new(Color::new(1.0, 0.78, 0.34), 0.1, 1.0);
+        assert!(polished.eval(&normal, &normal, &normal).red() > peak.red());
+        assert_eq!(gold.eval(&-normal, &normal, &normal), Color::black());
+    }
+
+    #[test]
+    fn pbr_lighting_through_the_trait() {
+        let m: Box<dyn MaterialModel> = Box::new(PbrMaterial::new(Color::RED, 0.5, 0.0));
+        let light = PointLight::new(Color::WHITE, Tuple::point(0.0, 0.0, -10.0));
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+        let lit = m.lighting(&light, &Tuple::point(0.0, 0.0, 0.0), &normal, &normal);
+        assert_eq!(lit, m.ambient() + m.eval(&normal, &normal, &normal));
+        assert!(lit.red() > lit.green());
+    }
+
+    #[test]
+    fn pbr_sampling_matches_pdf() {
+        let m = PbrMaterial::new(Color::GREY, 0.4, 0.5);
+        let normal = Tuple::vector(0.0, 0.0, 1.0);
+        let wo = Tuple::vector(0.3, 0.0, 1.0).normalize();
+        let mut rng = Rng::new(9);
+        for _ in 0..100 {
+            if let Some((wi, pdf)) = m.sample(&wo, &normal, &mut rng) {
+                assert!(wi.dot(&normal) > 0.0);
+                assert!((wi.magnitude() - 1.0).abs() < 1e-4);
+                assert!(pdf > 0.0);
+            }
+        }
+        // close to one: only half vectors reflecting below the surface are lost
+        let n = 20000;
+        let mut integral = 0.0;
+        for _ in 0..n {
+            let wi = from_local(&normal, rng.next_f32(), 2.0 * PI * rng.next_f32());
+            integral += m.pdf(&wi, &wo, &normal) * 2.0 * PI / n as f32;
+        }
+        assert!(integral > 0.9 && integral < 1.05);
+    }
+
+    #[test]
+    fn lighting_from_behind_a_surface() {
+        let mut m = Material::new();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        // the eye and light are both on the side the normal points away from
+        let eyev = Tuple::vector(0.0, 0.0, 1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Color::WHITE, Tuple::point(0.0, 0.0, 10.0));
+        assert_eq!(m.lighting(&light, &position, &eyev, &normalv), Color::new(0.1, 0.1, 0.1));
+        m.double_sided = true;
+        assert_eq!(m.lighting(&light, &position, &eyev, &normalv), Color::new(1.9, 1.9, 1.9));
+        // the PBR material follows the same flag
+        let mut pbr = PbrMaterial::new(Color::WHITE, 1.0, 0.0);
+        let back = pbr.lighting(&light, &position, &eyev, &normalv);
+        pbr.double_sided = true;
+        assert!(pbr.lighting(&light, &position, &eyev, &normalv).red() > back.red());
+        assert_eq!(face_forward(&normalv, &eyev), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(face_forward(&normalv, &-eyev), normalv);
+    }
 }
 
@@ -1,8 +1,32 @@
-use std::ops::{Index, Mul};
+use std::ops::{Index, IndexMut, Mul};
 use std::cmp::PartialEq;
+use std::fmt;
 use super::utils;
 use super::tuple::Tuple;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatrixError {
+    // det and inverse need a square matrix
+    NotSquare {rows: usize, cols: usize},
+    // the determinant is zero
+    Singular,
+    // left columns don't match right rows in a product
+    DimensionMismatch {left: (usize, usize), right: (usize, usize)}
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatrixError::NotSquare {rows, cols} => write!(f, "{}x{} matrix is not square", rows, cols),
+            MatrixError::Singular => write!(f, "matrix is singular"),
+            MatrixError::DimensionMismatch {left, right} =>
+                write!(f, "cannot multiply {}x{} by {}x{}", left.0, left.1, right.0, right.1)
+        }
+    }
+}
+
+impl std::error::Error for MatrixError {}
+
 #[derive(Debug, Clone)]
 pub struct Matrix {
     nrows: usize,
@@ -20,7 +44,7 @@ impl Matrix {
     pub fn identity(size: usize) -> Self {
         let mut matrix = Self::new(size, size);
         for i in 0..size {
-            matrix.set(i, i, 1.0);
+            matrix[(i, i)] = 1.0;
         }
         matrix
     }
@@ -37,6 +61,26 @@ impl Matrix {
         (self.nrows, self.ncols)
     }
 
+    pub fn row(&self, row: usize) -> &[f32] {
+        &self.vals[row * self.ncols..(row + 1) * self.ncols]
+    }
+
+    pub fn row_mut(&mut self, row: usize) -> &mut [f32] {
+        &mut self.vals[row * self.ncols..(row + 1) * self.ncols]
+    }
+
+    // columns aren't contiguous, so they are copied out
+    // panics if col is out of range, like row
+    pub fn col(&self, col: usize) -> Vec<f32> {
+        assert!(col < self.ncols, "column {} out of range for {} columns", col, self.ncols);
+        self.vals.iter().skip(col).step_by(self.ncols).copied().collect()
+    }
+
+    // elements in row-major order
+    pub fn iter(&self) -> std::slice::Iter<'_, f32> {
+        self.vals.iter()
+    }
+
     fn get_index(&self, row: usize, col: usize) -> Option<usize> {
         if row >= self.nrows || col >= self.ncols {
             return None;
@@ -64,17 +108,32 @@ impl Matrix {
     }
 
     pub fn transpose(&self) -> Self {
-        let mut transpose = Self::new(self.nrows, self.ncols);
+        let mut transpose = Self::new(self.ncols, self.nrows);
         for i in 0..self.nrows {
             for j in 0..self.ncols {
-                transpose.set(j, i, self[(i, j)]);
+                transpose[(j, i)] = self[(i, j)];
             }
         }
         transpose
     }
 
-    //TODO: determinant is only defined for square matrices, maybe we need to return a Result?
+    fn check_square(&self) -> Result<(), MatrixError> {
+        if self.nrows != self.ncols || self.nrows == 0 {
+            return Err(MatrixError::NotSquare {rows: self.nrows, cols: self.ncols});
+        }
+        Ok(())
+    }
+
+    pub fn try_det(&self) -> Result<f32, MatrixError> {
+        self.check_square()?;
+        Ok(self.det())
+    }
+
+    // only meaningful for square matrices, see try_det
     pub fn det(&self) -> f32 {
+        if self.nrows == 1 && self.ncols == 1 {
+            return self.vals[0];
+        }
         if self.nrows == 2 && self.ncols == 2 {
             return self.vals[0] * self.vals[3] - self.vals[1] * self.vals[2]
         }
@@ -98,7 +157,7 @@ impl Matrix {
                 if j == col {
                     continue;
                 }
-                submat.set(rowid, colid, self[(i, j)]);
+                submat[(rowid, colid)] = self[(i, j)];
                 colid += 1;
             }
             rowid += 1;
@@ -113,7 +172,7 @@ impl Matrix {
 
     pub fn cofactor(&self, row: usize, col: usize) -> f32 {
         let minor_val = self.minor(row, col);
-        if (row + col) % 2 != 0 {
+        if !(row + col).is_multiple_of(2) {
             return -minor_val;
         }
         minor_val
@@ -124,34 +183,55 @@ impl Matrix {
     }
 
     pub fn inverse(&self) -> Option<Self> {
-        let det = self.det();
+        self.try_inverse().ok()
+    }
+
+    pub fn try_inverse(&self) -> Result<Self, MatrixError> {
+        let det = self.try_det()?;
         if det == 0.0 {
-            return None;
+            return Err(MatrixError::Singular);
         }
         let mut inverse_mat = Self::new(self.nrows, self.ncols);
         for i in 0..self.nrows {
             for j in 0..self.ncols {
                 let c = self.cofactor(i, j);
-                inverse_mat.set(j, i, c / det);
+                inverse_mat[(j, i)] = c / det;
+            }
+        }
+        Ok(inverse_mat)
+    }
+
+    pub fn try_mul(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        if self.ncols != other.nrows {
+            return Err(MatrixError::DimensionMismatch {left: self.shape(), right: other.shape()});
+        }
+        let mut prod = Matrix::new(self.nrows, other.ncols);
+        for i in 0..self.nrows {
+            for j in 0..other.ncols {
+                let mut cell_val = 0.0f32;
+                for k in 0..self.ncols {
+                    cell_val += self[(i, k)] * other[(k, j)];
+                }
+                prod[(i, j)] = cell_val;
             }
         }
-        Some(inverse_mat)
+        return Ok(prod);
     }
 
     //specialized for 4 x 4 dimensions since raytracer requires only 3 dimensions
     pub fn translation(x: f32, y: f32, z: f32) -> Self {
         let mut ident = Self::identity(4);
-        ident.set(0, 3, x);
-        ident.set(1, 3, y);
-        ident.set(2, 3, z);
+        ident[(0, 3)] = x;
+        ident[(1, 3)] = y;
+        ident[(2, 3)] = z;
         return ident;
     }
 
     pub fn scaling(x: f32, y: f32, z: f32) -> Self {
         let mut ident = Self::identity(4);
-        ident.set(0, 0, x);
-        ident.set(1, 1, y);
-        ident.set(2, 2, z);
+        ident[(0, 0)] = x;
+        ident[(1, 1)] = y;
+        ident[(2, 2)] = z;
         return ident;
     }
 
@@ -188,28 +268,23 @@ impl Index<(usize, usize)> for Matrix {
     type Output = f32;
     fn index(&self, indexer: (usize, usize)) -> &Self::Output {
         let (row, col) = indexer;
-        let idx = row * self.ncols + col;
+        let idx = self.get_index(row, col).expect("matrix index out of bounds");
         return &self.vals[idx];
     }
 }
 
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, indexer: (usize, usize)) -> &mut Self::Output {
+        let (row, col) = indexer;
+        let idx = self.get_index(row, col).expect("matrix index out of bounds");
+        return &mut self.vals[idx];
+    }
+}
+
 impl Mul<&Matrix> for &Matrix {
     type Output = Option<Matrix>;
     fn mul(self, _rhs: &Matrix) -> Option<Matrix> {
-        if self.ncols != _rhs.nrows {
-            return None;
-        }
-        let mut prod = Matrix::new(self.nrows, _rhs.ncols);
-        for i in 0..self.nrows {
-            for j in 0.._rhs.ncols {
-                let mut cell_val = 0.0f32;
-                for k in 0..self.ncols {
-                    cell_val += self[(i, k)] * _rhs[(k, j)];
-                }
-                prod.set(i, j,cell_val);
-            }
-        }
-        return Some(prod);
+        self.try_mul(_rhs).ok()
     }
 }
 
@@ -217,20 +292,7 @@ impl Mul<&Matrix> for &Matrix {
 impl Mul<Matrix> for Matrix {
     type Output = Option<Matrix>;
     fn mul(self, _rhs: Self) -> Option<Matrix> {
-        if self.ncols != _rhs.nrows {
-            return None;
-        }
-        let mut prod = Matrix::new(self.nrows, _rhs.ncols);
-        for i in 0..self.nrows {
-            for j in 0.._rhs.ncols {
-                let mut cell_val = 0.0f32;
-                for k in 0..self.ncols {
-                    cell_val += self[(i, k)] * _rhs[(k, j)];
-                }
-                prod.set(i, j,cell_val);
-            }
-        }
-        return Some(prod);
+        self.try_mul(&_rhs).ok()
     }
 }
 
@@ -276,6 +338,7 @@ impl PartialEq for Matrix {
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant, clippy::excessive_precision, clippy::bool_assert_comparison)]
 mod tests{
     use std::f32::consts::PI;
     use super::*;
@@ -701,4 +764,65 @@ mod tests{
         assert_eq!((transform * p).unwrap(), Tuple::point(2.0, 3.0, 7.0));
     }
 
+    #[test]
+    fn test_try_det() {
+        let m = Matrix::from_array(2, 3, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        assert_eq!(m.try_det(), Err(MatrixError::NotSquare {rows: 2, cols: 3}));
+        assert_eq!(Matrix::new(0, 0).try_det(), Err(MatrixError::NotSquare {rows: 0, cols: 0}));
+        assert_eq!(Matrix::from_array(1, 1, &[7.0]).unwrap().try_det(), Ok(7.0));
+        let m = Matrix::from_array(2, 2, &[1.0, 5.0, -3.0, 2.0]).unwrap();
+        assert_eq!(m.try_det(), Ok(17.0));
+    }
+
+    #[test]
+    fn test_try_inverse() {
+        let singular = Matrix::from_array(2, 2, &[1.0, 2.0, 2.0, 4.0]).unwrap();
+        assert_eq!(singular.try_inverse(), Err(MatrixError::Singular));
+        let wide = Matrix::new(2, 4);
+        assert_eq!(wide.try_inverse(), Err(MatrixError::NotSquare {rows: 2, cols: 4}));
+        assert_eq!(wide.inverse(), None);
+        let m = Matrix::translation(1.0, 2.0, 3.0);
+        assert_eq!(m.try_inverse(), Ok(Matrix::translation(-1.0, -2.0, -3.0)));
+    }
+
+    #[test]
+    fn test_try_mul() {
+        let a = Matrix::new(2, 3);
+        let b = Matrix::new(2, 3);
+        assert_eq!(a.try_mul(&b), Err(MatrixError::DimensionMismatch {left: (2, 3), right: (2, 3)}));
+        assert_eq!(a.try_mul(&b.transpose()), Ok(Matrix::new(2, 2)));
+        assert_eq!(MatrixError::DimensionMismatch {left: (2, 3), right: (2, 3)}.to_string(), "cannot multiply 2x3 by 2x3");
+    }
+
+    #[test]
+    fn test_index_mut() {
+        let mut m = Matrix::new(2, 3);
+        m[(0, 1)] = 4.0;
+        m[(1, 2)] += 2.5;
+        assert_eq!(m, Matrix::from_array(2, 3, &[0.0, 4.0, 0.0, 0.0, 0.0, 2.5]).unwrap());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_bounds() {
+        let m = Matrix::new(2, 3);
+        let _ = m[(0, 3)];
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_col_out_of_range() {
+        let m = Matrix::from_array(2, 3, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        m.col(3);
+    }
+
+    #[test]
+    fn test_rows_cols_iter() {
+        let mut m = Matrix::from_array(2, 3, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        assert_eq!(m.row(1), &[4.0, 5.0, 6.0]);
+        assert_eq!(m.col(1), vec![2.0, 5.0]);
+        assert_eq!(m.iter().sum::<f32>(), 21.0);
+        m.row_mut(0).copy_from_slice(&[7.0, 8.0, 9.0]);
+        assert_eq!(m.col(2), vec![9.0, 6.0]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,573 @@
+use std::ops::{Index, IndexMut, Mul};
+use std::cmp::PartialEq;
+use super::utils;
+use super::tuple::Tuple;
+use super::matrix::Matrix;
+
+// stack allocated, fixed size counterparts of Matrix; everything the renderer
+// does with transforms goes through Matrix4, Matrix keeps the general case
+
+#[derive(Debug, Copy, Clone)]
+pub struct Matrix2 {
+    vals: [[f32; 2]; 2]
+}
+
+impl Matrix2 {
+    pub fn new(vals: [[f32; 2]; 2]) -> Self {
+        Self {vals}
+    }
+
+    pub fn det(&self) -> f32 {
+        self.vals[0][0] * self.vals[1][1] - self.vals[0][1] * self.vals[1][0]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Matrix3 {
+    vals: [[f32; 3]; 3]
+}
+
+impl Matrix3 {
+    pub fn new(vals: [[f32; 3]; 3]) -> Self {
+        Self {vals}
+    }
+
+    pub fn submatrix(&self, row: usize, col: usize) -> Matrix2 {
+        let mut vals = [[0.0; 2]; 2];
+        let rows = (0..3).filter(|&i| i != row);
+        for (r, i) in rows.enumerate() {
+            let cols = (0..3).filter(|&j| j != col);
+            for (c, j) in cols.enumerate() {
+                vals[r][c] = self.vals[i][j];
+            }
+        }
+        Matrix2 {vals}
+    }
+
+    pub fn minor(&self, row: usize, col: usize) -> f32 {
+        self.submatrix(row, col).det()
+    }
+
+    pub fn cofactor(&self, row: usize, col: usize) -> f32 {
+        let minor = self.minor(row, col);
+        if (row + col) % 2 == 1 { -minor } else { minor }
+    }
+
+    pub fn det(&self) -> f32 {
+        self.vals[0][0] * self.cofactor(0, 0) + self.vals[0][1] * self.cofactor(0, 1) + self.vals[0][2] * self.cofactor(0, 2)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Matrix4 {
+    vals: [[f32; 4]; 4]
+}
+
+impl Matrix4 {
+    pub fn new(vals: [[f32; 4]; 4]) -> Self {
+        Self {vals}
+    }
+
+    pub fn identity() -> Self {
+        Self::new([[1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    pub fn from_matrix(matrix: &Matrix) -> Option<Self> {
+        if matrix.shape() != (4, 4) {
+            return None;
+        }
+        let mut vals = [[0.0; 4]; 4];
+        for (i, row) in vals.iter_mut().enumerate() {
+            row.copy_from_slice(matrix.row(i));
+        }
+        Some(Self {vals})
+    }
+
+    pub fn row(&self, row: usize) -> &[f32; 4] {
+        &self.vals[row]
+    }
+
+    pub fn col(&self, col: usize) -> [f32; 4] {
+        [self.vals[0][col], self.vals[1][col], self.vals[2][col], self.vals[3][col]]
+    }
+
+    // Blends translation, rotation (along the shortest arc) and per-axis
+    // scale separately, so a rotating transform keeps its shape in between.
+    // Transforms with shear or projection, which don't split that way, fall
+    // back to an element-wise blend.
+    pub fn lerp(&self, other: &Matrix4, t: f32) -> Self {
+        if let (Some(a), Some(b)) = (self.decompose(), other.decompose()) {
+            let mix = |x: [f32; 3], y: [f32; 3]| [x[0] + (y[0] - x[0]) * t, x[1] + (y[1] - x[1]) * t, x[2] + (y[2] - x[2]) * t];
+            return Self::compose(mix(a.0, b.0), slerp(a.1, b.1, t), mix(a.2, b.2));
+        }
+        let mut vals = self.vals;
+        for (row, other_row) in vals.iter_mut().zip(other.vals.iter()) {
+            for (v, o) in row.iter_mut().zip(other_row.iter()) {
+                *v += (o - *v) * t;
+            }
+        }
+        Self {vals}
+    }
+
+    // translation, rotation as a unit quaternion (x, y, z, w) and scale of a
+    // translate * rotate * scale transform
+    pub(crate) fn decompose(&self) -> Option<([f32; 3], [f32; 4], [f32; 3])> {
+        let m = &self.vals;
+        if m[3] != [0.0, 0.0, 0.0, 1.0] {
+            return None;
+        }
+        let mut columns = [[m[0][0], m[1][0], m[2][0]], [m[0][1], m[1][1], m[2][1]], [m[0][2], m[1][2], m[2][2]]];
+        let dot = |a: &[f32; 3], b: &[f32; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+        let mut scale = [0.0; 3];
+        for (column, s) in columns.iter_mut().zip(scale.iter_mut()) {
+            *s = dot(column, column).sqrt();
+            if *s < 1e-6 {
+                return None;
+            }
+            for v in column.iter_mut() {
+                *v /= *s;
+            }
+        }
+        for (i, j) in [(0, 1), (0, 2), (1, 2)] {
+            if dot(&columns[i], &columns[j]).abs() > 1e-3 {
+                return None;
+            }
+        }
+        let [c0, c1, c2] = columns;
+        let cross = [c1[1] * c2[2] - c1[2] * c2[1], c1[2] * c2[0] - c1[0] * c2[2], c1[0] * c2[1] - c1[1] * c2[0]];
+        // a mirror is carried by the scale so the rest is a proper rotation
+        if dot(&c0, &cross) < 0.0 {
+            scale[0] = -scale[0];
+            columns[0] = [-c0[0], -c0[1], -c0[2]];
+        }
+        let r = |row: usize, col: usize| columns[col][row];
+        let trace = r(0, 0) + r(1, 1) + r(2, 2);
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            [(r(2, 1) - r(1, 2)) / s, (r(0, 2) - r(2, 0)) / s, (r(1, 0) - r(0, 1)) / s, 0.25 * s]
+        } else if r(0, 0) > r(1, 1) && r(0, 0) > r(2, 2) {
+            let s = (1.0 + r(0, 0) - r(1, 1) - r(2, 2)).sqrt() * 2.0;
+            [0.25 * s, (r(0, 1) + r(1, 0)) / s, (r(0, 2) + r(2, 0)) / s, (r(2, 1) - r(1, 2)) / s]
+        } else if r(1, 1) > r(2, 2) {
+            let s = (1.0 + r(1, 1) - r(0, 0) - r(2, 2)).sqrt() * 2.0;
+            [(r(0, 1) + r(1, 0)) / s, 0.25 * s, (r(1, 2) + r(2, 1)) / s, (r(0, 2) - r(2, 0)) / s]
+        } else {
+            let s = (1.0 + r(2, 2) - r(0, 0) - r(1, 1)).sqrt() * 2.0;
+            [(r(0, 2) + r(2, 0)) / s, (r(1, 2) + r(2, 1)) / s, 0.25 * s, (r(1, 0) - r(0, 1)) / s]
+        };
+        return Some(([m[0][3], m[1][3], m[2][3]], q, scale));
+    }
+
+    fn compose(translation: [f32; 3], rotation: [f32; 4], scale: [f32; 3]) -> Self {
+        let [x, y, z, w] = rotation;
+        let r = [[1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w)],
+            [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w)],
+            [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y)]];
+        let mut vals = [[0.0, 0.0, 0.0, 1.0]; 4];
+        for i in 0..3 {
+            vals[i] = [r[i][0] * scale[0], r[i][1] * scale[1], r[i][2] * scale[2], translation[i]];
+        }
+        Self {vals}
+    }
+
+    pub fn transpose(&self) -> Self {
+        let m = &self.vals;
+        Self::new([[m[0][0], m[1][0], m[2][0], m[3][0]],
+            [m[0][1], m[1][1], m[2][1], m[3][1]],
+            [m[0][2], m[1][2], m[2][2], m[3][2]],
+            [m[0][3], m[1][3], m[2][3], m[3][3]]])
+    }
+
+    pub fn submatrix(&self, row: usize, col: usize) -> Matrix3 {
+        let mut vals = [[0.0; 3]; 3];
+        let rows = (0..4).filter(|&i| i != row);
+        for (r, i) in rows.enumerate() {
+            let cols = (0..4).filter(|&j| j != col);
+            for (c, j) in cols.enumerate() {
+                vals[r][c] = self.vals[i][j];
+            }
+        }
+        Matrix3 {vals}
+    }
+
+    pub fn minor(&self, row: usize, col: usize) -> f32 {
+        self.submatrix(row, col).det()
+    }
+
+    pub fn cofactor(&self, row: usize, col: usize) -> f32 {
+        let minor = self.minor(row, col);
+        if (row + col) % 2 == 1 { -minor } else { minor }
+    }
+
+    // 2x2 determinants of the top two and bottom two rows, shared by det and inverse
+    fn sub_determinants(&self) -> ([f32; 6], [f32; 6]) {
+        let m = &self.vals;
+        let s = [m[0][0] * m[1][1] - m[1][0] * m[0][1],
+            m[0][0] * m[1][2] - m[1][0] * m[0][2],
+            m[0][0] * m[1][3] - m[1][0] * m[0][3],
+            m[0][1] * m[1][2] - m[1][1] * m[0][2],
+            m[0][1] * m[1][3] - m[1][1] * m[0][3],
+            m[0][2] * m[1][3] - m[1][2] * m[0][3]];
+        let c = [m[2][0] * m[3][1] - m[3][0] * m[2][1],
+            m[2][0] * m[3][2] - m[3][0] * m[2][2],
+            m[2][0] * m[3][3] - m[3][0] * m[2][3],
+            m[2][1] * m[3][2] - m[3][1] * m[2][2],
+            m[2][1] * m[3][3] - m[3][1] * m[2][3],
+            m[2][2] * m[3][3] - m[3][2] * m[2][3]];
+        return (s, c);
+    }
+
+    pub fn det(&self) -> f32 {
+        let (s, c) = self.sub_determinants();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    pub fn is_invertible(&self) -> bool {
+        self.det() != 0.0
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let (s, c) = self.sub_determinants();
+        let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        if det == 0.0 {
+            return None;
+        }
+        let m = &self.vals;
+        let inv = 1.0 / det;
+        return Some(Self::new([
+            [(m[1][1] * c[5] - m[1][2] * c[4] + m[1][3] * c[3]) * inv,
+                (-m[0][1] * c[5] + m[0][2] * c[4] - m[0][3] * c[3]) * inv,
+                (m[3][1] * s[5] - m[3][2] * s[4] + m[3][3] * s[3]) * inv,
+                (-m[2][1] * s[5] + m[2][2] * s[4] - m[2][3] * s[3]) * inv],
+            [(-m[1][0] * c[5] + m[1][2] * c[2] - m[1][3] * c[1]) * inv,
+                (m[0][0] * c[5] - m[0][2] * c[2] + m[0][3] * c[1]) * inv,
+                (-m[3][0] * s[5] + m[3][2] * s[2] - m[3][3] * s[1]) * inv,
+                (m[2][0] * s[5] - m[2][2] * s[2] + m[2][3] * s[1]) * inv],
+            [(m[1][0] * c[4] - m[1][1] * c[2] + m[1][3] * c[0]) * inv,
+                (-m[0][0] * c[4] + m[0][1] * c[2] - m[0][3] * c[0]) * inv,
+                (m[3][0] * s[4] - m[3][1] * s[2] + m[3][3] * s[0]) * inv,
+                (-m[2][0] * s[4] + m[2][1] * s[2] - m[2][3] * s[0]) * inv],
+            [(-m[1][0] * c[3] + m[1][1] * c[1] - m[1][2] * c[0]) * inv,
+                (m[0][0] * c[3] - m[0][1] * c[1] + m[0][2] * c[0]) * inv,
+                (-m[3][0] * s[3] + m[3][1] * s[1] - m[3][2] * s[0]) * inv,
+                (m[2][0] * s[3] - m[2][1] * s[1] + m[2][2] * s[0]) * inv]]));
+    }
+
+    pub fn translation(x: f32, y: f32, z: f32) -> Self {
+        Self::new([[1.0, 0.0, 0.0, x],
+            [0.0, 1.0, 0.0, y],
+            [0.0, 0.0, 1.0, z],
+            [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    pub fn scaling(x: f32, y: f32, z: f32) -> Self {
+        Self::new([[x, 0.0, 0.0, 0.0],
+            [0.0, y, 0.0, 0.0],
+            [0.0, 0.0, z, 0.0],
+            [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    pub fn rotation_x(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new([[1.0, 0.0, 0.0, 0.0],
+            [0.0, cos, -sin, 0.0],
+            [0.0, sin, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    pub fn rotation_y(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new([[cos, 0.0, sin, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-sin, 0.0, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    pub fn rotation_z(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new([[cos, -sin, 0.0, 0.0],
+            [sin, cos, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    pub fn shearing(x_y :f32, x_z: f32, y_x: f32, y_z: f32, z_x: f32, z_y: f32) -> Self {
+        Self::new([[1.0, x_y, x_z, 0.0],
+            [y_x, 1.0, y_z, 0.0],
+            [z_x, z_y, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    // orients the world relative to an eye at `from` looking towards `to`
+    pub fn view_transform(from: &Tuple, to: &Tuple, up: &Tuple) -> Self {
+        let forward = (to - from).normalize();
+        let left = forward.cross(&up.normalize());
+        let true_up = left.cross(&forward);
+        let orientation = Self::new([[left.x(), left.y(), left.z(), 0.0],
+            [true_up.x(), true_up.y(), true_up.z(), 0.0],
+            [-forward.x(), -forward.y(), -forward.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0]]);
+        return orientation * Self::translation(-from.x(), -from.y(), -from.z());
+    }
+}
+
+// spherical interpolation between unit quaternions
+fn slerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let mut cos = a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3];
+    let mut b = b;
+    // q and -q are the same rotation; take the shorter way round
+    if cos < 0.0 {
+        cos = -cos;
+        b = [-b[0], -b[1], -b[2], -b[3]];
+    }
+    let (wa, wb) = if cos > 0.9995 {
+        (1.0 - t, t)
+    } else {
+        let angle = cos.acos();
+        let sin = angle.sin();
+        (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+    };
+    let q: [f32; 4] = std::array::from_fn(|i| a[i] * wa + b[i] * wb);
+    let length = (q[0] * q[0] + q[1] * q[1] + q[2] * q[2] + q[3] * q[3]).sqrt();
+    return q.map(|v| v / length);
+}
+
+impl Index<(usize, usize)> for Matrix4 {
+    type Output = f32;
+    fn index(&self, indexer: (usize, usize)) -> &Self::Output {
+        let (row, col) = indexer;
+        &self.vals[row][col]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix4 {
+    fn index_mut(&mut self, indexer: (usize, usize)) -> &mut Self::Output {
+        let (row, col) = indexer;
+        &mut self.vals[row][col]
+    }
+}
+
+impl Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+    fn mul(self, _rhs: Matrix4) -> Matrix4 {
+        let a = &self.vals;
+        let b = &_rhs.vals;
+        let mut vals = [[0.0; 4]; 4];
+        for (i, row) in vals.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = a[i][0] * b[0][j] + a[i][1] * b[1][j] + a[i][2] * b[2][j] + a[i][3] * b[3][j];
+            }
+        }
+        Matrix4 {vals}
+    }
+}
+
+impl Mul<&Tuple> for &Matrix4 {
+    type Output = Tuple;
+    fn mul(self, _rhs: &Tuple) -> Tuple {
+        let m = &self.vals;
+        let (x, y, z, w) = (_rhs.x(), _rhs.y(), _rhs.z(), _rhs.w());
+        Tuple::new(m[0][0] * x + m[0][1] * y + m[0][2] * z + m[0][3] * w,
+            m[1][0] * x + m[1][1] * y + m[1][2] * z + m[1][3] * w,
+            m[2][0] * x + m[2][1] * y + m[2][2] * z + m[2][3] * w,
+            m[3][0] * x + m[3][1] * y + m[3][2] * z + m[3][3] * w)
+    }
+}
+
+impl Mul<Tuple> for Matrix4 {
+    type Output = Tuple;
+    fn mul(self, _rhs: Tuple) -> Tuple {
+        &self * &_rhs
+    }
+}
+
+impl PartialEq for Matrix4 {
+    fn eq(&self, other: &Self) -> bool {
+        for i in 0..4 {
+            for j in 0..4 {
+                if !utils::is_equal(self.vals[i][j], other.vals[i][j]) {
+                    return false;
+                }
+            }
+        }
+        return true;
+    }
+}
+
+impl From<Matrix4> for Matrix {
+    fn from(matrix: Matrix4) -> Self {
+        let mut converted = Matrix::new(4, 4);
+        for i in 0..4 {
+            converted.row_mut(i).copy_from_slice(&matrix.vals[i]);
+        }
+        converted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use super::*;
+
+    fn sample() -> Matrix4 {
+        Matrix4::new([[-5.0, 2.0, 6.0, -8.0],
+            [1.0, -5.0, 1.0, 8.0],
+            [7.0, 7.0, -6.0, -7.0],
+            [1.0, -3.0, 7.0, 4.0]])
+    }
+
+    #[test]
+    fn test_matrix2_det() {
+        assert_eq!(Matrix2::new([[1.0, 5.0], [-3.0, 2.0]]).det(), 17.0);
+    }
+
+    #[test]
+    fn test_matrix3() {
+        let m = Matrix3::new([[1.0, 2.0, 6.0], [-5.0, 8.0, -4.0], [2.0, 6.0, 4.0]]);
+        assert_eq!(m.submatrix(0, 2).det(), Matrix2::new([[-5.0, 8.0], [2.0, 6.0]]).det());
+        assert_eq!(m.cofactor(0, 0), 56.0);
+        assert_eq!(m.cofactor(0, 1), 12.0);
+        assert_eq!(m.cofactor(0, 2), -46.0);
+        assert_eq!(m.det(), -196.0);
+    }
+
+    #[test]
+    fn test_det_matches_cofactor_expansion() {
+        let m = Matrix4::new([[-2.0, -8.0, 3.0, 5.0],
+            [-3.0, 1.0, 7.0, 3.0],
+            [1.0, 2.0, -9.0, 6.0],
+            [-6.0, 7.0, 7.0, -9.0]]);
+        assert_eq!(m.cofactor(0, 0), 690.0);
+        assert_eq!(m.cofactor(0, 3), 51.0);
+        assert_eq!(m.det(), -4071.0);
+        assert_eq!(Matrix::from(m).det(), -4071.0);
+    }
+
+    #[test]
+    fn test_inverse_matches_dynamic_matrix() {
+        let m = sample();
+        let dynamic = Matrix::from(m).inverse().unwrap();
+        assert_eq!(Matrix::from(m.inverse().unwrap()), dynamic);
+        assert_eq!(m * m.inverse().unwrap(), Matrix4::identity());
+    }
+
+    #[test]
+    fn test_singular_inverse() {
+        let m = Matrix4::new([[-4.0, 2.0, -2.0, -3.0],
+            [9.0, 6.0, 2.0, 6.0],
+            [0.0, -5.0, 1.0, -5.0],
+            [0.0, 0.0, 0.0, 0.0]]);
+        assert!(!m.is_invertible());
+        assert_eq!(m.inverse(), None);
+    }
+
+    #[test]
+    fn test_mul_matches_dynamic_matrix() {
+        let a = sample();
+        let b = Matrix4::rotation_y(0.3) * Matrix4::translation(1.0, 2.0, 3.0);
+        let expected = (Matrix::from(a) * Matrix::from(b)).unwrap();
+        assert_eq!(Matrix::from(a * b), expected);
+        let p = Tuple::point(1.0, 2.0, 3.0);
+        assert_eq!(a * p, (Matrix::from(a) * p).unwrap());
+    }
+
+    #[test]
+    fn test_index_rows_cols() {
+        let mut m = Matrix4::identity();
+        m[(0, 3)] = 5.0;
+        assert_eq!(m, Matrix4::translation(5.0, 0.0, 0.0));
+        assert_eq!(sample().row(2), &[7.0, 7.0, -6.0, -7.0]);
+        assert_eq!(sample().col(1), [2.0, -5.0, 7.0, -3.0]);
+    }
+
+    #[test]
+    fn test_transpose() {
+        let m = sample();
+        assert_eq!(Matrix::from(m.transpose()), Matrix::from(m).transpose());
+        assert_eq!(Matrix4::identity().transpose(), Matrix4::identity());
+    }
+
+    #[test]
+    fn test_from_matrix() {
+        assert_eq!(Matrix4::from_matrix(&Matrix::identity(4)), Some(Matrix4::identity()));
+        assert_eq!(Matrix4::from_matrix(&Matrix::identity(3)), None);
+        assert_eq!(Matrix4::from_matrix(&Matrix::translation(1.0, 2.0, 3.0)), Some(Matrix4::translation(1.0, 2.0, 3.0)));
+    }
+
+    #[test]
+    fn test_transforms_match_dynamic_matrix() {
+        assert_eq!(Matrix::from(Matrix4::scaling(2.0, 3.0, 4.0)), Matrix::scaling(2.0, 3.0, 4.0));
+        assert_eq!(Matrix::from(Matrix4::rotation_x(PI / 3.0)), Matrix::rotation_x(PI / 3.0));
+        assert_eq!(Matrix::from(Matrix4::rotation_y(PI / 3.0)), Matrix::rotation_y(PI / 3.0));
+        assert_eq!(Matrix::from(Matrix4::rotation_z(PI / 3.0)), Matrix::rotation_z(PI / 3.0));
+        assert_eq!(Matrix::from(Matrix4::shearing(1.0, 2.0, 3.0, 4.0, 5.0, 6.0)), Matrix::shearing(1.0, 2.0, 3.0, 4.0, 5.0, 6.0));
+    }
+
+    #[test]
+    fn test_view_transform_default() {
+        let from = Tuple::point(0.0, 0.0, 0.0);
+        let to = Tuple::point(0.0, 0.0, -1.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        assert_eq!(Matrix4::view_transform(&from, &to, &up), Matrix4::identity());
+    }
+
+    #[test]
+    fn test_view_transform_positive_z() {
+        let from = Tuple::point(0.0, 0.0, 0.0);
+        let to = Tuple::point(0.0, 0.0, 1.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        assert_eq!(Matrix4::view_transform(&from, &to, &up), Matrix4::scaling(-1.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn test_view_transform_moves_world() {
+        let from = Tuple::point(0.0, 0.0, 8.0);
+        let to = Tuple::point(0.0, 0.0, 0.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        assert_eq!(Matrix4::view_transform(&from, &to, &up), Matrix4::translation(0.0, 0.0, -8.0));
+    }
+
+    #[test]
+    fn test_view_transform_arbitrary() {
+        let from = Tuple::point(1.0, 3.0, 2.0);
+        let to = Tuple::point(4.0, -2.0, 8.0);
+        let up = Tuple::vector(1.0, 1.0, 0.0);
+        let expected = Matrix4::new([[-0.50709, 0.50709, 0.67612, -2.36643],
+            [0.76772, 0.60609, 0.12122, -2.82843],
+            [-0.35857, 0.59761, -0.71714, 0.0],
+            [0.0, 0.0, 0.0, 1.0]]);
+        assert_eq!(Matrix4::view_transform(&from, &to, &up), expected);
+    }
+
+    #[test]
+    fn test_lerp() {
+        let a = Matrix4::translation(0.0, 0.0, 0.0);
+        let b = Matrix4::translation(4.0, -2.0, 8.0);
+        assert_eq!(a.lerp(&b, 0.0), a);
+        assert_eq!(a.lerp(&b, 1.0), b);
+        assert_eq!(a.lerp(&b, 0.25), Matrix4::translation(1.0, -0.5, 2.0));
+    }
+
+    #[test]
+    fn test_lerp_rotation() {
+        use std::f32::consts::{FRAC_PI_2, PI};
+        let half = Matrix4::identity().lerp(&Matrix4::rotation_y(2.5), 0.5);
+        assert_eq!(half, Matrix4::rotation_y(1.25));
+        // element by element this would pass through a singular matrix; a
+        // half turn can go either way round
+        let half = Matrix4::identity().lerp(&Matrix4::rotation_y(PI), 0.5);
+        assert!(half == Matrix4::rotation_y(FRAC_PI_2) || half == Matrix4::rotation_y(-FRAC_PI_2));
+        let a = Matrix4::translation(1.0, 0.0, 0.0) * Matrix4::rotation_z(0.2) * Matrix4::scaling(1.0, 2.0, -1.0);
+        let b = Matrix4::translation(3.0, 2.0, 0.0) * Matrix4::rotation_z(1.0) * Matrix4::scaling(3.0, 2.0, -1.0);
+        assert_eq!(a.lerp(&b, 0.0), a);
+        assert_eq!(a.lerp(&b, 1.0), b);
+        let mid = Matrix4::translation(2.0, 1.0, 0.0) * Matrix4::rotation_z(0.6) * Matrix4::scaling(2.0, 2.0, -1.0);
+        assert_eq!(a.lerp(&b, 0.5), mid);
+        // shear doesn't decompose, so it blends element-wise
+        let sheared = Matrix4::shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        assert_eq!(Matrix4::identity().lerp(&sheared, 0.5), Matrix4::shearing(0.5, 0.0, 0.0, 0.0, 0.0, 0.0));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,431 @@
+use super::ray::Ray;
+use super::tuple::Tuple;
+use super::matrix4::Matrix4;
+use super::material::Material;
+use super::intersection::{Intersect, Intersection, Intersections, Shape};
+use super::sampler;
+use super::voxel;
+use super::watchdog;
+
+// determinants below this mean the ray runs parallel to the triangle
+const PARALLEL_EPSILON: f32 = 1e-8;
+// how far outside a triangle normal_at still accepts a point, in barycentric units
+const BARYCENTRIC_EPSILON: f32 = 1e-4;
+
+// Triangles sharing one vertex buffer, with faces as index triples into it.
+// Optional per-vertex normals are interpolated across each face for smooth
+// shading; without them faces are flat shaded.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TriangleMesh {
+    transform: Matrix4,
+    inverse: Matrix4,
+    vertices: Vec<Tuple>,
+    normals: Vec<Tuple>,
+    faces: Vec<[usize; 3]>,
+    // object space bounding box, for rejecting rays that miss the whole mesh
+    min: Tuple,
+    max: Tuple,
+    // used by faces without an entry in the material table
+    pub material: Material,
+    // skip faces turned away from primary rays; only correct for closed
+    // meshes whose faces wind counter-clockwise seen from outside
+    pub cull_backfaces: bool,
+    materials: Vec<Material>,
+    face_materials: Vec<Option<usize>>,
+    id: i32
+}
+
+impl TriangleMesh {
+    // None if a face refers to a vertex that doesn't exist
+    pub fn new(id: i32, vertices: Vec<Tuple>, faces: Vec<[usize; 3]>) -> Option<Self> {
+        if faces.iter().any(|face| face.iter().any(|&i| i >= vertices.len())) {
+            return None;
+        }
+        let mut min = Tuple::point(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = Tuple::point(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for v in &vertices {
+            min = Tuple::point(min.x().min(v.x()), min.y().min(v.y()), min.z().min(v.z()));
+            max = Tuple::point(max.x().max(v.x()), max.y().max(v.y()), max.z().max(v.z()));
+        }
+        let face_materials = vec![None; faces.len()];
+        Some(Self {id, transform: Matrix4::identity(), inverse: Matrix4::identity(), vertices, normals: Vec::new(), faces, min, max,
+            material: Material::new(), cull_backfaces: false, materials: Vec::new(), face_materials})
+    }
+
+    // one normal per vertex; None (leaving the mesh unchanged) if the counts differ
+    pub fn set_normals(&mut self, normals: Vec<Tuple>) -> Option<()> {
+        if normals.len() != self.vertices.len() {
+            return None;
+        }
+        self.normals = normals.iter().map(|n| n.normalize()).collect();
+        Some(())
+    }
+
+    // None (leaving the mesh unchanged) if the transform can't be inverted
+    pub fn set_transform(&mut self, transform: Matrix4) -> Option<()> {
+        self.inverse = transform.inverse()?;
+        self.transform = transform;
+        Some(())
+    }
+
+    pub fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    // per-instance value in [0, 1) for varying materials and textures
+    pub fn random_value(&self, seed: u64) -> f32 {
+        sampler::instance_random(self.id, seed)
+    }
+
+    // object space bounding box
+    pub fn bounds(&self) -> (Tuple, Tuple) {
+        (self.min, self.max)
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    // adds a material to the mesh's table and returns its index
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    // None if either the face or the material index is out of range
+    pub fn set_face_material(&mut self, face: usize, material: usize) -> Option<()> {
+        if face >= self.faces.len() || material >= self.materials.len() {
+            return None;
+        }
+        self.face_materials[face] = Some(material);
+        Some(())
+    }
+
+    pub fn face_material(&self, face: usize) -> Option<&Material> {
+        let index = (*self.face_materials.get(face)?)?;
+        self.materials.get(index)
+    }
+
+    // world space distance from a hit to the nearest edge of the face it
+    // landed on, for drawing wireframes; None if the hit has no face
+    pub fn edge_distance(&self, hit: &Intersection<'_, Self>) -> Option<f32> {
+        let face = hit.face()?;
+        let (u, v) = hit.barycentric()?;
+        let (v0, v1, v2) = self.corners(face);
+        let corners = [self.transform * *v0, self.transform * *v1, self.transform * *v2];
+        let point = corners[0] * (1.0 - u - v) + corners[1] * u + corners[2] * v;
+        let mut nearest = f32::INFINITY;
+        for i in 0..3 {
+            let (a, b) = (corners[i], corners[(i + 1) % 3]);
+            let edge = b - a;
+            let length = edge.magnitude();
+            if length > 0.0 {
+                nearest = nearest.min((point - a).cross(&edge).magnitude() / length);
+            }
+        }
+        return Some(nearest);
+    }
+
+    // material of the face a hit landed on
+    pub fn material_at(&self, hit: &Intersection<'_, Self>) -> &Material {
+        hit.face().and_then(|face| self.face_material(face)).unwrap_or(&self.material)
+    }
+
+    fn corners(&self, face: usize) -> (&Tuple, &Tuple, &Tuple) {
+        let [a, b, c] = self.faces[face];
+        (&self.vertices[a], &self.vertices[b], &self.vertices[c])
+    }
+
+    // Moller-Trumbore: t and the barycentric (u, v) of the hit, or None.
+    // With `cull` set, faces seen from behind are missed as well.
+    fn intersect_face(&self, face: usize, origin: &Tuple, direction: &Tuple, cull: bool) -> Option<(f32, f32, f32)> {
+        let (v0, v1, v2) = self.corners(face);
+        let e1 = v1 - v0;
+        let e2 = v2 - v0;
+        let p = direction.cross(&e2);
+        // det is -direction . (e1 x e2), positive when the ray meets the front
+        let det = e1.dot(&p);
+        if det.abs() < PARALLEL_EPSILON || (cull && det < 0.0) {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let s = origin - v0;
+        let u = s.dot(&p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(&e1);
+        let v = direction.dot(&q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        return Some((e2.dot(&q) * inv_det, u, v));
+    }
+
+    // the face an object space point lies on and its barycentric (u, v).
+    // A point off the mesh gets the face whose plane is closest, with (u, v)
+    // clamped onto it, so a normal can always be found; None only if every
+    // face is degenerate
+    fn locate(&self, point: &Tuple) -> Option<(usize, f32, f32)> {
+        let mut best = None;
+        let mut best_distance = f32::INFINITY;
+        let mut closest = None;
+        let mut closest_distance = f32::INFINITY;
+        for face in 0..self.faces.len() {
+            let (v0, v1, v2) = self.corners(face);
+            let e1 = v1 - v0;
+            let e2 = v2 - v0;
+            let normal = e1.cross(&e2);
+            let area2 = normal.dot(&normal);
+            if area2 == 0.0 {
+                continue;
+            }
+            let w = point - v0;
+            let distance = normal.dot(&w).abs() / area2.sqrt();
+            if distance >= best_distance {
+                continue;
+            }
+            let u = w.cross(&e2).dot(&normal) / area2;
+            let v = e1.cross(&w).dot(&normal) / area2;
+            if u >= -BARYCENTRIC_EPSILON && v >= -BARYCENTRIC_EPSILON && u + v <= 1.0 + BARYCENTRIC_EPSILON {
+                best = Some((face, u, v));
+                best_distance = distance;
+            } else if distance < closest_distance {
+                let (u, v) = (u.max(0.0), v.max(0.0));
+                let scale = 1.0 / (u + v).max(1.0);
+                closest = Some((face, u * scale, v * scale));
+                closest_distance = distance;
+            }
+        }
+        return best.or(closest);
+    }
+
+    fn object_normal(&self, face: usize, u: f32, v: f32) -> Tuple {
+        let [a, b, c] = self.faces[face];
+        if self.normals.is_empty() {
+            let (v0, v1, v2) = self.corners(face);
+            return (v1 - v0).cross(&(v2 - v0)).normalize();
+        }
+        return self.normals[a] * (1.0 - u - v) + self.normals[b] * u + self.normals[c] * v;
+    }
+
+    fn to_world_normal(&self, object_normal: Tuple) -> Tuple {
+        let world_normal = self.inverse.transpose() * object_normal;
+        let world_normal_vector = Tuple::vector(world_normal.x(), world_normal.y(), world_normal.z());
+        return world_normal_vector.normalize();
+    }
+
+    // intersect_range for a ray already in object space
+    fn intersect_object(&self, transformed_ray: &Ray, t_min: f32, t_max: f32) -> Intersections<'_, Self> {
+        let origin = transformed_ray.origin();
+        let direction = transformed_ray.direction();
+        let o = [origin.x() - self.min.x(), origin.y() - self.min.y(), origin.z() - self.min.z()];
+        let d = [direction.x(), direction.y(), direction.z()];
+        let n = [self.max.x() - self.min.x(), self.max.y() - self.min.y(), self.max.z() - self.min.z()];
+        if self.faces.is_empty() || voxel::clip_to_grid(&o, &d, &n).is_none() {
+            return Intersections::new_empty();
+        }
+        let cull = self.cull_backfaces && transformed_ray.is_primary();
+        let mut xs = Intersections::new_empty();
+        for face in 0..self.faces.len() {
+            if let Some((t, u, v)) = self.intersect_face(face, origin, direction, cull) {
+                if watchdog::check_t("triangle mesh", self.id, t) && t >= t_min && t <= t_max {
+                    xs.add_point(Intersection::on_face(self, t, face, u, v));
+                }
+            }
+        }
+        return xs;
+    }
+}
+
+impl Shape for TriangleMesh {
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl Intersect<Self> for TriangleMesh {
+    fn intersect_range(&self, ray: &Ray, t_min: f32, t_max: f32) -> Intersections<'_, Self> {
+        self.intersect_object(&ray.transform(&self.inverse), t_min, t_max)
+    }
+
+    fn intersect_batch(&self, rays: &[Ray]) -> Vec<Intersections<'_, Self>> {
+        rays.iter().map(|ray| self.intersect_object(&ray.transform(&self.inverse), f32::NEG_INFINITY, f32::INFINITY)).collect()
+    }
+
+    // has to search the faces for the point; shading hits should go through
+    // normal_at_hit, which reads the face off the hit
+    fn normal_at(&self, point: Tuple) -> Tuple {
+        let object_normal = match self.locate(&(self.inverse * point)) {
+            Some((face, u, v)) => self.object_normal(face, u, v),
+            None => return Tuple::vector(0.0, 0.0, 0.0)
+        };
+        return self.to_world_normal(object_normal);
+    }
+
+    fn normal_at_hit(&self, hit: &Intersection<'_, Self>, point: Tuple) -> Tuple {
+        match (hit.face(), hit.barycentric()) {
+            (Some(face), Some((u, v))) => self.to_world_normal(self.object_normal(face, u, v)),
+            _ => self.normal_at(point)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use super::super::instance::Instance;
+
+    // unit square in the z = 0 plane split along its diagonal
+    fn square() -> TriangleMesh {
+        let vertices = vec![Tuple::point(0.0, 0.0, 0.0), Tuple::point(1.0, 0.0, 0.0),
+            Tuple::point(1.0, 1.0, 0.0), Tuple::point(0.0, 1.0, 0.0)];
+        TriangleMesh::new(1, vertices, vec![[0, 1, 2], [0, 2, 3]]).unwrap()
+    }
+
+    #[test]
+    fn test_new_mesh() {
+        let mesh = square();
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(mesh.bounds(), (Tuple::point(0.0, 0.0, 0.0), Tuple::point(1.0, 1.0, 0.0)));
+        assert!(TriangleMesh::new(1, vec![Tuple::point(0.0, 0.0, 0.0)], vec![[0, 0, 1]]).is_none());
+        let mut mesh = square();
+        assert!(mesh.set_normals(vec![Tuple::vector(0.0, 0.0, 1.0)]).is_none());
+    }
+
+    #[test]
+    fn test_moller_trumbore() {
+        let mesh = square();
+        let direction = Tuple::vector(0.0, 0.0, 1.0);
+        let (t, u, v) = mesh.intersect_face(0, &Tuple::point(0.75, 0.25, -2.0), &direction, false).unwrap();
+        assert_eq!((t, u, v), (2.0, 0.5, 0.25));
+        // the same point is outside the other half of the square
+        assert!(mesh.intersect_face(1, &Tuple::point(0.75, 0.25, -2.0), &direction, false).is_none());
+        // parallel to the triangle
+        assert!(mesh.intersect_face(0, &Tuple::point(0.5, 0.25, -2.0), &Tuple::vector(1.0, 0.0, 0.0), false).is_none());
+    }
+
+    #[test]
+    fn test_intersect_mesh() {
+        let mesh = square();
+        let r = Ray::new(Tuple::point(0.25, 0.75, -3.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = mesh.intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].point(), 3.0);
+        // outside the bounding box
+        let r = Ray::new(Tuple::point(2.0, 0.5, -3.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(mesh.intersect(&r).is_empty());
+        let mut moved = square();
+        moved.set_transform(Matrix4::translation(0.0, 0.0, 5.0));
+        let r = Ray::new(Tuple::point(0.25, 0.75, -3.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(moved.intersect(&r)[0].point(), 8.0);
+    }
+
+    #[test]
+    fn test_flat_and_smooth_normals() {
+        let mut mesh = square();
+        let n = mesh.normal_at(Tuple::point(0.75, 0.25, 0.0));
+        assert_eq!(n, Tuple::vector(0.0, 0.0, 1.0));
+        let tilt = Tuple::vector(1.0, 0.0, 1.0);
+        let up = Tuple::vector(0.0, 0.0, 1.0);
+        mesh.set_normals(vec![up, tilt, tilt, up]).unwrap();
+        // interpolated across the face: more tilted nearer x = 1
+        let near = mesh.normal_at(Tuple::point(0.9, 0.5, 0.0));
+        let far = mesh.normal_at(Tuple::point(0.1, 0.5, 0.0));
+        assert!(near.x() > far.x());
+        assert!((near.magnitude() - 1.0).abs() < 1e-4);
+        assert_eq!(mesh.normal_at(Tuple::point(0.0, 0.0, 0.0)), up);
+        // off the mesh the nearest face still gives a normal
+        assert_eq!(mesh.normal_at(Tuple::point(3.0, 0.5, 1.0)), tilt.normalize());
+    }
+
+    #[test]
+    fn test_singular_transform_is_rejected() {
+        let mut mesh = square();
+        let moved = Matrix4::translation(0.0, 0.0, 5.0);
+        assert!(mesh.set_transform(moved).is_some());
+        assert!(mesh.set_transform(Matrix4::scaling(1.0, 1.0, 0.0)).is_none());
+        assert_eq!(mesh.transform(), &moved);
+        let r = Ray::new(Tuple::point(0.5, 0.25, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(mesh.intersect(&r).hit().unwrap().point(), 5.0);
+    }
+
+    #[test]
+    fn test_hits_record_their_face() {
+        let mut mesh = square();
+        mesh.set_transform(Matrix4::scaling(2.0, 2.0, 2.0));
+        let tilt = Tuple::vector(1.0, 0.0, 1.0);
+        let up = Tuple::vector(0.0, 0.0, 1.0);
+        mesh.set_normals(vec![up, tilt, tilt, up]).unwrap();
+        let r = Ray::new(Tuple::point(1.5, 0.5, -3.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = mesh.intersect(&r);
+        let hit = xs.hit().unwrap();
+        assert_eq!(hit.face(), Some(0));
+        assert_eq!(hit.barycentric(), Some((0.5, 0.25)));
+        let point = r.position(hit.point());
+        assert_eq!(mesh.normal_at_hit(hit, point), mesh.normal_at(point));
+        // instances pass the face through to the shared mesh
+        let instance = Instance::new(2, Arc::new(mesh.clone()));
+        let xs = instance.intersect(&r);
+        let hit = xs.hit().unwrap();
+        assert_eq!(hit.face(), Some(0));
+        assert_eq!(instance.normal_at_hit(hit, point), mesh.normal_at(point));
+    }
+
+    #[test]
+    fn test_per_face_materials() {
+        use super::super::color::Color;
+        let mut mesh = square();
+        mesh.set_transform(Matrix4::translation(0.0, 0.0, 5.0));
+        let mut red = Material::new();
+        red.color = Color::RED;
+        let index = mesh.add_material(red.clone());
+        assert!(mesh.set_face_material(2, index).is_none());
+        assert!(mesh.set_face_material(0, index + 1).is_none());
+        mesh.set_face_material(0, index).unwrap();
+        assert_eq!(mesh.face_material(0), Some(&red));
+        assert_eq!(mesh.face_material(1), None);
+        // the hit carries the face it landed on
+        let r = Ray::new(Tuple::point(0.75, 0.25, -3.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(mesh.material_at(mesh.intersect(&r).hit().unwrap()), &red);
+        let r = Ray::new(Tuple::point(0.25, 0.75, -3.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(mesh.material_at(mesh.intersect(&r).hit().unwrap()), &Material::new());
+        assert_eq!(mesh.material_at(&Intersection::new(&mesh, 1.0)), &Material::new());
+    }
+
+    #[test]
+    fn test_backface_culling_only_for_primary_rays() {
+        // square() faces +z, so a ray travelling along +z sees its back
+        let mut mesh = square();
+        mesh.cull_backfaces = true;
+        let from_back = Ray::new(Tuple::point(0.25, 0.75, -3.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(mesh.intersect(&from_back).len(), 1);
+        let primary = Ray::new(Tuple::point(0.25, 0.75, -3.0), Tuple::vector(0.0, 0.0, 1.0)).as_primary();
+        assert!(mesh.intersect(&primary).is_empty());
+        let from_front = Ray::new(Tuple::point(0.25, 0.75, 3.0), Tuple::vector(0.0, 0.0, -1.0)).as_primary();
+        assert_eq!(mesh.intersect(&from_front).len(), 1);
+        mesh.cull_backfaces = false;
+        assert_eq!(mesh.intersect(&primary).len(), 1);
+    }
+
+    #[test]
+    fn test_edge_distance() {
+        let mut mesh = square();
+        let distance_at = |mesh: &TriangleMesh, x: f32, y: f32| {
+            let r = Ray::new(Tuple::point(x, y, -3.0), Tuple::vector(0.0, 0.0, 1.0));
+            mesh.edge_distance(mesh.intersect(&r).hit().unwrap()).unwrap()
+        };
+        assert!((distance_at(&mesh, 0.75, 0.25) - 0.25).abs() < 1e-4);
+        // the shared diagonal is an edge too
+        assert!(distance_at(&mesh, 0.5, 0.49) < 0.01);
+        assert!(mesh.edge_distance(&Intersection::new(&mesh, 1.0)).is_none());
+        // measured after the transform
+        mesh.set_transform(Matrix4::scaling(4.0, 4.0, 4.0));
+        assert!((distance_at(&mesh, 3.0, 1.0) - 1.0).abs() < 1e-4);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use super::ray::Ray;
+use super::tuple::Tuple;
+use super::matrix4::Matrix4;
+use super::voxel;
+
+// a node splits into octants once it holds more objects than this
+const LEAF_CAPACITY: usize = 8;
+const MAX_DEPTH: usize = 8;
+
+// world space box around an object space box, e.g. a shape's bounds() under
+// its transform(); moving spheres have their own Sphere::world_bounds()
+pub fn world_bounds(transform: &Matrix4, min: &Tuple, max: &Tuple) -> (Tuple, Tuple) {
+    let mut lo = Tuple::point(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+    let mut hi = Tuple::point(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for i in 0..8 {
+        let corner = Tuple::point(if i & 1 == 0 { min.x() } else { max.x() },
+            if i & 2 == 0 { min.y() } else { max.y() },
+            if i & 4 == 0 { min.z() } else { max.z() });
+        let p = *transform * corner;
+        lo = Tuple::point(lo.x().min(p.x()), lo.y().min(p.y()), lo.z().min(p.z()));
+        hi = Tuple::point(hi.x().max(p.x()), hi.y().max(p.y()), hi.z().max(p.z()));
+    }
+    return (lo, hi);
+}
+
+fn contains(min: &Tuple, max: &Tuple, lo: &Tuple, hi: &Tuple) -> bool {
+    lo.x() >= min.x() && lo.y() >= min.y() && lo.z() >= min.z()
+        && hi.x() <= max.x() && hi.y() <= max.y() && hi.z() <= max.z()
+}
+
+fn ray_hits_box(ray: &Ray, min: &Tuple, max: &Tuple) -> bool {
+    let origin = ray.origin();
+    let direction = ray.direction();
+    let o = [origin.x() - min.x(), origin.y() - min.y(), origin.z() - min.z()];
+    let d = [direction.x(), direction.y(), direction.z()];
+    let n = [max.x() - min.x(), max.y() - min.y(), max.z() - min.z()];
+    match voxel::clip_to_grid(&o, &d, &n) {
+        Some((_, t_exit)) => t_exit >= 0.0,
+        None => false
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    min: Tuple,
+    max: Tuple,
+    // objects that don't fit entirely inside a single child
+    items: Vec<i32>,
+    children: Option<Box<[Node; 8]>>
+}
+
+impl Node {
+    fn new(min: Tuple, max: Tuple) -> Self {
+        Self {min, max, items: Vec::new(), children: None}
+    }
+
+    fn octant(&self, i: usize) -> (Tuple, Tuple) {
+        let mid = Tuple::point((self.min.x() + self.max.x()) / 2.0, (self.min.y() + self.max.y()) / 2.0,
+            (self.min.z() + self.max.z()) / 2.0);
+        let pick = |bit: bool, lo: f32, m: f32, hi: f32| if bit { (m, hi) } else { (lo, m) };
+        let (x0, x1) = pick(i & 1 != 0, self.min.x(), mid.x(), self.max.x());
+        let (y0, y1) = pick(i & 2 != 0, self.min.y(), mid.y(), self.max.y());
+        let (z0, z1) = pick(i & 4 != 0, self.min.z(), mid.z(), self.max.z());
+        (Tuple::point(x0, y0, z0), Tuple::point(x1, y1, z1))
+    }
+
+    fn child_for(&self, lo: &Tuple, hi: &Tuple) -> Option<usize> {
+        (0..8).find(|&i| {
+            let (min, max) = self.octant(i);
+            contains(&min, &max, lo, hi)
+        })
+    }
+
+    fn insert(&mut self, id: i32, lo: &Tuple, hi: &Tuple, depth: usize, bounds: &HashMap<i32, (Tuple, Tuple)>) {
+        if let Some(children) = &mut self.children {
+            if let Some(i) = (0..8).find(|&i| contains(&children[i].min, &children[i].max, lo, hi)) {
+                children[i].insert(id, lo, hi, depth + 1, bounds);
+                return;
+            }
+        }
+        self.items.push(id);
+        if self.children.is_none() && self.items.len() > LEAF_CAPACITY && depth < MAX_DEPTH {
+            self.split(depth, bounds);
+        }
+    }
+
+    fn split(&mut self, depth: usize, bounds: &HashMap<i32, (Tuple, Tuple)>) {
+        let children: [Node; 8] = std::array::from_fn(|i| {
+            let (min, max) = self.octant(i);
+            Node::new(min, max)
+        });
+        self.children = Some(Box::new(children));
+        let items = std::mem::take(&mut self.items);
+        for id in items {
+            let (lo, hi) = bounds[&id];
+            self.insert(id, &lo, &hi, depth, bounds);
+        }
+    }
+
+    fn remove(&mut self, id: i32, lo: &Tuple, hi: &Tuple) -> bool {
+        if let Some(pos) = self.items.iter().position(|&item| item == id) {
+            self.items.swap_remove(pos);
+            return true;
+        }
+        let i = match self.child_for(lo, hi) {
+            Some(i) => i,
+            None => return false
+        };
+        match &mut self.children {
+            Some(children) => children[i].remove(id, lo, hi),
+            None => false
+        }
+    }
+
+    fn query(&self, ray: &Ray, found: &mut Vec<i32>) {
+        if !ray_hits_box(ray, &self.min, &self.max) {
+            return;
+        }
+        found.extend_from_slice(&self.items);
+        if let Some(children) = &self.children {
+            for child in children.iter() {
+                child.query(ray, found);
+            }
+        }
+    }
+
+    fn node_count(&self) -> usize {
+        1 + self.children.as_ref().map_or(0, |children| children.iter().map(|c| c.node_count()).sum())
+    }
+}
+
+// Spatial index over objects' world space boxes, keyed by the objects' ids.
+// Objects can be inserted and removed one at a time, so an interactively
+// edited scene doesn't need a full rebuild per change. Rays get back the ids
+// of objects they might hit, which the caller then intersects.
+#[derive(Debug, Clone)]
+pub struct Octree {
+    root: Node,
+    bounds: HashMap<i32, (Tuple, Tuple)>,
+    // objects reaching outside the root box, tested by every ray
+    outside: Vec<i32>
+}
+
+impl Octree {
+    // min and max bound the region worth subdividing; objects outside it are
+    // still found, just without culling
+    pub fn new(min: Tuple, max: Tuple) -> Self {
+        Self {root: Node::new(min, max), bounds: HashMap::new(), outside: Vec::new()}
+    }
+
+    pub fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounds.is_empty()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.root.node_count()
+    }
+
+    // inserting an id that's already present moves it to the new box
+    pub fn insert(&mut self, id: i32, min: Tuple, max: Tuple) {
+        self.remove(id);
+        self.bounds.insert(id, (min, max));
+        if contains(&self.root.min, &self.root.max, &min, &max) {
+            self.root.insert(id, &min, &max, 0, &self.bounds);
+        } else {
+            self.outside.push(id);
+        }
+    }
+
+    // false if the id wasn't in the tree
+    pub fn remove(&mut self, id: i32) -> bool {
+        let (min, max) = match self.bounds.remove(&id) {
+            Some(bounds) => bounds,
+            None => return false
+        };
+        if let Some(pos) = self.outside.iter().position(|&item| item == id) {
+            self.outside.swap_remove(pos);
+            return true;
+        }
+        return self.root.remove(id, &min, &max);
+    }
+
+    // ids of the objects whose boxes the ray passes through, in no particular order
+    pub fn candidates(&self, ray: &Ray) -> Vec<i32> {
+        let mut found = self.outside.clone();
+        self.root.query(ray, &mut found);
+        found.retain(|id| {
+            let (min, max) = &self.bounds[id];
+            ray_hits_box(ray, min, max)
+        });
+        return found;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::sphere::Sphere;
+
+    fn unit_box_at(x: f32, y: f32, z: f32) -> (Tuple, Tuple) {
+        (Tuple::point(x - 0.5, y - 0.5, z - 0.5), Tuple::point(x + 0.5, y + 0.5, z + 0.5))
+    }
+
+    fn grid() -> Octree {
+        let mut tree = Octree::new(Tuple::point(-10.0, -10.0, -10.0), Tuple::point(10.0, 10.0, 10.0));
+        let mut id = 0;
+        for x in -4..4 {
+            for z in -4..4 {
+                let (min, max) = unit_box_at(x as f32 * 2.0, 0.0, z as f32 * 2.0);
+                tree.insert(id, min, max);
+                id += 1;
+            }
+        }
+        tree
+    }
+
+    #[test]
+    fn test_world_bounds() {
+        let mut s = Sphere::new(1);
+        s.set_transform(Matrix4::translation(1.0, 2.0, 3.0) * Matrix4::scaling(2.0, 1.0, 1.0));
+        let (min, max) = s.bounds();
+        let (lo, hi) = world_bounds(s.transform(), &min, &max);
+        assert_eq!(lo, Tuple::point(-1.0, 1.0, 2.0));
+        assert_eq!(hi, Tuple::point(3.0, 3.0, 4.0));
+    }
+
+    #[test]
+    fn test_ray_candidates() {
+        let tree = grid();
+        assert_eq!(tree.len(), 64);
+        assert!(tree.node_count() > 1);
+        // straight down the x = 0 column of boxes
+        let r = Ray::new(Tuple::point(0.0, 0.0, -20.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut found = tree.candidates(&r);
+        found.sort();
+        assert_eq!(found, vec![32, 33, 34, 35, 36, 37, 38, 39]);
+        // passing over everything
+        let r = Ray::new(Tuple::point(0.0, 5.0, -20.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(tree.candidates(&r).is_empty());
+    }
+
+    #[test]
+    fn test_incremental_edits() {
+        let mut tree = grid();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -20.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(tree.remove(33));
+        assert!(!tree.remove(33));
+        assert!(!tree.candidates(&r).contains(&33));
+        // moving an object out of the ray's path
+        let (min, max) = unit_box_at(0.0, 5.0, 0.0);
+        tree.insert(34, min, max);
+        assert!(!tree.candidates(&r).contains(&34));
+        assert_eq!(tree.len(), 63);
+        // objects beyond the root box are still found
+        let (min, max) = unit_box_at(0.0, 0.0, 15.0);
+        tree.insert(100, min, max);
+        assert!(tree.candidates(&r).contains(&100));
+        assert!(tree.remove(100));
+    }
+}
\ No newline at end of file
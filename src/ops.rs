@@ -0,0 +1,88 @@
+// Operator boilerplate for the vector-like types. Each operator is written
+// once for references and these macros derive the owned/borrowed variants
+// from it, so callers never have to clone just to satisfy an operator.
+
+// T op U, T op &U and &T op U from &T op &U
+macro_rules! forward_binop {
+    (impl $imp:ident, $method:ident for $t:ty, $u:ty => $out:ty) => {
+        impl $imp<$u> for $t {
+            type Output = $out;
+            fn $method(self, _rhs: $u) -> $out {
+                $imp::$method(&self, &_rhs)
+            }
+        }
+
+        impl $imp<&$u> for $t {
+            type Output = $out;
+            fn $method(self, _rhs: &$u) -> $out {
+                $imp::$method(&self, _rhs)
+            }
+        }
+
+        impl $imp<$u> for &$t {
+            type Output = $out;
+            fn $method(self, _rhs: $u) -> $out {
+                $imp::$method(self, &_rhs)
+            }
+        }
+    };
+}
+
+// T op f32 from &T op f32
+macro_rules! forward_scalar_binop {
+    (impl $imp:ident, $method:ident for $t:ty) => {
+        impl $imp<f32> for $t {
+            type Output = $t;
+            fn $method(self, _rhs: f32) -> $t {
+                $imp::$method(&self, _rhs)
+            }
+        }
+    };
+}
+
+// f32 * T and f32 * &T from &T * f32
+macro_rules! scalar_mul_left {
+    ($t:ty) => {
+        impl Mul<$t> for f32 {
+            type Output = $t;
+            fn mul(self, _rhs: $t) -> $t {
+                &_rhs * self
+            }
+        }
+
+        impl Mul<&$t> for f32 {
+            type Output = $t;
+            fn mul(self, _rhs: &$t) -> $t {
+                _rhs * self
+            }
+        }
+    };
+}
+
+// T op= U and T op= &U from &T op &U
+macro_rules! forward_assign_op {
+    (impl $imp:ident, $method:ident for $t:ty, $u:ty, $op:tt) => {
+        impl $imp<$u> for $t {
+            fn $method(&mut self, _rhs: $u) {
+                *self = &*self $op &_rhs;
+            }
+        }
+
+        impl $imp<&$u> for $t {
+            fn $method(&mut self, _rhs: &$u) {
+                *self = &*self $op _rhs;
+            }
+        }
+    };
+}
+
+// T op= f32 from &T op f32
+macro_rules! forward_scalar_assign_op {
+    (impl $imp:ident, $method:ident for $t:ty, $op:tt) => {
+        impl $imp<f32> for $t {
+            fn $method(&mut self, _rhs: f32) {
+                *self = &*self $op _rhs;
+            }
+        }
+    };
+}
\ No newline at end of file
@@ -0,0 +1,82 @@
+// Ready-made materials with sensible parameters. Each returns a fresh value
+// to tweak further. There is no transparency or refraction yet, so no glass.
+use super::color::Color;
+use super::material::{Material, PbrMaterial, SpecularModel};
+
+// mirror-like polished metal
+pub fn chrome() -> PbrMaterial {
+    PbrMaterial::new(Color::new(0.55, 0.56, 0.55), 0.05, 1.0)
+}
+
+// metal with a soft, spread-out highlight
+pub fn brushed_metal() -> PbrMaterial {
+    PbrMaterial::new(Color::new(0.91, 0.92, 0.92), 0.35, 1.0)
+}
+
+pub fn gold() -> PbrMaterial {
+    PbrMaterial::new(Color::new(1.0, 0.78, 0.34), 0.2, 1.0)
+}
+
+pub fn copper() -> PbrMaterial {
+    PbrMaterial::new(Color::new(0.95, 0.64, 0.54), 0.25, 1.0)
+}
+
+// coloured plastic with a small, sharp highlight
+pub fn plastic(color: Color) -> Material {
+    let mut material = Material::new();
+    material.color = color;
+    material.diffuse = 0.8;
+    material.specular = 0.5;
+    material.shininess = 300.0;
+    material.specular_model = SpecularModel::BlinnPhong;
+    return material;
+}
+
+// plastic without a visible highlight
+pub fn matte_plastic(color: Color) -> PbrMaterial {
+    PbrMaterial::new(color, 0.9, 0.0)
+}
+
+// dark and dull, with a broad faint sheen
+pub fn rubber(color: Color) -> Material {
+    let mut material = Material::new();
+    material.color = color;
+    material.diffuse = 0.7;
+    material.specular = 0.1;
+    material.shininess = 10.0;
+    return material;
+}
+
+// purely diffuse, like chalk or unglazed clay
+pub fn matte(color: Color) -> Material {
+    let mut material = Material::new();
+    material.color = color;
+    material.specular = 0.0;
+    material.specular_model = SpecularModel::None;
+    return material;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::material::MaterialModel;
+    use super::super::tuple::Tuple;
+
+    #[test]
+    fn test_metals_are_glossier_than_plastics() {
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+        let peak = |m: &dyn MaterialModel| m.eval(&normal, &normal, &normal).red();
+        assert!(peak(&chrome()) > peak(&brushed_metal()));
+        assert!(peak(&brushed_metal()) > peak(&matte_plastic(Color::WHITE)));
+        assert!(peak(&plastic(Color::RED)) > peak(&rubber(Color::RED)));
+        assert_eq!(matte(Color::WHITE).eval(&normal, &normal, &normal), Color::new(0.9, 0.9, 0.9));
+    }
+
+    #[test]
+    fn test_presets_keep_their_colour() {
+        assert_eq!(plastic(Color::BLUE).color, Color::BLUE);
+        assert_eq!(rubber(Color::GREEN).color, Color::GREEN);
+        assert_eq!(matte_plastic(Color::RED).color, Color::RED);
+        assert_eq!(gold().metallic, 1.0);
+    }
+}
\ No newline at end of file
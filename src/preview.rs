@@ -0,0 +1,102 @@
+use super::camera::Camera;
+use super::canvas::Canvas;
+use super::color::Color;
+use super::matrix4::Matrix4;
+use super::tuple::Tuple;
+
+// quick wireframe previews: object bounds are projected straight onto the
+// canvas through the camera, no rays traced, so camera and object placement
+// can be checked before a full render
+
+// corner pairs of a box, corners numbered by their (x, y, z) bits
+const BOX_EDGES: [(usize, usize); 12] = [(0, 1), (2, 3), (4, 5), (6, 7),
+    (0, 2), (1, 3), (4, 6), (5, 7),
+    (0, 4), (1, 5), (2, 6), (3, 7)];
+
+// draws the pixels of the line between two canvas positions, skipping the
+// parts that fall off the canvas
+pub fn draw_line(canvas: &mut Canvas, from: (f32, f32), to: (f32, f32), color: &Color) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let steps = dx.abs().max(dy.abs()).ceil().max(1.0) as usize;
+    let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+    for i in 0..=steps {
+        let t = i as f32 / steps as f32;
+        let x = (from.0 + dx * t).floor();
+        let y = (from.1 + dy * t).floor();
+        if x >= 0.0 && y >= 0.0 && x < width && y < height {
+            canvas.write_pixel(x as usize, y as usize, color.clone());
+        }
+    }
+}
+
+// outlines the box from min to max (object space), placed by transform
+pub fn wireframe_box(canvas: &mut Canvas, camera: &Camera, transform: &Matrix4, min: &Tuple, max: &Tuple, color: &Color) {
+    let mut corners = Vec::with_capacity(8);
+    for i in 0..8 {
+        let x = if i & 4 == 0 { min.x() } else { max.x() };
+        let y = if i & 2 == 0 { min.y() } else { max.y() };
+        let z = if i & 1 == 0 { min.z() } else { max.z() };
+        corners.push(camera.project(&(transform * &Tuple::point(x, y, z))));
+    }
+    for (a, b) in BOX_EDGES.iter() {
+        // edges crossing behind the eye are dropped rather than clipped
+        if let (Some(from), Some(to)) = (corners[*a], corners[*b]) {
+            draw_line(canvas, from, to, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::sphere::Sphere;
+    use super::super::voxel::VoxelGrid;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn lit(canvas: &Canvas) -> usize {
+        canvas.gen_ppm_body().split_whitespace().filter(|v| *v != "0").count()
+    }
+
+    #[test]
+    fn test_draw_line() {
+        let mut canvas = Canvas::new(5, 5);
+        draw_line(&mut canvas, (0.5, 0.5), (4.5, 4.5), &Color::new(1.0, 1.0, 1.0));
+        assert_eq!(lit(&canvas), 5 * 3);
+        // entirely off the canvas
+        let mut canvas = Canvas::new(5, 5);
+        draw_line(&mut canvas, (-10.0, -1.0), (20.0, -1.0), &Color::new(1.0, 1.0, 1.0));
+        assert_eq!(lit(&canvas), 0);
+    }
+
+    #[test]
+    fn test_camera_project_inverts_rays() {
+        let mut camera = Camera::new(11, 11, FRAC_PI_2);
+        camera.set_transform(Matrix4::view_transform(&Tuple::point(0.0, 0.0, -5.0),
+            &Tuple::point(0.0, 0.0, 0.0), &Tuple::vector(0.0, 1.0, 0.0)));
+        let r = camera.ray_for_pixel(2, 7);
+        let (x, y) = camera.project(&r.position(3.0)).unwrap();
+        assert!((x - 2.5).abs() < 1e-3 && (y - 7.5).abs() < 1e-3);
+        assert_eq!(camera.project(&Tuple::point(0.0, 0.0, -6.0)), None);
+    }
+
+    #[test]
+    fn test_wireframe_shapes() {
+        let mut camera = Camera::new(50, 50, FRAC_PI_2);
+        camera.set_transform(Matrix4::view_transform(&Tuple::point(0.0, 0.0, -5.0),
+            &Tuple::point(0.0, 0.0, 0.0), &Tuple::vector(0.0, 1.0, 0.0)));
+        let mut canvas = Canvas::new(50, 50);
+        let s = Sphere::new(1);
+        let (min, max) = s.bounds();
+        wireframe_box(&mut canvas, &camera, s.transform(), &min, &max, &Color::new(1.0, 0.0, 0.0));
+        let drawn = lit(&canvas);
+        assert!(drawn > 0);
+        // the sphere's box fits well inside a 90 degree view from 5 units away
+        let body = canvas.gen_ppm_body();
+        let first_row: Vec<&str> = body.lines().next().unwrap().split_whitespace().collect();
+        assert!(first_row.iter().all(|v| *v == "0"));
+        let grid = VoxelGrid::new(2, 2, 2, 2);
+        let (min, max) = grid.bounds();
+        wireframe_box(&mut canvas, &camera, grid.transform(), &min, &max, &Color::new(0.0, 1.0, 0.0));
+        assert!(lit(&canvas) > drawn);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub pixels_done: usize,
+    pub total_pixels: usize,
+    pub tiles_done: usize,
+    pub total_tiles: usize,
+    pub elapsed: Duration
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f32 {
+        if self.total_pixels == 0 {
+            return 1.0;
+        }
+        self.pixels_done as f32 / self.total_pixels as f32
+    }
+
+    // extrapolates from the pixels done so far
+    pub fn eta(&self) -> Option<Duration> {
+        if self.pixels_done == 0 {
+            return None;
+        }
+        let remaining = self.total_pixels.saturating_sub(self.pixels_done);
+        Some(self.elapsed.mul_f64(remaining as f64 / self.pixels_done as f64))
+    }
+}
+
+// called by the renderer after every finished tile
+pub trait RenderProgress {
+    fn update(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress)> RenderProgress for F {
+    fn update(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+// reports nothing, for renders nobody is watching
+pub struct NoProgress;
+
+impl RenderProgress for NoProgress {
+    fn update(&mut self, _progress: &Progress) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(pixels_done: usize, elapsed: u64) -> Progress {
+        Progress {pixels_done, total_pixels: 100, tiles_done: 0, total_tiles: 4, elapsed: Duration::from_secs(elapsed)}
+    }
+
+    #[test]
+    fn test_fraction() {
+        assert_eq!(progress(0, 0).fraction(), 0.0);
+        assert_eq!(progress(25, 1).fraction(), 0.25);
+        assert_eq!(progress(100, 4).fraction(), 1.0);
+    }
+
+    #[test]
+    fn test_eta() {
+        assert_eq!(progress(0, 3).eta(), None);
+        assert_eq!(progress(25, 10).eta(), Some(Duration::from_secs(30)));
+        assert_eq!(progress(100, 10).eta(), Some(Duration::from_secs(0)));
+        assert_eq!(progress(150, 10).eta(), Some(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn test_closure_progress() {
+        let mut seen = Vec::new();
+        {
+            let mut hook = |p: &Progress| seen.push(p.pixels_done);
+            hook.update(&progress(10, 1));
+            hook.update(&progress(20, 2));
+        }
+        assert_eq!(seen, vec![10, 20]);
+    }
+}
\ No newline at end of file
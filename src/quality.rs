@@ -0,0 +1,84 @@
+use super::camera::Camera;
+use super::sampler::Sampler;
+
+// named bundles of the camera's cost/quality knobs; there is no recursion or
+// shadow sampling yet, so sampling and resolution are all a preset controls
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quality {
+    Draft,
+    Medium,
+    Final
+}
+
+impl Quality {
+    pub fn samples(&self) -> u16 {
+        match self {
+            Quality::Draft => 1,
+            Quality::Medium => 2,
+            Quality::Final => 4
+        }
+    }
+
+    pub fn sampler(&self) -> Sampler {
+        match self {
+            Quality::Draft => Sampler::Uniform,
+            Quality::Medium | Quality::Final => Sampler::Jittered
+        }
+    }
+
+    pub fn adaptive_threshold(&self) -> Option<f32> {
+        match self {
+            Quality::Draft | Quality::Final => None,
+            Quality::Medium => Some(0.1)
+        }
+    }
+
+    pub fn resolution_scale(&self) -> f32 {
+        match self {
+            Quality::Draft => 0.5,
+            Quality::Medium | Quality::Final => 1.0
+        }
+    }
+
+    // a copy of `camera` configured for this preset
+    pub fn apply(&self, camera: &Camera) -> Camera {
+        let mut configured = camera.scaled(self.resolution_scale());
+        configured.set_samples(self.samples());
+        configured.set_sampler(self.sampler());
+        configured.set_adaptive_threshold(self.adaptive_threshold());
+        return configured;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_draft() {
+        let c = Quality::Draft.apply(&Camera::new(100, 60, FRAC_PI_2));
+        assert_eq!((c.hsize(), c.vsize()), (50, 30));
+        assert_eq!(c.samples(), 1);
+        assert_eq!(c.sampler(), Sampler::Uniform);
+        assert_eq!(c.adaptive_threshold(), None);
+    }
+
+    #[test]
+    fn test_final() {
+        let mut camera = Camera::new(100, 60, FRAC_PI_2);
+        camera.set_seed(7);
+        let c = Quality::Final.apply(&camera);
+        assert_eq!((c.hsize(), c.vsize()), (100, 60));
+        assert_eq!(c.samples(), 4);
+        assert_eq!(c.sampler(), Sampler::Jittered);
+        assert_eq!(c.seed(), 7);
+    }
+
+    #[test]
+    fn test_presets_get_more_expensive() {
+        let cost = |q: Quality| q.samples() as f32 * q.resolution_scale() * q.resolution_scale();
+        assert!(cost(Quality::Draft) < cost(Quality::Medium));
+        assert!(cost(Quality::Medium) < cost(Quality::Final));
+    }
+}
\ No newline at end of file
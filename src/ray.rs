@@ -1,17 +1,38 @@
 use super::tuple::Tuple;
-use super::matrix::Matrix;
+use super::matrix4::Matrix4;
 
 #[derive(Debug, PartialEq)]
 pub struct Ray {
     origin: Tuple,
-    direction: Tuple
+    direction: Tuple,
+    // moment within the shutter interval, for motion blur
+    time: f32,
+    // straight from the camera; shadow, reflection and refraction rays
+    // aren't, so optimizations like backface culling stay off for them
+    primary: bool
 }
 
 impl Ray {
     pub fn new(origin: Tuple, direction: Tuple) -> Self {
         //TODO: first parameter should be a point and second a vector
         // do we need to add a check?
-        Self {origin, direction}
+        Self {origin, direction, time: 0.0, primary: false}
+    }
+
+    pub fn with_time(self, time: f32) -> Self {
+        Self {time, ..self}
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn as_primary(self) -> Self {
+        Self {primary: true, ..self}
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.primary
     }
 
     pub fn origin(&self) -> &Tuple {
@@ -26,9 +47,63 @@ impl Ray {
         (self.direction * t) + self.origin
     }
 
-    pub fn transform(&self, transform: &Matrix) -> Self {
-        return Self {origin: (transform * &self.origin).unwrap(),
-            direction: (transform * &self.direction).unwrap()}
+    pub fn transform(&self, transform: &Matrix4) -> Self {
+        return Self {origin: transform * &self.origin,
+            direction: transform * &self.direction, time: self.time, primary: self.primary}
+    }
+}
+
+// Rays stored component by component (structure of arrays), the layout
+// SIMD traversal wants. Shapes without a packet path unpack it with rays().
+#[derive(Debug, PartialEq, Clone)]
+pub struct RayPacket {
+    origin: [Vec<f32>; 3],
+    direction: [Vec<f32>; 3],
+    time: Vec<f32>,
+    primary: Vec<bool>
+}
+
+impl RayPacket {
+    pub fn from_rays(rays: &[Ray]) -> Self {
+        let mut packet = Self {origin: Default::default(), direction: Default::default(), time: Vec::with_capacity(rays.len()),
+            primary: Vec::with_capacity(rays.len())};
+        for ray in rays {
+            let (o, d) = (ray.origin(), ray.direction());
+            for (axis, (oc, dc)) in [(o.x(), d.x()), (o.y(), d.y()), (o.z(), d.z())].iter().enumerate() {
+                packet.origin[axis].push(*oc);
+                packet.direction[axis].push(*dc);
+            }
+            packet.time.push(ray.time());
+            packet.primary.push(ray.is_primary());
+        }
+        return packet;
+    }
+
+    pub fn len(&self) -> usize {
+        self.time.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.time.is_empty()
+    }
+
+    // one component (0 = x, 1 = y, 2 = z) of every origin or direction
+    pub fn origins(&self, axis: usize) -> &[f32] {
+        &self.origin[axis]
+    }
+
+    pub fn directions(&self, axis: usize) -> &[f32] {
+        &self.direction[axis]
+    }
+
+    pub fn ray(&self, i: usize) -> Ray {
+        let ray = Ray::new(Tuple::point(self.origin[0][i], self.origin[1][i], self.origin[2][i]),
+            Tuple::vector(self.direction[0][i], self.direction[1][i], self.direction[2][i])).with_time(self.time[i]);
+        Ray {primary: self.primary[i], ..ray}
+    }
+
+    pub fn rays(&self) -> Vec<Ray> {
+        (0..self.len()).map(|i| self.ray(i)).collect()
     }
 }
 
@@ -56,7 +131,7 @@ mod tests {
     #[test]
     fn test_ray_translation() {
         let r = Ray::new(Tuple::point(1.0, 2.0, 3.0), Tuple::vector(0.0, 1.0, 0.0));
-        let transform = TransformBuilder::new(4).translate(3.0, 4.0, 5.0).build();
+        let transform = TransformBuilder::new4().translate(3.0, 4.0, 5.0).build();
         let r2 = r.transform(&transform);
         assert_eq!(r2.origin, Tuple::point(4.0, 6.0, 8.0));
         assert_eq!(r2.direction, Tuple::vector(0.0, 1.0, 0.0));
@@ -65,9 +140,31 @@ mod tests {
     #[test]
     fn test_ray_scaling() {
         let r = Ray::new(Tuple::point(1.0, 2.0, 3.0), Tuple::vector(0.0, 1.0, 0.0));
-        let transform = TransformBuilder::new(4).scale(2.0, 3.0, 4.0).build();
+        let transform = TransformBuilder::new4().scale(2.0, 3.0, 4.0).build();
         let r2 = r.transform(&transform);
         assert_eq!(r2.origin, Tuple::point(2.0, 6.0, 12.0));
         assert_eq!(r2.direction, Tuple::vector(0.0, 3.0, 0.0));
     }
+
+    #[test]
+    fn test_ray_time() {
+        let r = Ray::new(Tuple::point(1.0, 2.0, 3.0), Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(r.time(), 0.0);
+        let r = r.with_time(0.25);
+        assert_eq!(r.time(), 0.25);
+        let moved = r.transform(&TransformBuilder::new4().translate(3.0, 4.0, 5.0).build());
+        assert_eq!(moved.time(), 0.25);
+    }
+
+    #[test]
+    fn test_ray_packet_round_trip() {
+        let rays = vec![Ray::new(Tuple::point(1.0, 2.0, 3.0), Tuple::vector(0.0, 0.0, 1.0)),
+            Ray::new(Tuple::point(-1.0, 0.0, 5.0), Tuple::vector(1.0, 0.0, 0.0)).with_time(0.5).as_primary()];
+        let packet = RayPacket::from_rays(&rays);
+        assert_eq!(packet.len(), 2);
+        assert_eq!(packet.origins(0), &[1.0, -1.0]);
+        assert_eq!(packet.directions(2), &[1.0, 0.0]);
+        assert_eq!(packet.rays(), rays);
+        assert!(RayPacket::from_rays(&[]).is_empty());
+    }
 }
@@ -0,0 +1,198 @@
+// small, dependency free random number generator (xorshift64*), seeded so
+// that renders are reproducible
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // a zero state would make xorshift return zeros forever
+        Self {state: Self::mix(seed) | 1}
+    }
+
+    // splitmix64 finaliser, used to spread similar seeds apart
+    pub fn mix(value: u64) -> u64 {
+        let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        return z ^ (z >> 31);
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        return self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+    }
+
+    // uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+// stable value in [0, 1) for an object instance, so scattered copies of a
+// shape can vary procedurally while rendering identically every time
+pub fn instance_random(id: i32, seed: u64) -> f32 {
+    let mut rng = Rng::new(seed ^ Rng::mix(id as u32 as u64));
+    rng.next_f32()
+}
+
+// Position of `index` in a pseudo-random permutation of 0..len chosen by
+// `seed`, without building the permutation (Kensler's hash with cycle
+// walking, from "Correlated Multi-Jittered Sampling"). Each prefix of the
+// order is spread over the whole range rather than bunched at the start.
+pub fn permute(index: usize, len: usize, seed: u64) -> usize {
+    assert!(index < len && len <= u32::MAX as usize);
+    let (len, p) = (len as u32, (seed ^ (seed >> 32)) as u32);
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    let mut i = index as u32;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        // values past len are walked on until they land back inside
+        if i < len {
+            break;
+        }
+    }
+    return ((i as u64 + p as u64) % len as u64) as usize;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampler {
+    // centres of an n x n grid, no randomness
+    Uniform,
+    // n * n independent points
+    Random,
+    // one random point inside each cell of an n x n grid
+    Jittered
+}
+
+impl Sampler {
+    // n * n points in the unit square, e.g. sub-pixel offsets or lens positions
+    pub fn samples(&self, n: usize, rng: &mut Rng) -> Vec<(f32, f32)> {
+        (0..n * n).map(|index| self.sample(n, index, rng)).collect()
+    }
+
+    // the index-th of the n * n points, row by row
+    pub fn sample(&self, n: usize, index: usize, rng: &mut Rng) -> (f32, f32) {
+        let (i, j) = (index / n, index % n);
+        let cell = 1.0 / n as f32;
+        match self {
+            Sampler::Uniform => ((j as f32 + 0.5) * cell, (i as f32 + 0.5) * cell),
+            Sampler::Random => (rng.next_f32(), rng.next_f32()),
+            Sampler::Jittered => ((j as f32 + rng.next_f32()) * cell, (i as f32 + rng.next_f32()) * cell)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_is_reproducible() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+        let xs: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+        let ys: Vec<u64> = (0..8).map(|_| b.next_u64()).collect();
+        let zs: Vec<u64> = (0..8).map(|_| c.next_u64()).collect();
+        assert_eq!(xs, ys);
+        assert_ne!(xs, zs);
+    }
+
+    #[test]
+    fn test_rng_zero_seed() {
+        let mut rng = Rng::new(0);
+        assert_ne!(rng.next_u64(), 0);
+    }
+
+    #[test]
+    fn test_next_f32_range() {
+        let mut rng = Rng::new(7);
+        let mut sum = 0.0;
+        for _ in 0..10000 {
+            let v = rng.next_f32();
+            assert!((0.0..1.0).contains(&v));
+            sum += v;
+        }
+        // mean of a uniform distribution is 0.5
+        assert!((sum / 10000.0 - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_uniform_samples() {
+        let mut rng = Rng::new(1);
+        let points = Sampler::Uniform.samples(2, &mut rng);
+        assert_eq!(points, vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]);
+        assert_eq!(rng, Rng::new(1));
+    }
+
+    #[test]
+    fn test_random_samples() {
+        let points = Sampler::Random.samples(3, &mut Rng::new(5));
+        assert_eq!(points.len(), 9);
+        for (x, y) in &points {
+            assert!((0.0..1.0).contains(x) && (0.0..1.0).contains(y));
+        }
+        assert_eq!(points, Sampler::Random.samples(3, &mut Rng::new(5)));
+    }
+
+    #[test]
+    fn test_jittered_samples_stay_in_cells() {
+        let n = 4;
+        let points = Sampler::Jittered.samples(n, &mut Rng::new(9));
+        assert_eq!(points.len(), n * n);
+        for (idx, (x, y)) in points.iter().enumerate() {
+            let (i, j) = (idx / n, idx % n);
+            assert_eq!((x * n as f32).floor() as usize, j);
+            assert_eq!((y * n as f32).floor() as usize, i);
+        }
+    }
+
+    #[test]
+    fn test_instance_random() {
+        let a = instance_random(1, 42);
+        assert!((0.0..1.0).contains(&a));
+        assert_eq!(a, instance_random(1, 42));
+        assert_ne!(a, instance_random(2, 42));
+        assert_ne!(a, instance_random(1, 43));
+    }
+
+    #[test]
+    fn test_permute() {
+        for len in [1, 2, 5, 9, 16, 100] {
+            for seed in [0, 7, u64::MAX] {
+                let mut seen: Vec<usize> = (0..len).map(|i| permute(i, len, seed)).collect();
+                seen.sort();
+                assert_eq!(seen, (0..len).collect::<Vec<_>>());
+            }
+        }
+        let a: Vec<usize> = (0..9).map(|i| permute(i, 9, 1)).collect();
+        let b: Vec<usize> = (0..9).map(|i| permute(i, 9, 2)).collect();
+        assert_ne!(a, b);
+    }
+}
\ No newline at end of file
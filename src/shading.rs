@@ -0,0 +1,200 @@
+// Stylized and diagnostic shading stages. There is no world to switch a
+// global mode on, so each stage is applied by the color_at closure to what
+// it shaded, using what it knows about the hit.
+use super::color::Color;
+use super::light::PointLight;
+use super::material::{face_forward, Material, MaterialModel};
+use super::sampler::Rng;
+use super::tuple::Tuple;
+
+// Draws edges in `color` where a hit lies within `width` (world units) of a
+// triangle edge, e.g. TriangleMesh::edge_distance. Over the shaded image, or
+// over `background` alone for a plain wireframe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wireframe {
+    pub color: Color,
+    pub width: f32,
+    pub overlay: bool,
+    pub background: Color
+}
+
+impl Wireframe {
+    pub fn new(color: Color, width: f32) -> Self {
+        Self {color, width, overlay: true, background: Color::BLACK}
+    }
+
+    // `edge_distance` is None for misses and for shapes without edges
+    pub fn apply(&self, shaded: &Color, edge_distance: Option<f32>) -> Color {
+        match edge_distance {
+            Some(distance) if distance <= self.width => self.color.clone(),
+            _ if self.overlay => shaded.clone(),
+            _ => self.background.clone()
+        }
+    }
+}
+
+// Cel shading: diffuse light quantized into flat bands, a hard-edged
+// highlight and a dark outline where the surface turns away from the eye.
+// Uses the material's colour, ambient, diffuse and specular strengths.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toon {
+    pub bands: u32,
+    // |normal . eye| below this is drawn as outline
+    pub outline: f32,
+    pub outline_color: Color,
+    // Phong highlight strength above which the highlight is drawn at full strength
+    pub highlight: f32
+}
+
+impl Toon {
+    pub fn new(bands: u32) -> Self {
+        Self {bands: bands.max(1), outline: 0.3, outline_color: Color::BLACK, highlight: 0.5}
+    }
+
+    pub fn lighting(&self, material: &Material, light: &PointLight, position: &Tuple, eyev: &Tuple, normalv: &Tuple) -> Color {
+        let normalv = if material.double_sided { face_forward(normalv, eyev) } else { *normalv };
+        if normalv.dot(eyev).abs() < self.outline {
+            return self.outline_color.clone();
+        }
+        let ambient = &material.color * light.intensity() * material.ambient;
+        let lightv = (light.position() - position).normalize();
+        let light_dot_normal = lightv.dot(&normalv);
+        if light_dot_normal <= 0.0 {
+            return ambient;
+        }
+        let bands = self.bands.max(1) as f32;
+        let band = (light_dot_normal * bands).ceil() / bands;
+        let mut result = ambient + &material.color * light.intensity() * (material.diffuse * band);
+        let reflect_dot_eye = eyev.dot(&-lightv.reflect(&normalv));
+        if reflect_dot_eye > 0.0 && reflect_dot_eye.powf(material.shininess) > self.highlight {
+            result += light.intensity() * material.specular;
+        }
+        return result;
+    }
+}
+
+// A material drawn with toon shading, for picking the style per object.
+// Path tracing still sees the underlying material.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToonMaterial {
+    pub material: Material,
+    pub toon: Toon
+}
+
+impl ToonMaterial {
+    pub fn new(material: Material, toon: Toon) -> Self {
+        Self {material, toon}
+    }
+}
+
+impl MaterialModel for ToonMaterial {
+    fn ambient(&self) -> Color {
+        self.material.ambient()
+    }
+
+    fn double_sided(&self) -> bool {
+        self.material.double_sided
+    }
+
+    fn eval(&self, wi: &Tuple, wo: &Tuple, normal: &Tuple) -> Color {
+        self.material.eval(wi, wo, normal)
+    }
+
+    fn sample(&self, wo: &Tuple, normal: &Tuple, rng: &mut Rng) -> Option<(Tuple, f32)> {
+        self.material.sample(wo, normal, rng)
+    }
+
+    fn pdf(&self, wi: &Tuple, wo: &Tuple, normal: &Tuple) -> f32 {
+        self.material.pdf(wi, wo, normal)
+    }
+
+    fn lighting(&self, light: &PointLight, position: &Tuple, eyev: &Tuple, normalv: &Tuple) -> Color {
+        self.toon.lighting(&self.material, light, position, eyev, normalv)
+    }
+}
+
+// Debug view of a world space normal: each component mapped from [-1, 1] to
+// [0, 1], so +x is red, +y green and +z blue. Normals that aren't unit
+// length (or have picked up a w) show up as out-of-range or odd colours.
+pub fn normal_to_color(normal: &Tuple) -> Color {
+    Color::new((normal.x() + 1.0) / 2.0, (normal.y() + 1.0) / 2.0, (normal.z() + 1.0) / 2.0)
+}
+
+// Debug view of texture coordinates: u in red, v in green, wrapped into [0, 1)
+// so tiling repeats are visible
+pub fn uv_to_color(u: f32, v: f32) -> Color {
+    Color::new(u - u.floor(), v - v.floor(), 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::mesh::TriangleMesh;
+    use super::super::tuple::Tuple;
+    use super::super::ray::Ray;
+    use super::super::intersection::Intersect;
+
+    #[test]
+    fn test_wireframe() {
+        let mut wireframe = Wireframe::new(Color::GREEN, 0.05);
+        assert_eq!(wireframe.apply(&Color::RED, Some(0.01)), Color::GREEN);
+        assert_eq!(wireframe.apply(&Color::RED, Some(0.5)), Color::RED);
+        assert_eq!(wireframe.apply(&Color::RED, None), Color::RED);
+        wireframe.overlay = false;
+        assert_eq!(wireframe.apply(&Color::RED, Some(0.5)), Color::BLACK);
+        assert_eq!(wireframe.apply(&Color::RED, Some(0.0)), Color::GREEN);
+    }
+
+    #[test]
+    fn test_wireframe_over_mesh_hits() {
+        let vertices = vec![Tuple::point(0.0, 0.0, 0.0), Tuple::point(1.0, 0.0, 0.0), Tuple::point(0.0, 1.0, 0.0)];
+        let mesh = TriangleMesh::new(1, vertices, vec![[0, 1, 2]]).unwrap();
+        let wireframe = Wireframe::new(Color::WHITE, 0.05);
+        let shade = |x: f32, y: f32| {
+            let r = Ray::new(Tuple::point(x, y, -1.0), Tuple::vector(0.0, 0.0, 1.0));
+            let xs = mesh.intersect(&r);
+            wireframe.apply(&Color::RED, mesh.edge_distance(xs.hit().unwrap()))
+        };
+        assert_eq!(shade(0.25, 0.25), Color::RED);
+        assert_eq!(shade(0.02, 0.5), Color::WHITE);
+        assert_eq!(shade(0.49, 0.49), Color::WHITE);
+    }
+
+    #[test]
+    fn test_toon_bands_and_outline() {
+        let toon = Toon::new(2);
+        let m = Material::new();
+        let light = PointLight::new(Color::WHITE, Tuple::point(0.0, 0.0, -10.0));
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        // head on: full band plus the highlight
+        let lit = toon.lighting(&m, &light, &position, &eyev, &Tuple::vector(0.0, 0.0, -1.0));
+        assert_eq!(lit, Color::new(1.9, 1.9, 1.9));
+        // every normal in a band gets the same colour
+        let tilt = |angle: f32| Tuple::vector(angle.sin(), 0.0, -angle.cos());
+        let a = toon.lighting(&m, &light, &position, &eyev, &tilt(1.1));
+        let b = toon.lighting(&m, &light, &position, &eyev, &tilt(1.2));
+        assert_eq!(a, b);
+        assert_eq!(a, Color::new(0.55, 0.55, 0.55));
+        // grazing the eye: outline
+        assert_eq!(toon.lighting(&m, &light, &position, &eyev, &tilt(1.4)), Color::BLACK);
+    }
+
+    #[test]
+    fn test_toon_material_is_a_material_model() {
+        let m: Box<dyn MaterialModel> = Box::new(ToonMaterial::new(Material::new(), Toon::new(3)));
+        let light = PointLight::new(Color::WHITE, Tuple::point(0.0, 0.0, -10.0));
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+        let lit = m.lighting(&light, &Tuple::point(0.0, 0.0, 0.0), &normal, &normal);
+        assert_eq!(lit, Color::new(1.9, 1.9, 1.9));
+        assert_eq!(m.eval(&normal, &normal, &normal), Material::new().eval(&normal, &normal, &normal));
+    }
+
+    #[test]
+    fn test_debug_colors() {
+        assert_eq!(normal_to_color(&Tuple::vector(0.0, 1.0, 0.0)), Color::new(0.5, 1.0, 0.5));
+        assert_eq!(normal_to_color(&Tuple::vector(-1.0, 0.0, 0.0)), Color::new(0.0, 0.5, 0.5));
+        assert_eq!(uv_to_color(0.25, 0.75), Color::new(0.25, 0.75, 0.0));
+        assert_eq!(uv_to_color(1.25, -0.25), Color::new(0.25, 0.75, 0.0));
+    }
+}
\ No newline at end of file
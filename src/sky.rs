@@ -0,0 +1,291 @@
+use std::f32::consts::PI;
+use super::tuple::Tuple;
+use super::color::Color;
+use super::light::PointLight;
+
+// Rayleigh optical depth of a clear atmosphere at roughly 680, 550 and 450nm
+const RAYLEIGH_DEPTH: [f32; 3] = [0.04, 0.097, 0.19];
+
+// Perez distribution coefficients (A to E), each linear in turbidity
+const LUMINANCE_COEFFS: [(f32, f32); 5] = [(0.1787, -1.4630), (-0.3554, 0.4275), (-0.0227, 5.3251),
+    (0.1206, -2.5771), (-0.0670, 0.3703)];
+const X_COEFFS: [(f32, f32); 5] = [(-0.0193, -0.2592), (-0.0665, 0.0008), (-0.0004, 0.2125),
+    (-0.0641, -0.8989), (-0.0033, 0.0452)];
+const Y_COEFFS: [(f32, f32); 5] = [(-0.0167, -0.2608), (-0.0950, 0.0092), (-0.0079, 0.2102),
+    (-0.0441, -1.6537), (-0.0109, 0.0529)];
+
+// A UTC date and time, used to place the sun for a given location
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SolarTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub utc_hours: f32
+}
+
+impl SolarTime {
+    pub fn new(year: i32, month: u32, day: u32, utc_hours: f32) -> Self {
+        Self {year, month, day, utc_hours}
+    }
+
+    pub fn day_of_year(&self) -> u32 {
+        const DAYS_BEFORE_MONTH: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+        let leap = (self.year % 4 == 0 && self.year % 100 != 0) || self.year % 400 == 0;
+        let month = self.month.clamp(1, 12);
+        let leap_day = if leap && month > 2 { 1 } else { 0 };
+        DAYS_BEFORE_MONTH[month as usize - 1] + leap_day + self.day
+    }
+}
+
+// Sun (azimuth, elevation) in radians for a latitude/longitude in degrees
+// (north and east positive), using the NOAA low precision solar position
+// equations. The azimuth is clockwise from north, which matches Sky when
+// the scene is laid out with +z pointing north and +x pointing east.
+pub fn sun_position(latitude: f32, longitude: f32, time: &SolarTime) -> (f32, f32) {
+    let fractional_year = 2.0 * PI / 365.0 * (time.day_of_year() as f32 - 1.0 + (time.utc_hours - 12.0) / 24.0);
+    let (g, g2, g3) = (fractional_year, 2.0 * fractional_year, 3.0 * fractional_year);
+    let equation_of_time = 229.18 * (0.000075 + 0.001868 * g.cos() - 0.032077 * g.sin()
+        - 0.014615 * g2.cos() - 0.040849 * g2.sin());
+    let declination = 0.006918 - 0.399912 * g.cos() + 0.070257 * g.sin() - 0.006758 * g2.cos()
+        + 0.000907 * g2.sin() - 0.002697 * g3.cos() + 0.00148 * g3.sin();
+    let true_solar_minutes = time.utc_hours * 60.0 + equation_of_time + 4.0 * longitude;
+    let hour_angle = (true_solar_minutes / 4.0 - 180.0).to_radians();
+    let lat = latitude.to_radians();
+    let cos_zenith = (lat.sin() * declination.sin() + lat.cos() * declination.cos() * hour_angle.cos()).clamp(-1.0, 1.0);
+    let elevation = PI / 2.0 - cos_zenith.acos();
+    let azimuth = hour_angle.sin().atan2(hour_angle.cos() * lat.sin() - declination.tan() * lat.cos()) + PI;
+    (azimuth.rem_euclid(2.0 * PI), elevation)
+}
+
+// Analytic daylight sky after Preetham, Shirley and Smits, "A Practical
+// Analytic Model for Daylight". The y axis points up; azimuth is measured
+// from +z towards +x, elevation from the horizon, both in radians.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Sky {
+    azimuth: f32,
+    elevation: f32,
+    turbidity: f32,
+    // scales the model's luminance (kcd/m^2) into the renderer's color range
+    pub exposure: f32
+}
+
+impl Sky {
+    pub fn new(azimuth: f32, elevation: f32, turbidity: f32) -> Self {
+        Self {azimuth, elevation, turbidity: turbidity.clamp(1.7, 10.0), exposure: 0.05}
+    }
+
+    // Sky for a place and time, e.g. for architectural shadow studies
+    pub fn at_location(latitude: f32, longitude: f32, time: &SolarTime, turbidity: f32) -> Self {
+        let (azimuth, elevation) = sun_position(latitude, longitude, time);
+        Self::new(azimuth, elevation, turbidity)
+    }
+
+    pub fn azimuth(&self) -> f32 {
+        self.azimuth
+    }
+
+    pub fn elevation(&self) -> f32 {
+        self.elevation
+    }
+
+    pub fn turbidity(&self) -> f32 {
+        self.turbidity
+    }
+
+    pub fn sun_direction(&self) -> Tuple {
+        let (sin_el, cos_el) = self.elevation.sin_cos();
+        Tuple::vector(cos_el * self.azimuth.sin(), sin_el, cos_el * self.azimuth.cos())
+    }
+
+    fn perez(coeffs: &[f32; 5], theta: f32, gamma: f32) -> f32 {
+        let [a, b, c, d, e] = *coeffs;
+        (1.0 + a * (b / theta.cos()).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+    }
+
+    fn coefficients(&self, table: &[(f32, f32); 5]) -> [f32; 5] {
+        let mut coeffs = [0.0; 5];
+        for (i, (slope, intercept)) in table.iter().enumerate() {
+            coeffs[i] = slope * self.turbidity + intercept;
+        }
+        coeffs
+    }
+
+    fn zenith_values(&self) -> (f32, f32, f32) {
+        let t = self.turbidity;
+        let theta = PI / 2.0 - self.elevation.max(0.0);
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let (t2, th2, th3) = (t * t, theta * theta, theta * theta * theta);
+        let x = t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * theta)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * theta + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * theta + 0.25886);
+        let y = t2 * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * theta)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * theta + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * theta + 0.26688);
+        (luminance, x, y)
+    }
+
+    // Sky radiance seen along direction. Directions below the horizon get
+    // the horizon color.
+    pub fn color(&self, direction: &Tuple) -> Color {
+        let dir = Tuple::vector(direction.x(), direction.y().max(0.001), direction.z()).normalize();
+        let sun = self.sun_direction();
+        let theta = dir.y().acos();
+        let gamma = dir.dot(&sun).clamp(-1.0, 1.0).acos();
+        let theta_sun = PI / 2.0 - self.elevation.max(0.0);
+        let (zenith_luminance, zenith_x, zenith_y) = self.zenith_values();
+        let value = |table: &[(f32, f32); 5], zenith: f32| {
+            let coeffs = self.coefficients(table);
+            zenith * Self::perez(&coeffs, theta, gamma) / Self::perez(&coeffs, 0.0, theta_sun)
+        };
+        let luminance = value(&LUMINANCE_COEFFS, zenith_luminance) * self.exposure;
+        let x = value(&X_COEFFS, zenith_x);
+        let y = value(&Y_COEFFS, zenith_y);
+        xyy_to_rgb(x, y, luminance)
+    }
+
+    // Color of direct sunlight after passing through the atmosphere, which
+    // reddens and dims as the sun approaches the horizon.
+    pub fn sun_color(&self) -> Color {
+        if self.elevation <= 0.0 {
+            return Color::black();
+        }
+        let zenith_deg = 90.0 - self.elevation.to_degrees();
+        // Kasten-Young relative air mass
+        let air_mass = 1.0 / (self.elevation.sin() + 0.50572 * (96.07995 - zenith_deg).powf(-1.6364));
+        let aerosol = 0.03 * (self.turbidity - 1.0);
+        let transmittance = |depth: f32| (-air_mass * (depth + aerosol)).exp();
+        Color::new(transmittance(RAYLEIGH_DEPTH[0]), transmittance(RAYLEIGH_DEPTH[1]),
+            transmittance(RAYLEIGH_DEPTH[2]))
+    }
+
+    // The sun as a point light far away along the sun direction, for use
+    // with Material::lighting
+    pub fn sun_light(&self, distance: f32) -> PointLight {
+        let position = Tuple::point(0.0, 0.0, 0.0) + self.sun_direction() * distance;
+        PointLight::new(self.sun_color(), position)
+    }
+}
+
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Color {
+    if y <= 0.0 {
+        return Color::black();
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    Color::new((3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brightness(c: &Color) -> f32 {
+        c.red() + c.green() + c.blue()
+    }
+
+    #[test]
+    fn test_sun_direction() {
+        let sky = Sky::new(0.0, PI / 2.0, 3.0);
+        assert_eq!(sky.sun_direction(), Tuple::vector(0.0, 1.0, 0.0));
+        let sky = Sky::new(PI / 2.0, 0.0, 3.0);
+        assert_eq!(sky.sun_direction(), Tuple::vector(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_day_of_year() {
+        assert_eq!(SolarTime::new(2023, 1, 1, 0.0).day_of_year(), 1);
+        assert_eq!(SolarTime::new(2023, 3, 1, 0.0).day_of_year(), 60);
+        assert_eq!(SolarTime::new(2024, 3, 1, 0.0).day_of_year(), 61);
+        assert_eq!(SolarTime::new(2000, 12, 31, 0.0).day_of_year(), 366);
+        assert_eq!(SolarTime::new(1900, 12, 31, 0.0).day_of_year(), 365);
+    }
+
+    #[test]
+    fn test_equinox_noon_at_equator() {
+        let (_, elevation) = sun_position(0.0, 0.0, &SolarTime::new(2023, 3, 20, 12.0));
+        assert!(elevation.to_degrees() > 87.0);
+    }
+
+    #[test]
+    fn test_solstice_noon_in_london() {
+        let (azimuth, elevation) = sun_position(51.5, 0.0, &SolarTime::new(2023, 6, 21, 12.0));
+        assert!((elevation.to_degrees() - 62.0).abs() < 0.5);
+        assert!((azimuth.to_degrees() - 180.0).abs() < 3.0);
+    }
+
+    #[test]
+    fn test_sun_rises_east_and_sets_west() {
+        let (morning_azimuth, morning_elevation) = sun_position(0.0, 0.0, &SolarTime::new(2023, 3, 20, 6.5));
+        assert!((morning_azimuth.to_degrees() - 90.0).abs() < 2.0);
+        assert!(morning_elevation > 0.0);
+        let (evening_azimuth, _) = sun_position(0.0, 0.0, &SolarTime::new(2023, 3, 20, 17.5));
+        assert!((evening_azimuth.to_degrees() - 270.0).abs() < 2.0);
+        let (_, night_elevation) = sun_position(0.0, 0.0, &SolarTime::new(2023, 3, 20, 0.0));
+        assert!(night_elevation < 0.0);
+    }
+
+    #[test]
+    fn test_longitude_shifts_local_noon() {
+        // local noon 90 degrees east of Greenwich is six hours earlier in UTC
+        let (_, elevation) = sun_position(0.0, 90.0, &SolarTime::new(2023, 3, 20, 6.0));
+        assert!(elevation.to_degrees() > 87.0);
+    }
+
+    #[test]
+    fn test_sky_at_location() {
+        let time = SolarTime::new(2023, 6, 21, 12.0);
+        let sky = Sky::at_location(51.5, 0.0, &time, 3.0);
+        let (azimuth, elevation) = sun_position(51.5, 0.0, &time);
+        assert_eq!(sky.azimuth(), azimuth);
+        assert_eq!(sky.elevation(), elevation);
+        // with +z north, the midday sun in the northern hemisphere is to the south
+        assert!(sky.sun_direction().z() < 0.0);
+    }
+
+    #[test]
+    fn test_turbidity_clamped() {
+        assert_eq!(Sky::new(0.0, 0.5, 0.0).turbidity(), 1.7);
+        assert_eq!(Sky::new(0.0, 0.5, 50.0).turbidity(), 10.0);
+    }
+
+    #[test]
+    fn test_clear_sky_is_blue_overhead() {
+        let sky = Sky::new(0.0, PI / 6.0, 2.5);
+        let zenith = sky.color(&Tuple::vector(0.0, 1.0, 0.0));
+        assert!(zenith.blue() > zenith.red());
+    }
+
+    #[test]
+    fn test_brighter_towards_sun() {
+        let sky = Sky::new(0.0, PI / 6.0, 3.0);
+        let towards = sky.color(&Tuple::vector(0.0, 0.6, 1.0));
+        let away = sky.color(&Tuple::vector(0.0, 0.6, -1.0));
+        assert!(brightness(&towards) > brightness(&away));
+    }
+
+    #[test]
+    fn test_below_horizon_uses_horizon_color() {
+        let sky = Sky::new(0.0, PI / 4.0, 3.0);
+        assert_eq!(sky.color(&Tuple::vector(1.0, -0.5, 0.0)), sky.color(&Tuple::vector(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_sun_reddens_near_horizon() {
+        let high = Sky::new(0.0, PI / 3.0, 3.0).sun_color();
+        let low = Sky::new(0.0, 0.05, 3.0).sun_color();
+        assert!(low.blue() / low.red() < high.blue() / high.red());
+        assert!(brightness(&low) < brightness(&high));
+        assert_eq!(Sky::new(0.0, -0.1, 3.0).sun_color(), Color::black());
+    }
+
+    #[test]
+    fn test_sun_light() {
+        let sky = Sky::new(0.0, PI / 2.0, 3.0);
+        let light = sky.sun_light(1000.0);
+        assert_eq!(*light.position(), Tuple::point(0.0, 1000.0, 0.0));
+        assert_eq!(*light.intensity(), sky.sun_color());
+    }
+}
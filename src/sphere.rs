@@ -1,64 +1,168 @@
 use super::ray::Ray;
 use super::tuple::Tuple;
-use super::matrix::Matrix;
+use super::matrix4::Matrix4;
 use super::material::Material;
-use super::intersection::{Intersect, Intersection, Intersections};
+use super::intersection::{Intersect, Intersection, Intersections, Shape};
+use super::sampler;
+use super::utils;
+use super::watchdog;
+use super::octree;
+use std::f32::consts::PI;
 
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Sphere {
-    transform: Matrix,
+    transform: Matrix4,
+    // transform at the end of the shutter interval, for motion blur
+    motion: Option<Matrix4>,
     pub material: Material,
     id: i32
 }
 
 impl Sphere {
     pub fn new(id: i32) -> Self {
-        Self{id, transform: Matrix::identity(4), material: Material::new()}
+        Self{id, transform: Matrix4::identity(), motion: None, material: Material::new()}
     }
 
-    pub fn set_transform(&mut self, transform: Matrix) {
+    pub fn set_transform(&mut self, transform: Matrix4) {
         self.transform = transform;
     }
 
+    pub fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
 
-}
+    // moves the sphere from its transform at time 0 to `end` at time 1
+    pub fn set_motion(&mut self, end: Matrix4) {
+        self.motion = Some(end);
+    }
 
-impl Intersect<Self> for Sphere {
-    fn intersect(&self, ray: &Ray) -> Intersections<Self> {
-        let transformed_ray = ray.transform(&self.transform.inverse().unwrap());
-        let sphere_to_ray = transformed_ray.origin() - &Tuple::point(0.0, 0.0, 0.0);
+    pub fn transform_at(&self, time: f32) -> Matrix4 {
+        match &self.motion {
+            Some(end) => self.transform.lerp(end, time),
+            None => self.transform
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    // per-instance value in [0, 1) for varying materials and textures
+    pub fn random_value(&self, seed: u64) -> f32 {
+        sampler::instance_random(self.id, seed)
+    }
+
+    // object space bounding box
+    pub fn bounds(&self) -> (Tuple, Tuple) {
+        (Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0))
+    }
+
+    // world space box around everywhere the sphere goes during the shutter
+    pub fn world_bounds(&self) -> (Tuple, Tuple) {
+        let (min, max) = self.bounds();
+        let (mut lo, mut hi) = octree::world_bounds(&self.transform, &min, &max);
+        let end = match &self.motion {
+            Some(end) => end,
+            None => return (lo, hi)
+        };
+        let mut grow = |a: Tuple, b: Tuple| {
+            lo = Tuple::point(lo.x().min(a.x()), lo.y().min(a.y()), lo.z().min(a.z()));
+            hi = Tuple::point(hi.x().max(b.x()), hi.y().max(b.y()), hi.z().max(b.z()));
+        };
+        let (end_lo, end_hi) = octree::world_bounds(end, &min, &max);
+        grow(end_lo, end_hi);
+        // when both ends decompose, the centre moves in a straight line and
+        // each axis's scale stays between its ends', so a ball the size of the
+        // largest one swept along the path covers any rotation; otherwise the
+        // blend stays inside the two end boxes
+        if let (Some(a), Some(b)) = (self.transform.decompose(), end.decompose()) {
+            let radius = a.2.iter().chain(b.2.iter()).fold(0.0f32, |r, s| r.max(s.abs()));
+            for m in [&self.transform, end] {
+                let centre = *m * Tuple::point(0.0, 0.0, 0.0);
+                grow(Tuple::point(centre.x() - radius, centre.y() - radius, centre.z() - radius),
+                    Tuple::point(centre.x() + radius, centre.y() + radius, centre.z() + radius));
+            }
+        }
+        return (lo, hi);
+    }
+
+    // spherical texture coordinates of a world space point on the sphere: u
+    // goes once around the y axis from -z through +x, v from the bottom
+    // pole (0) to the top (1)
+    pub fn uv_at(&self, point: &Tuple) -> (f32, f32) {
+        let p = self.transform.inverse().unwrap() * *point;
+        let theta = p.x().atan2(p.z());
+        let radius = (p.x() * p.x() + p.y() * p.y() + p.z() * p.z()).sqrt();
+        let phi = (p.y() / radius).clamp(-1.0, 1.0).acos();
+        let u = 1.0 - (theta / (2.0 * PI) + 0.5);
+        return (u - u.floor(), 1.0 - phi / PI);
+    }
+
+    // intersect_range for a ray already in object space
+    fn intersect_object(&self, transformed_ray: &Ray, t_min: f32, t_max: f32) -> Intersections<'_, Self> {
+        let sphere_to_ray = transformed_ray.origin() - Tuple::point(0.0, 0.0, 0.0);
         let a = transformed_ray.direction().dot(transformed_ray.direction());
         let b = 2.0 * transformed_ray.direction().dot(&sphere_to_ray);
         let c = sphere_to_ray.dot(&sphere_to_ray) - 1.0;
-        let discriminant = b * b - 4.0 * a * c;
-        if discriminant < 0.0 {
-            return Intersections::new_empty();
-        }
+        let (near, far) = match utils::solve_quadratic(a, b, c) {
+            Some(roots) => roots,
+            None => return Intersections::new_empty()
+        };
         let mut xs = Intersections::with_capacity(2);
-        let x1 = (-b - discriminant.sqrt()) / (2.0 * a);
-        let x2 = (-b + discriminant.sqrt()) / (2.0 * a);
-        if x1 < x2 {
-            xs.add_point(Intersection::new(self, x1));
-            xs.add_point(Intersection::new(self, x2));
-        } else {
-            xs.add_point(Intersection::new(self, x2));
-            xs.add_point(Intersection::new(self, x1));
+        for t in [near, far] {
+            if watchdog::check_t("sphere", self.id, t) && t >= t_min && t <= t_max {
+                xs.add_point(Intersection::new(self, t));
+            }
         }
         return xs;
     }
+}
+
+impl Shape for Sphere {
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl Intersect<Self> for Sphere {
+    // a motion that collapses the sphere mid-shutter has nothing to hit
+    fn intersect_range(&self, ray: &Ray, t_min: f32, t_max: f32) -> Intersections<'_, Self> {
+        match self.transform_at(ray.time()).inverse() {
+            Some(inverse) => self.intersect_object(&ray.transform(&inverse), t_min, t_max),
+            None => Intersections::new_empty()
+        }
+    }
+
+    // a moving sphere's transform depends on each ray's time, so only a
+    // static one can share the inverse across the batch
+    fn intersect_batch(&self, rays: &[Ray]) -> Vec<Intersections<'_, Self>> {
+        if self.motion.is_some() {
+            return rays.iter().map(|ray| self.intersect(ray)).collect();
+        }
+        match self.transform.inverse() {
+            Some(inverse) => rays.iter().map(|ray| self.intersect_object(&ray.transform(&inverse), f32::NEG_INFINITY, f32::INFINITY)).collect(),
+            // nothing to hit, as in intersect_range
+            None => rays.iter().map(|_| Intersections::new_empty()).collect()
+        }
+    }
 
     fn normal_at(&self, point: Tuple) -> Tuple {
-        let transform_inverse = self.transform.inverse().unwrap();
-        let object_point = (&transform_inverse * &point).unwrap();
+        self.normal_at_time(point, 0.0)
+    }
+
+    fn normal_at_time(&self, point: Tuple, time: f32) -> Tuple {
+        let transform_inverse = self.transform_at(time).inverse().or_else(|| self.transform.inverse()).unwrap();
+        let object_point = transform_inverse * point;
         let object_normal = object_point - Tuple::point(0.0, 0.0, 0.0);
-        let world_normal = (transform_inverse.transpose() * object_normal).unwrap();
+        let world_normal = transform_inverse.transpose() * object_normal;
         let world_normal_vector = Tuple::vector(world_normal.x(), world_normal.y(), world_normal.z());
         return world_normal_vector.normalize();
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod tests {
     use super::*;
     use super::super::transform::TransformBuilder;
@@ -113,17 +217,32 @@ mod tests {
         assert_eq!(intersections[1].point(), -4.0);
     }
 
+    #[test]
+    fn test_intersect_range() {
+        let s = Sphere::new(1);
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = s.intersect_range(&r, 0.0, f32::INFINITY);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].point(), 1.0);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(s.intersect_range(&r, 0.0, 3.0).len(), 0);
+        let xs = s.intersect_range(&r, 4.0, 6.0);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].point(), 4.0);
+        assert_eq!(xs[1].point(), 6.0);
+    }
+
     #[test]
     fn test_transform() {
         let s = Sphere::new(1);
-        assert_eq!(s.transform, Matrix::identity(4));
+        assert_eq!(s.transform, Matrix4::identity());
     }
 
     #[test]
     fn test_set_transform() {
         let mut s = Sphere::new(1);
-        let transform = TransformBuilder::new(4).translate(2.0, 3.0, 4.0).build();
-        s.set_transform(transform.clone());
+        let transform = TransformBuilder::new4().translate(2.0, 3.0, 4.0).build();
+        s.set_transform(transform);
         assert_eq!(s.transform, transform);
     }
 
@@ -131,7 +250,7 @@ mod tests {
     fn test_scaled_sphere_ray_intersection() {
         let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
         let mut s = Sphere::new(1);
-        s.set_transform(TransformBuilder::new(4).scale(2.0, 2.0, 2.0).build());
+        s.set_transform(TransformBuilder::new4().scale(2.0, 2.0, 2.0).build());
         let xs = s.intersect(&r);
         assert_eq!(xs.len(), 2);
         assert_eq!(xs[0].point(), 3.0);
@@ -142,7 +261,7 @@ mod tests {
     fn test_translated_sphere_ray_intersection() {
         let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
         let mut s = Sphere::new(1);
-        s.set_transform(TransformBuilder::new(4).translate(5.0, 0.0, 0.0).build());
+        s.set_transform(TransformBuilder::new4().translate(5.0, 0.0, 0.0).build());
         let xs = s.intersect(&r);
         assert_eq!(xs.len(), 0);
     }
@@ -180,7 +299,7 @@ mod tests {
     #[test]
     fn test_normal_translated_sphere() {
         let mut s = Sphere::new(1);
-        s.set_transform(TransformBuilder::new(4).translate(0.0, 1.0, 0.0).build());
+        s.set_transform(TransformBuilder::new4().translate(0.0, 1.0, 0.0).build());
         let n = s.normal_at(Tuple::point(0.0, 1.70711, -0.70711));
         assert_eq!(n, Tuple::vector(0.0, 0.70711, -0.70711));
     }
@@ -188,7 +307,7 @@ mod tests {
     #[test]
     fn test_normal_transformed_sphere() {
         let mut s = Sphere::new(1);
-        s.set_transform(TransformBuilder::new(4).rotate_z(std::f32::consts::PI / 5.0).scale(1.0, 0.5, 1.0).build());
+        s.set_transform(TransformBuilder::new4().rotate_z(std::f32::consts::PI / 5.0).scale(1.0, 0.5, 1.0).build());
         let v = 2.0f32.sqrt() / 2.0;
         let n = s.normal_at(Tuple::point(0.0, v, v));
         assert_eq!(n, Tuple::vector(0.0, 0.97014254, 0.24253564));
@@ -210,4 +329,102 @@ mod tests {
         assert_eq!(s.material, m);
     }
 
+    #[test]
+    fn test_random_value_per_instance() {
+        let a = Sphere::new(1);
+        assert_eq!(a.id(), 1);
+        assert_eq!(a.random_value(7), Sphere::new(1).random_value(7));
+        assert_ne!(a.random_value(7), Sphere::new(2).random_value(7));
+    }
+
+    #[test]
+    fn test_moving_sphere() {
+        let mut s = Sphere::new(1);
+        s.set_motion(TransformBuilder::new4().translate(4.0, 0.0, 0.0).build());
+        let r = Ray::new(Tuple::point(4.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(s.intersect(&r).len(), 0);
+        let r = r.with_time(1.0);
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].point(), 4.0);
+        // halfway through it is centred on x = 2
+        assert_eq!(s.transform_at(0.5), TransformBuilder::new4().translate(2.0, 0.0, 0.0).build());
+        assert_eq!(s.normal_at_time(Tuple::point(5.0, 0.0, 0.0), 1.0), Tuple::vector(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_rotating_sphere() {
+        let mut s = Sphere::new(1);
+        s.set_transform(Matrix4::scaling(2.0, 1.0, 1.0));
+        s.set_motion(Matrix4::rotation_y(PI) * Matrix4::scaling(2.0, 1.0, 1.0));
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0)).with_time(0.5);
+        // a quarter turn in, the long axis points along z
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert!(utils::is_equal(xs[0].point(), 3.0));
+        let (lo, hi) = s.world_bounds();
+        assert!(lo.z() <= -2.0 && hi.z() >= 2.0);
+    }
+
+    #[test]
+    fn test_moving_sphere_bounds() {
+        let mut s = Sphere::new(1);
+        assert_eq!(s.world_bounds(), s.bounds());
+        s.set_motion(Matrix4::translation(4.0, 0.0, 0.0));
+        let (lo, hi) = s.world_bounds();
+        assert_eq!((lo.x(), hi.x()), (-1.0, 5.0));
+    }
+
+    #[test]
+    fn test_intersect_batch_matches_single_rays() {
+        use super::super::ray::RayPacket;
+        let mut s = Sphere::new(1);
+        s.set_transform(Matrix4::scaling(2.0, 2.0, 2.0));
+        let rays: Vec<Ray> = (0..5).map(|i| Ray::new(Tuple::point(i as f32 * 1.5 - 3.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0))).collect();
+        let batch = s.intersect_batch(&rays);
+        assert_eq!(batch.len(), 5);
+        for (ray, xs) in rays.iter().zip(&batch) {
+            let single = s.intersect(ray);
+            assert_eq!(xs.len(), single.len());
+            for i in 0..xs.len() {
+                assert_eq!(xs[i].point(), single[i].point());
+            }
+        }
+        assert_eq!(batch[2][0].point(), 3.0);
+        let packet = s.intersect_packet(&RayPacket::from_rays(&rays));
+        assert_eq!(packet.iter().map(|xs| xs.len()).collect::<Vec<_>>(), vec![0, 2, 2, 2, 0]);
+        // moving spheres fall back to per-ray transforms
+        s.set_motion(Matrix4::translation(10.0, 0.0, 0.0));
+        let moving = s.intersect_batch(&[Ray::new(Tuple::point(10.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0)).with_time(1.0)]);
+        assert_eq!(moving[0].len(), 2);
+    }
+
+    #[test]
+    fn test_intersect_batch_singular_transform() {
+        let mut s = Sphere::new(1);
+        s.set_transform(Matrix4::scaling(0.0, 1.0, 1.0));
+        let rays: Vec<Ray> = (0..2).map(|i| Ray::new(Tuple::point(i as f32, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0))).collect();
+        let batch = s.intersect_batch(&rays);
+        assert_eq!(batch.len(), 2);
+        assert!(batch.iter().all(|xs| xs.is_empty()));
+        assert!(s.intersect(&rays[0]).is_empty());
+    }
+
+    #[test]
+    fn test_uv_at() {
+        let mut s = Sphere::new(1);
+        let check = |s: &Sphere, p: Tuple, u: f32, v: f32| {
+            let (pu, pv) = s.uv_at(&p);
+            assert!((pu - u).abs() < 1e-4 && (pv - v).abs() < 1e-4, "{:?} -> ({}, {})", p, pu, pv);
+        };
+        check(&s, Tuple::point(0.0, 0.0, -1.0), 0.0, 0.5);
+        check(&s, Tuple::point(1.0, 0.0, 0.0), 0.25, 0.5);
+        check(&s, Tuple::point(0.0, 0.0, 1.0), 0.5, 0.5);
+        check(&s, Tuple::point(-1.0, 0.0, 0.0), 0.75, 0.5);
+        check(&s, Tuple::point(0.0, 1.0, 0.0), 0.5, 1.0);
+        check(&s, Tuple::point(0.0, -1.0, 0.0), 0.5, 0.0);
+        // coordinates stay with the surface when the sphere moves
+        s.set_transform(Matrix4::translation(0.0, 0.0, 5.0));
+        check(&s, Tuple::point(1.0, 0.0, 5.0), 0.25, 0.5);
+    }
 }
\ No newline at end of file
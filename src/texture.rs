@@ -0,0 +1,538 @@
+use super::canvas::Canvas;
+use super::color::{Color, Encoding};
+use super::sampler::Rng;
+use super::tile::{self, TileOrder};
+use std::fs::File;
+use std::io::BufReader;
+
+// size of the chunks textures are generated in; every texel depends only on
+// the seed and its own coordinates, so chunks can be produced independently
+const CHUNK_SIZE: usize = 32;
+
+// a 2D procedural texture over u, v in [0, 1)
+pub trait Texture {
+    fn color_at(&self, u: f32, v: f32) -> Color;
+}
+
+// hash of a lattice point, in [0, 1)
+fn lattice(seed: u64, x: i64, y: i64) -> f32 {
+    let key = ((x as u64) << 32) ^ (y as u64 & 0xFFFF_FFFF);
+    let h = Rng::mix(seed ^ Rng::mix(key));
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+// value noise: random values on the integer lattice, smoothly interpolated
+pub fn value_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (ix, iy) = (x0 as i64, y0 as i64);
+    let (tx, ty) = (smoothstep(x - x0), smoothstep(y - y0));
+    let top = lattice(seed, ix, iy) * (1.0 - tx) + lattice(seed, ix + 1, iy) * tx;
+    let bottom = lattice(seed, ix, iy + 1) * (1.0 - tx) + lattice(seed, ix + 1, iy + 1) * tx;
+    top * (1.0 - ty) + bottom * ty
+}
+
+// fractal sum of value noise octaves, normalised back to [0, 1)
+pub fn fbm(seed: u64, x: f32, y: f32, octaves: u32) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    let mut total = 0.0;
+    for octave in 0..octaves {
+        sum += amplitude * value_noise(seed.wrapping_add(octave as u64), x * frequency, y * frequency);
+        total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    if total == 0.0 {
+        return 0.0;
+    }
+    sum / total
+}
+
+// fbm noise blended between two colours
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseTexture {
+    pub seed: u64,
+    pub scale: f32,
+    pub octaves: u32,
+    pub low: Color,
+    pub high: Color
+}
+
+impl NoiseTexture {
+    pub fn new(seed: u64, scale: f32) -> Self {
+        Self {seed, scale, octaves: 4, low: Color::black(), high: Color::WHITE}
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn color_at(&self, u: f32, v: f32) -> Color {
+        let n = fbm(self.seed, u * self.scale, v * self.scale, self.octaves);
+        &self.low * (1.0 - n) + &self.high * n
+    }
+}
+
+// oceans, land, mountains and polar ice from a height field
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanetTexture {
+    pub seed: u64,
+    pub sea_level: f32
+}
+
+impl PlanetTexture {
+    pub fn new(seed: u64) -> Self {
+        Self {seed, sea_level: 0.5}
+    }
+}
+
+impl Texture for PlanetTexture {
+    fn color_at(&self, u: f32, v: f32) -> Color {
+        let height = fbm(self.seed, u * 6.0, v * 3.0, 6);
+        let latitude = (v - 0.5).abs() * 2.0;
+        // noise on the ice line keeps the caps from being perfect circles
+        if latitude + 0.1 * (height - 0.5) > 0.85 {
+            return Color::new(0.95, 0.95, 1.0);
+        }
+        let land = height - self.sea_level;
+        if land < 0.0 {
+            let depth = (-land / self.sea_level).min(1.0);
+            return Color::new(0.05, 0.2, 0.6) * (1.0 - 0.6 * depth);
+        }
+        if land < 0.02 {
+            return Color::new(0.8, 0.75, 0.5);
+        }
+        if land < 0.15 {
+            return Color::new(0.2, 0.55, 0.2);
+        }
+        if land < 0.25 {
+            return Color::new(0.45, 0.4, 0.35);
+        }
+        Color::WHITE
+    }
+}
+
+// running-bond brickwork; sizes are in texture units and alternate rows are
+// offset by half a brick. Each brick's colour is varied by up to `variation`
+// using a hash of its (column, row) id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrickTexture {
+    pub seed: u64,
+    pub brick_width: f32,
+    pub brick_height: f32,
+    pub mortar: f32,
+    pub variation: f32,
+    pub brick_color: Color,
+    pub mortar_color: Color
+}
+
+impl BrickTexture {
+    pub fn new(seed: u64) -> Self {
+        Self {seed, brick_width: 0.25, brick_height: 0.0625, mortar: 0.005, variation: 0.2,
+            brick_color: Color::new(0.6, 0.25, 0.15), mortar_color: Color::new(0.75, 0.75, 0.7)}
+    }
+}
+
+impl Texture for BrickTexture {
+    fn color_at(&self, u: f32, v: f32) -> Color {
+        let row = (v / self.brick_height).floor();
+        let offset = if row as i64 % 2 == 0 { 0.0 } else { 0.5 * self.brick_width };
+        let col = ((u + offset) / self.brick_width).floor();
+        let (bu, bv) = (u + offset - col * self.brick_width, v - row * self.brick_height);
+        cell_color(self.seed, col as i64, row as i64, (bu, bv), (self.brick_width, self.brick_height),
+            self.mortar, self.variation, &self.brick_color, &self.mortar_color)
+    }
+}
+
+// square tiles on a grid separated by grout, varied per tile like bricks
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileTexture {
+    pub seed: u64,
+    pub tile_size: f32,
+    pub grout: f32,
+    pub variation: f32,
+    pub tile_color: Color,
+    pub grout_color: Color
+}
+
+impl TileTexture {
+    pub fn new(seed: u64) -> Self {
+        Self {seed, tile_size: 0.125, grout: 0.004, variation: 0.1,
+            tile_color: Color::new(0.9, 0.9, 0.85), grout_color: Color::new(0.3, 0.3, 0.3)}
+    }
+}
+
+impl Texture for TileTexture {
+    fn color_at(&self, u: f32, v: f32) -> Color {
+        let (col, row) = ((u / self.tile_size).floor(), (v / self.tile_size).floor());
+        let local = (u - col * self.tile_size, v - row * self.tile_size);
+        cell_color(self.seed, col as i64, row as i64, local, (self.tile_size, self.tile_size),
+            self.grout, self.variation, &self.tile_color, &self.grout_color)
+    }
+}
+
+// colour at `local` inside the cell (col, row) of the given size: the joint
+// colour within `joint` of any edge, otherwise the cell colour brightened or
+// darkened by the cell's hash
+#[allow(clippy::too_many_arguments)]
+fn cell_color(seed: u64, col: i64, row: i64, local: (f32, f32), size: (f32, f32),
+              joint: f32, variation: f32, cell: &Color, joint_color: &Color) -> Color {
+    let half = joint / 2.0;
+    if local.0 < half || local.1 < half || local.0 > size.0 - half || local.1 > size.1 - half {
+        return joint_color.clone();
+    }
+    let shade = 1.0 + variation * (2.0 * lattice(seed, col, row) - 1.0);
+    cell * shade
+}
+
+// Worley/cellular noise: one jittered feature point per unit cell. Returns
+// the distances to the nearest and second nearest feature points and the
+// cell owning the nearest one.
+pub fn voronoi(seed: u64, x: f32, y: f32) -> (f32, f32, (i64, i64)) {
+    let (cx, cy) = (x.floor() as i64, y.floor() as i64);
+    let mut f1 = f32::INFINITY;
+    let mut f2 = f32::INFINITY;
+    let mut nearest = (cx, cy);
+    for j in cy - 1..=cy + 1 {
+        for i in cx - 1..=cx + 1 {
+            let fx = i as f32 + lattice(seed, i, j);
+            let fy = j as f32 + lattice(seed.wrapping_add(1), i, j);
+            let d = ((fx - x).powi(2) + (fy - y).powi(2)).sqrt();
+            if d < f1 {
+                f2 = f1;
+                f1 = d;
+                nearest = (i, j);
+            } else if d < f2 {
+                f2 = d;
+            }
+        }
+    }
+    (f1, f2, nearest)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoronoiMode {
+    // gradient on the distance to the nearest feature point
+    Distance,
+    // `low` within `width` of a cell boundary, `high` elsewhere (cracks)
+    Border(f32),
+    // a flat colour per cell picked by hashing its id (scales, paving)
+    CellId
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoronoiTexture {
+    pub seed: u64,
+    pub scale: f32,
+    pub mode: VoronoiMode,
+    pub low: Color,
+    pub high: Color
+}
+
+impl VoronoiTexture {
+    pub fn new(seed: u64, scale: f32, mode: VoronoiMode) -> Self {
+        Self {seed, scale, mode, low: Color::black(), high: Color::WHITE}
+    }
+}
+
+impl Texture for VoronoiTexture {
+    fn color_at(&self, u: f32, v: f32) -> Color {
+        let (f1, f2, (i, j)) = voronoi(self.seed, u * self.scale, v * self.scale);
+        let t = match self.mode {
+            VoronoiMode::Distance => f1.min(1.0),
+            // f2 - f1 is zero on the boundary between two cells
+            VoronoiMode::Border(width) => if f2 - f1 < width { 0.0 } else { 1.0 },
+            VoronoiMode::CellId => lattice(self.seed.wrapping_add(2), i, j)
+        };
+        &self.low * (1.0 - t) + &self.high * t
+    }
+}
+
+// The pointy-top hexagon of circumradius 1 containing (x, y), as axial
+// coordinates (q, r), and how far (x, y) lies from the hexagon's centre
+// towards its edges: 0 at the centre, 1 on an edge. Hexagon (q, r) is
+// centred on (sqrt(3) * (q + r / 2), 1.5 * r).
+pub fn hex_cell(x: f32, y: f32) -> ((i64, i64), f32) {
+    let sqrt3 = 3f32.sqrt();
+    let q = sqrt3 / 3.0 * x - y / 3.0;
+    let r = 2.0 / 3.0 * y;
+    // round in cube coordinates (q, r, -q - r), fixing up the component
+    // that moved the most so the three still sum to zero
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    let (dx, dy) = (x - sqrt3 * (rq + rr / 2.0), y - 1.5 * rr);
+    // the edges face 0, 60 and 120 degrees and are sqrt(3) / 2 from the centre
+    let reach = dx.abs().max((dx + sqrt3 * dy).abs() / 2.0).max((dx - sqrt3 * dy).abs() / 2.0);
+    ((rq as i64, rr as i64), reach / (sqrt3 / 2.0))
+}
+
+// a hexagonal grid, shaded with the Voronoi modes: Distance grows from each
+// hexagon's centre to its edges, Border draws the edges and CellId gives
+// every hexagon its own colour
+#[derive(Debug, Clone, PartialEq)]
+pub struct HexTexture {
+    pub seed: u64,
+    pub scale: f32,
+    pub mode: VoronoiMode,
+    pub low: Color,
+    pub high: Color
+}
+
+impl HexTexture {
+    pub fn new(seed: u64, scale: f32, mode: VoronoiMode) -> Self {
+        Self {seed, scale, mode, low: Color::black(), high: Color::WHITE}
+    }
+}
+
+impl Texture for HexTexture {
+    fn color_at(&self, u: f32, v: f32) -> Color {
+        let ((q, r), reach) = hex_cell(u * self.scale, v * self.scale);
+        let t = match self.mode {
+            VoronoiMode::Distance => reach.min(1.0),
+            // matches Voronoi's f2 - f1, which is twice the distance to the edge
+            VoronoiMode::Border(width) => if 3f32.sqrt() * (1.0 - reach) < width { 0.0 } else { 1.0 },
+            VoronoiMode::CellId => lattice(self.seed.wrapping_add(2), q, r)
+        };
+        &self.low * (1.0 - t) + &self.high * t
+    }
+}
+
+// an image used as a texture. Colour images are normally stored sRGB
+// encoded and must be decoded to linear before shading, while data maps
+// (normals, roughness) are already linear and must be left alone, so the
+// source encoding is given when the texture is created.
+pub struct ImageTexture {
+    image: Canvas
+}
+
+impl ImageTexture {
+    pub fn new(image: &Canvas, encoding: Encoding) -> Self {
+        let mut linear = Canvas::new(image.width(), image.height());
+        for (x, y, c) in image.enumerate_pixels() {
+            linear.write_pixel(x, y, c.decode(encoding));
+        }
+        Self {image: linear}
+    }
+
+    pub fn from_ppm(path: String, encoding: Encoding) -> std::io::Result<Self> {
+        log::debug!("loading texture {}", path);
+        let image = Canvas::from_ppm(BufReader::new(File::open(path)?))?;
+        Ok(Self::new(&image, encoding))
+    }
+
+    // the decoded, linear texels
+    pub fn image(&self) -> &Canvas {
+        &self.image
+    }
+}
+
+impl Texture for ImageTexture {
+    // nearest texel, repeating outside [0, 1)
+    fn color_at(&self, u: f32, v: f32) -> Color {
+        let (width, height) = (self.image.width() as usize, self.image.height() as usize);
+        if width == 0 || height == 0 {
+            return Color::black();
+        }
+        let x = ((u.rem_euclid(1.0) * width as f32) as usize).min(width - 1);
+        let y = ((v.rem_euclid(1.0) * height as f32) as usize).min(height - 1);
+        self.image.pixel_at(x, y).unwrap().clone()
+    }
+}
+
+// samples `texture` at texel centres into a width x height canvas
+pub fn render_texture(texture: &dyn Texture, width: u16, height: u16) -> Canvas {
+    let mut canvas = Canvas::new(width, height);
+    for chunk in tile::tiles(width as usize, height as usize, CHUNK_SIZE, TileOrder::Scanline) {
+        for (x, y) in chunk.pixels() {
+            let u = (x as f32 + 0.5) / width as f32;
+            let v = (y as f32 + 0.5) / height as f32;
+            canvas.write_pixel(x, y, texture.color_at(u, v));
+        }
+    }
+    return canvas;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_noise_range_and_lattice() {
+        for i in 0..200 {
+            let x = i as f32 * 0.37 - 20.0;
+            let n = value_noise(3, x, x * 0.5);
+            assert!((0.0..1.0).contains(&n));
+        }
+        // on lattice points noise is exactly the lattice value
+        assert_eq!(value_noise(3, 2.0, -4.0), lattice(3, 2, -4));
+    }
+
+    #[test]
+    fn test_noise_is_continuous() {
+        let a = value_noise(9, 1.5, 2.5);
+        let b = value_noise(9, 1.5001, 2.5);
+        assert!((a - b).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_fbm() {
+        assert_eq!(fbm(1, 0.3, 0.7, 1), value_noise(1, 0.3, 0.7));
+        assert_eq!(fbm(1, 0.3, 0.7, 0), 0.0);
+        let n = fbm(1, 12.3, 4.5, 6);
+        assert!((0.0..1.0).contains(&n));
+    }
+
+    #[test]
+    fn test_render_is_deterministic() {
+        let planet = PlanetTexture::new(42);
+        let a = render_texture(&planet, 40, 20).gen_ppm_body();
+        assert_eq!(a, render_texture(&planet, 40, 20).gen_ppm_body());
+        assert_ne!(a, render_texture(&PlanetTexture::new(43), 40, 20).gen_ppm_body());
+    }
+
+    #[test]
+    fn test_render_matches_texture() {
+        let noise = NoiseTexture::new(5, 8.0);
+        let canvas = render_texture(&noise, 70, 40);
+        assert_eq!(canvas.pixel_at(65, 33), Some(&noise.color_at(65.5 / 70.0, 33.5 / 40.0)));
+    }
+
+    #[test]
+    fn test_planet_has_ice_caps() {
+        let planet = PlanetTexture::new(7);
+        assert_eq!(planet.color_at(0.3, 0.0), Color::new(0.95, 0.95, 1.0));
+        assert_eq!(planet.color_at(0.8, 0.999), Color::new(0.95, 0.95, 1.0));
+    }
+
+    #[test]
+    fn test_bricks() {
+        let mut bricks = BrickTexture::new(3);
+        bricks.variation = 0.0;
+        assert_eq!(bricks.color_at(0.1, 0.03), bricks.brick_color);
+        // the vertical joint between the first two bricks of the first row
+        assert_eq!(bricks.color_at(0.25, 0.03), bricks.mortar_color);
+        // the second row is offset by half a brick
+        assert_eq!(bricks.color_at(0.25, 0.09), bricks.brick_color);
+        assert_eq!(bricks.color_at(0.125, 0.09), bricks.mortar_color);
+        // horizontal joint between rows
+        assert_eq!(bricks.color_at(0.1, 0.0625), bricks.mortar_color);
+    }
+
+    #[test]
+    fn test_brick_variation_is_per_brick() {
+        let bricks = BrickTexture::new(11);
+        // the same brick is one colour throughout, neighbours differ
+        assert_eq!(bricks.color_at(0.05, 0.02), bricks.color_at(0.2, 0.05));
+        assert_ne!(bricks.color_at(0.05, 0.02), bricks.color_at(0.3, 0.02));
+    }
+
+    #[test]
+    fn test_tiles() {
+        let tiles = TileTexture::new(5);
+        assert_eq!(tiles.color_at(0.125, 0.06), tiles.grout_color);
+        assert_eq!(tiles.color_at(0.06, 0.249), tiles.grout_color);
+        assert_eq!(tiles.color_at(0.01, 0.01), tiles.color_at(0.12, 0.12));
+        let mut plain = tiles.clone();
+        plain.variation = 0.0;
+        assert_eq!(plain.color_at(0.3, 0.3), plain.tile_color);
+    }
+
+    #[test]
+    fn test_voronoi() {
+        let (f1, f2, cell) = voronoi(4, 3.2, -1.7);
+        assert!(f1 <= f2);
+        // the nearest feature point is in the cell or one of its neighbours
+        assert!((cell.0 - 3).abs() <= 1 && (cell.1 + 2).abs() <= 1);
+        // sitting on a feature point
+        let fx = 3.0 + lattice(4, 3, -2);
+        let fy = -2.0 + lattice(5, 3, -2);
+        let (f1, _, cell) = voronoi(4, fx, fy);
+        assert!(f1 < 1e-5);
+        assert_eq!(cell, (3, -2));
+    }
+
+    #[test]
+    fn test_voronoi_modes() {
+        let cells = VoronoiTexture::new(8, 4.0, VoronoiMode::CellId);
+        let (_, _, id) = voronoi(8, 2.0, 2.0);
+        let mut found = false;
+        // any other point of the same cell has the same colour
+        for k in 1..20 {
+            let (x, y) = (2.0 + k as f32 * 0.01, 2.0);
+            if voronoi(8, x, y).2 == id {
+                assert_eq!(cells.color_at(x / 4.0, y / 4.0), cells.color_at(0.5, 0.5));
+                found = true;
+            }
+        }
+        assert!(found);
+        let cracks = VoronoiTexture::new(8, 4.0, VoronoiMode::Border(0.05));
+        let body = render_texture(&cracks, 32, 32).gen_ppm_body();
+        // both cracks and cell interiors show up
+        assert!(body.contains("255") && body.split_whitespace().any(|v| v == "0"));
+    }
+
+    #[test]
+    fn test_hex_cell() {
+        let sqrt3 = 3f32.sqrt();
+        // centres map to their own hexagon
+        for &(q, r) in &[(0, 0), (2, -1), (-3, 4)] {
+            let (x, y) = (sqrt3 * (q as f32 + r as f32 / 2.0), 1.5 * r as f32);
+            let (cell, reach) = hex_cell(x, y);
+            assert_eq!(cell, (q, r));
+            assert!(reach < 1e-4);
+            // just inside a corner, straight up
+            assert_eq!(hex_cell(x, y + 0.99).0, (q, r));
+        }
+        // halfway to the right-hand neighbour is on the shared edge
+        let (cell, reach) = hex_cell(sqrt3 / 2.0 - 0.01, 0.0);
+        assert_eq!(cell, (0, 0));
+        assert!((reach - 1.0).abs() < 0.02);
+        assert_eq!(hex_cell(sqrt3 / 2.0 + 0.01, 0.0).0, (1, 0));
+    }
+
+    #[test]
+    fn test_hex_modes() {
+        let cells = HexTexture::new(3, 1.0, VoronoiMode::CellId);
+        assert_eq!(cells.color_at(0.1, 0.1), cells.color_at(-0.2, -0.3));
+        assert_ne!(cells.color_at(0.0, 0.0), cells.color_at(3f32.sqrt(), 0.0));
+        let border = HexTexture::new(3, 1.0, VoronoiMode::Border(0.1));
+        assert_eq!(border.color_at(0.0, 0.0), Color::WHITE);
+        assert_eq!(border.color_at(3f32.sqrt() / 2.0, 0.0), Color::black());
+        let distance = HexTexture::new(3, 1.0, VoronoiMode::Distance);
+        assert_eq!(distance.color_at(0.0, 0.0), Color::black());
+    }
+
+    #[test]
+    fn test_image_texture_color_space() {
+        let mut image = Canvas::new(2, 1);
+        image.write_pixel(0, 0, Color::new(0.5, 0.5, 0.5));
+        image.write_pixel(1, 0, Color::WHITE);
+        let srgb = ImageTexture::new(&image, Encoding::Srgb);
+        let linear = ImageTexture::new(&image, Encoding::Linear);
+        // sRGB 0.5 is about 0.214 in linear light, data maps are untouched
+        assert_eq!(srgb.color_at(0.25, 0.5), Color::new(0.214, 0.214, 0.214));
+        assert_eq!(linear.color_at(0.25, 0.5), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(srgb.color_at(0.75, 0.5), Color::WHITE);
+        // wraps around
+        assert_eq!(linear.color_at(1.25, -0.5), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_image_texture_from_ppm() {
+        let path = std::env::temp_dir().join("raytracer_test_image_texture.ppm");
+        std::fs::write(&path, "P3\n1 1\n255\n255 128 0\n").unwrap();
+        let texture = ImageTexture::from_ppm(path.to_str().unwrap().to_string(), Encoding::Srgb).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(texture.color_at(0.5, 0.5), Color::from_u8(255, 128, 0).decode(Encoding::Srgb));
+        assert!(ImageTexture::from_ppm("/nonexistent/texture.ppm".to_string(), Encoding::Srgb).is_err());
+    }
+}
\ No newline at end of file
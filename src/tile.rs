@@ -0,0 +1,159 @@
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileOrder {
+    // left to right, top to bottom
+    Scanline,
+    // outwards from the centre of the image, so the interesting part shows up first
+    Spiral,
+    // Z-curve, keeps consecutive tiles close together
+    Morton
+}
+
+// order of the pixels inside a tile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PixelOrder {
+    Scanline,
+    // Z-curve, so neighbouring primary rays stay close in screen space
+    Morton
+}
+
+// a rectangle of pixels; tiles on the right and bottom edges may be smaller
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize
+}
+
+impl Tile {
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> {
+        let (x, y, width, height) = (self.x, self.y, self.width, self.height);
+        (y..y + height).flat_map(move |py| (x..x + width).map(move |px| (px, py)))
+    }
+
+    pub fn pixels_in(&self, order: PixelOrder) -> Vec<(usize, usize)> {
+        let mut pixels: Vec<(usize, usize)> = self.pixels().collect();
+        if order == PixelOrder::Morton {
+            let (x, y) = (self.x, self.y);
+            pixels.sort_by_key(|&(px, py)| morton_code((px - x) as u32, (py - y) as u32));
+        }
+        return pixels;
+    }
+}
+
+// interleaves the bits of x and y, x taking the even bits
+pub fn morton_code(x: u32, y: u32) -> u64 {
+    fn spread(v: u32) -> u64 {
+        let mut v = v as u64;
+        v = (v | (v << 16)) & 0x0000_FFFF_0000_FFFF;
+        v = (v | (v << 8)) & 0x00FF_00FF_00FF_00FF;
+        v = (v | (v << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+        v = (v | (v << 2)) & 0x3333_3333_3333_3333;
+        v = (v | (v << 1)) & 0x5555_5555_5555_5555;
+        return v;
+    }
+    spread(x) | (spread(y) << 1)
+}
+
+// splits a width x height image into size x size tiles visited in `order`
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let columns = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+    let mut grid = Vec::with_capacity(columns * rows);
+    for ty in 0..rows {
+        for tx in 0..columns {
+            grid.push((tx, ty));
+        }
+    }
+    match order {
+        TileOrder::Scanline => {},
+        TileOrder::Morton => grid.sort_by_key(|&(tx, ty)| morton_code(tx as u32, ty as u32)),
+        TileOrder::Spiral => {
+            let cx = (columns as f32 - 1.0) / 2.0;
+            let cy = (rows as f32 - 1.0) / 2.0;
+            // ring by ring around the centre, each ring swept by angle
+            let key = |&(tx, ty): &(usize, usize)| {
+                let dx = tx as f32 - cx;
+                let dy = ty as f32 - cy;
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            grid.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap_or(Ordering::Equal));
+        }
+    }
+    return grid.into_iter().map(|(tx, ty)| {
+        let x = tx * size;
+        let y = ty * size;
+        Tile {x, y, width: size.min(width - x), height: size.min(height - y)}
+    }).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn covers_once(tiles: &[Tile], width: usize, height: usize) -> bool {
+        let mut seen = vec![0; width * height];
+        for tile in tiles {
+            for (x, y) in tile.pixels() {
+                seen[y * width + x] += 1;
+            }
+        }
+        seen.iter().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn test_scanline_tiles() {
+        let ts = tiles(70, 40, 32, TileOrder::Scanline);
+        assert_eq!(ts.len(), 6);
+        assert_eq!(ts[0], Tile {x: 0, y: 0, width: 32, height: 32});
+        assert_eq!(ts[2], Tile {x: 64, y: 0, width: 6, height: 32});
+        assert_eq!(ts[5], Tile {x: 64, y: 32, width: 6, height: 8});
+        assert!(covers_once(&ts, 70, 40));
+    }
+
+    #[test]
+    fn test_tile_pixels() {
+        let tile = Tile {x: 2, y: 1, width: 2, height: 2};
+        assert_eq!(tile.pixels().collect::<Vec<_>>(), vec![(2, 1), (3, 1), (2, 2), (3, 2)]);
+    }
+
+    #[test]
+    fn test_tile_pixels_in_morton_order() {
+        let tile = Tile {x: 4, y: 2, width: 3, height: 2};
+        assert_eq!(tile.pixels_in(PixelOrder::Scanline), tile.pixels().collect::<Vec<_>>());
+        assert_eq!(tile.pixels_in(PixelOrder::Morton), vec![(4, 2), (5, 2), (4, 3), (5, 3), (6, 2), (6, 3)]);
+    }
+
+    #[test]
+    fn test_morton_code() {
+        assert_eq!(morton_code(0, 0), 0);
+        assert_eq!(morton_code(1, 0), 1);
+        assert_eq!(morton_code(0, 1), 2);
+        assert_eq!(morton_code(1, 1), 3);
+        assert_eq!(morton_code(2, 0), 4);
+        assert_eq!(morton_code(3, 5), 0b100111);
+    }
+
+    #[test]
+    fn test_morton_tiles() {
+        let ts = tiles(4, 4, 1, TileOrder::Morton);
+        let origins: Vec<(usize, usize)> = ts.iter().take(8).map(|t| (t.x, t.y)).collect();
+        assert_eq!(origins, vec![(0, 0), (1, 0), (0, 1), (1, 1), (2, 0), (3, 0), (2, 1), (3, 1)]);
+        assert!(covers_once(&tiles(100, 37, 16, TileOrder::Morton), 100, 37));
+    }
+
+    #[test]
+    fn test_spiral_tiles() {
+        let ts = tiles(5, 5, 1, TileOrder::Spiral);
+        assert_eq!((ts[0].x, ts[0].y), (2, 2));
+        // the first ring around the centre comes before any corner of the image
+        for t in &ts[1..9] {
+            assert!(t.x >= 1 && t.x <= 3 && t.y >= 1 && t.y <= 3);
+        }
+        assert!(covers_once(&ts, 5, 5));
+        assert!(covers_once(&tiles(100, 37, 16, TileOrder::Spiral), 100, 37));
+    }
+}
\ No newline at end of file
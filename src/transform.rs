@@ -1,9 +1,13 @@
     use super::matrix::Matrix;
-    pub struct TransformBuilder {
-        matrix: Matrix
+    use super::matrix4::Matrix4;
+    // Chains transforms, each applied after the ones before it. new4() builds
+    // a Matrix4; new(size) is the original builder over a dynamic Matrix.
+    pub struct TransformBuilder<M = Matrix> {
+        matrix: M
     }
 
-    impl TransformBuilder {
+    impl TransformBuilder<Matrix> {
+        #[deprecated(note = "builds a dynamic Matrix; use TransformBuilder::new4() for a Matrix4")]
         pub fn new(size: usize) -> Self {
             Self{matrix: Matrix::identity(size)}
         }
@@ -44,6 +48,53 @@
 
     }
 
+    impl TransformBuilder<Matrix4> {
+        pub fn new4() -> Self {
+            Self{matrix: Matrix4::identity()}
+        }
+
+        pub fn rotate_x(self, angle: f32) -> Self {
+            let result = Matrix4::rotation_x(angle) * self.matrix;
+            Self {matrix: result}
+        }
+
+        pub fn rotate_y(self, angle: f32) -> Self {
+            let result = Matrix4::rotation_y(angle) * self.matrix;
+            Self {matrix: result}
+        }
+
+        pub fn rotate_z(self, angle: f32) -> Self {
+            let result = Matrix4::rotation_z(angle) * self.matrix;
+            Self {matrix: result}
+        }
+
+        pub fn scale(self, x: f32, y: f32, z: f32) -> Self {
+            let result = Matrix4::scaling(x, y, z) * self.matrix;
+            Self {matrix: result}
+        }
+
+        pub fn translate(self, x: f32, y: f32, z: f32) -> Self {
+            let result = Matrix4::translation(x, y, z) * self.matrix;
+            Self {matrix: result}
+        }
+
+        pub fn shear(self, x_y :f32, x_z: f32, y_x: f32, y_z: f32, z_x: f32, z_y: f32) -> Self {
+            let result = Matrix4::shearing(x_y, x_z, y_x, y_z, z_x, z_y) * self.matrix;
+            Self {matrix: result}
+        }
+
+        pub fn build(self) -> Matrix4 {
+            self.matrix
+        }
+
+    }
+
+    impl Default for TransformBuilder<Matrix4> {
+        fn default() -> Self {
+            Self::new4()
+        }
+    }
+
     #[cfg(test)]
     mod tests {
         use std::f32::consts::PI;
@@ -51,6 +102,7 @@
         use super::super::tuple::Tuple;
 
         #[test]
+        #[allow(deprecated)]
         fn test_chain_transformations() {
             let p = Tuple::point(1.0, 0.0, 1.0);
             let rotation = Matrix::rotation_x(PI/2.0);
@@ -65,5 +117,15 @@
             let transform = TransformBuilder::new(4).rotate_x(PI / 2.0).scale(5.0, 5.0, 5.0).translate(10.0, 5.0, 7.0).build();
             assert_eq!((transform * p).unwrap(), Tuple::point(15.0, 0.0, 7.0));
         }
+
+        #[test]
+        fn test_chain_transformations4() {
+            let p = Tuple::point(1.0, 0.0, 1.0);
+            let transform = TransformBuilder::new4().rotate_x(PI / 2.0).scale(5.0, 5.0, 5.0).translate(10.0, 5.0, 7.0).build();
+            assert_eq!(transform * p, Tuple::point(15.0, 0.0, 7.0));
+            #[allow(deprecated)]
+            let dynamic = TransformBuilder::new(4).rotate_x(PI / 2.0).scale(5.0, 5.0, 5.0).translate(10.0, 5.0, 7.0).build();
+            assert_eq!(Matrix::from(transform), dynamic);
+        }
     }
     
\ No newline at end of file
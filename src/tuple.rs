@@ -1,4 +1,4 @@
-use std::ops::{Add, Sub, Neg, Mul, Div};
+use std::ops::{Add, Sub, Neg, Mul, Div, AddAssign, SubAssign, MulAssign};
 use std::cmp::PartialEq;
 use super::utils;
 
@@ -42,8 +42,26 @@ impl Tuple {
         self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
     }
 
+    pub fn cross(&self, other: &Tuple) -> Self {
+        Self::vector(self.y * other.z - self.z * other.y, self.z * other.x - self.x * other.z, self.x * other.y - self.y * other.x)
+    }
+
+    // reflects the vector about `normal`, which must be normalized
     pub fn reflect(&self, normal: &Tuple) -> Self {
-        return self - &(normal * 2.0 * self.dot(normal));
+        return self - normal * 2.0 * self.dot(normal);
+    }
+
+    // linear interpolation, t = 0 gives self and t = 1 gives other
+    pub fn lerp(&self, other: &Tuple, t: f32) -> Self {
+        Self {x: self.x + (other.x - self.x) * t, y: self.y + (other.y - self.y) * t,
+            z: self.z + (other.z - self.z) * t, w: self.w + (other.w - self.w) * t}
+    }
+
+    // angle in radians between two vectors, in [0, pi]
+    pub fn angle_between(&self, other: &Tuple) -> f32 {
+        let cos = self.dot(other) / (self.magnitude() * other.magnitude());
+        // rounding can push cos just outside [-1, 1] for parallel vectors
+        return cos.clamp(-1.0, 1.0).acos();
     }
 
     pub fn x(&self) -> f32 {
@@ -78,143 +96,92 @@ impl PartialEq<&Tuple> for Tuple {
     }
 }
 
-impl<'a> PartialEq<Tuple> for &'a Tuple {
+impl PartialEq<Tuple> for &Tuple {
     fn eq(&self, other: &Tuple) -> bool {
         *self == other
     }
 }
 
-impl Add<Tuple> for Tuple {
-    type Output = Self;
-    fn add(self, _rhs: Tuple) -> Self {
-        Self {x: self.x + _rhs.x, y: self.y + _rhs.y, z: self.z + _rhs.z, w: self.w + _rhs.w}
+impl Add<&Tuple> for &Tuple {
+    type Output = Tuple;
+    fn add(self, _rhs: &Tuple) -> Tuple {
+        Tuple {x: self.x + _rhs.x, y: self.y + _rhs.y, z: self.z + _rhs.z, w: self.w + _rhs.w}
     }
 }
 
-impl Add<&Tuple> for Tuple {
-    type Output = Self;
-    fn add(self, _rhs: &Tuple) -> Self {
-        Self {x: self.x + _rhs.x, y: self.y + _rhs.y, z: self.z + _rhs.z, w: self.w + _rhs.w}
+impl Sub<&Tuple> for &Tuple {
+    type Output = Tuple;
+    fn sub(self, _rhs: &Tuple) -> Tuple {
+        Tuple {x: self.x - _rhs.x, y: self.y - _rhs.y, z: self.z - _rhs.z, w: self.w - _rhs.w}
     }
 }
 
-
-impl Sub<Tuple> for Tuple {
-    type Output = Self;
-    fn sub(self, _rhs: Self) -> Self {
-        Self{x: self.x - _rhs.x, y: self.y - _rhs.y, z: self.z - _rhs.z, w: self.w - _rhs.w}
+// cross product, kept for existing callers; prefer cross()
+impl Mul<&Tuple> for &Tuple {
+    type Output = Tuple;
+    fn mul(self, _rhs: &Tuple) -> Tuple {
+        Tuple {w: self.w, ..self.cross(_rhs)}
     }
 }
 
-impl<'a> Sub<&'a Tuple> for &'a Tuple {
+forward_binop!(impl Add, add for Tuple, Tuple => Tuple);
+forward_binop!(impl Sub, sub for Tuple, Tuple => Tuple);
+forward_binop!(impl Mul, mul for Tuple, Tuple => Tuple);
+
+impl Neg for &Tuple {
     type Output = Tuple;
-    fn sub(self, _rhs: Self) -> Tuple {
-        *self - *_rhs
+    fn neg(self) -> Tuple {
+        Tuple {x: -self.x, y: -self.y, z: -self.z, w: self.w}
     }
 }
 
 impl Neg for Tuple {
-    type Output = Self;
-    fn neg(self) -> Self {
-        Self{x: -self.x, y: -self.y, z: -self.z, w: self.w}
-    }
-
-}
-
-impl Mul<f32> for Tuple {
-    type Output = Self;
-    fn mul(self, _rhs: f32) -> Self {
-        Self{x:  self.x * _rhs, y: self.y * _rhs, z: self.z * _rhs, w: self.w}
+    type Output = Tuple;
+    fn neg(self) -> Tuple {
+        -&self
     }
 }
 
+// scaling leaves w alone so points stay points
 impl Mul<f32> for &Tuple {
     type Output = Tuple;
     fn mul(self, _rhs: f32) -> Tuple {
-        Tuple{x: self.x * _rhs, y: self.y * _rhs, z: self.z * _rhs, w: self.w}
-    }
-}
-
-impl Mul<Tuple> for Tuple {
-    type Output = Self;
-    fn mul(self, _rhs: Tuple) -> Self {
-        Self {x: self.y * _rhs.z - self.z * _rhs.y, y: self.z * _rhs.x - self.x * _rhs.z, z: self.x * _rhs.y - self.y * _rhs.x, w: self.w}
-    }
-}
-
-impl Div<f32> for Tuple {
-    type Output = Self;
-    fn div(self, _rhs: f32) -> Self {
-        Self{x: self.x / _rhs, y: self.y / _rhs, z: self.z / _rhs, w: self.w}
-    }
-}
-
-
-impl Div<i32> for Tuple {
-    type Output = Self;
-    fn div(self, _rhs: i32) -> Self {
-        let den = _rhs as f32;
-        Self{x: self.x / den, y: self.y / den, z: self.z / den, w: self.w}
-    }
-}
-
-impl Div<i64> for Tuple {
-    type Output = Self;
-    fn div(self, _rhs: i64) -> Self {
-        let den = _rhs as f32;
-        Self{x: self.x / den, y: self.y / den, z: self.z / den, w: self.w}
-    }
-}
-
-impl Div<i16> for Tuple {
-    type Output = Self;
-    fn div(self, _rhs: i16) -> Self {
-        let den = _rhs as f32;
-        Self{x: self.x / den, y: self.y / den, z: self.z / den, w: self.w}
+        Tuple {x: self.x * _rhs, y: self.y * _rhs, z: self.z * _rhs, w: self.w}
     }
 }
 
-impl Div<i8> for Tuple {
-    type Output = Self;
-    fn div(self, _rhs: i8) -> Self {
-        let den = _rhs as f32;
-        Self{x: self.x / den, y: self.y / den, z: self.z / den, w: self.w}
+impl Div<f32> for &Tuple {
+    type Output = Tuple;
+    fn div(self, _rhs: f32) -> Tuple {
+        Tuple {x: self.x / _rhs, y: self.y / _rhs, z: self.z / _rhs, w: self.w}
     }
 }
 
-impl Div<u32> for Tuple {
-    type Output = Self;
-    fn div(self, _rhs: u32) -> Self {
-        let den = _rhs as f32;
-        Self{x: self.x / den, y: self.y / den, z: self.z / den, w: self.w}
-    }
+forward_scalar_binop!(impl Mul, mul for Tuple);
+forward_scalar_binop!(impl Div, div for Tuple);
+scalar_mul_left!(Tuple);
+
+macro_rules! int_div {
+    ($($int:ty),*) => {
+        $(
+            impl Div<$int> for Tuple {
+                type Output = Tuple;
+                fn div(self, _rhs: $int) -> Tuple {
+                    self / _rhs as f32
+                }
+            }
+        )*
+    };
 }
 
-impl Div<u64> for Tuple {
-    type Output = Self;
-    fn div(self, _rhs: u64) -> Self {
-        let den = _rhs as f32;
-        Self{x: self.x / den, y: self.y / den, z: self.z / den, w: self.w}
-    }
-}
+int_div!(i8, i16, i32, i64, u8, u16, u32, u64);
 
-impl Div<u16> for Tuple {
-    type Output = Self;
-    fn div(self, _rhs: u16) -> Self {
-        let den = _rhs as f32;
-        Self{x: self.x / den, y: self.y / den, z: self.z / den, w: self.w}
-    }
-}
-
-impl Div<u8> for Tuple {
-    type Output = Self;
-    fn div(self, _rhs: u8) -> Self {
-        let den = _rhs as f32;
-        Self{x: self.x / den, y: self.y / den, z: self.z / den, w: self.w}
-    }
-}
+forward_assign_op!(impl AddAssign, add_assign for Tuple, Tuple, +);
+forward_assign_op!(impl SubAssign, sub_assign for Tuple, Tuple, -);
+forward_scalar_assign_op!(impl MulAssign, mul_assign for Tuple, *);
 
 #[cfg(test)]
+#[allow(clippy::unnecessary_cast, clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -436,4 +403,66 @@ mod tests {
         assert_eq!(v.reflect(&n), expected_reflection);
     }
 
+    #[test]
+    #[allow(clippy::op_ref)]
+    fn test_reference_operators() {
+        let a = Tuple::point(3.0, -2.0, 5.0);
+        let b = Tuple::vector(-2.0, 3.0, 1.0);
+        let sum = Tuple::point(1.0, 1.0, 6.0);
+        assert_eq!(&a + &b, sum);
+        assert_eq!(a + &b, sum);
+        assert_eq!(&a + b, sum);
+        assert_eq!(&a - &b, a - b);
+        assert_eq!(&a - b, a - b);
+        assert_eq!(-&b, Tuple::vector(2.0, -3.0, -1.0));
+        assert_eq!(&b * &a, b * a);
+        assert_eq!(&b / 2.0, Tuple::vector(-1.0, 1.5, 0.5));
+    }
+
+    #[test]
+    #[allow(clippy::op_ref)]
+    fn test_scalar_on_left() {
+        let v = Tuple::vector(1.0, -2.0, 3.0);
+        assert_eq!(2.0 * v, v * 2.0);
+        assert_eq!(2.0 * &v, Tuple::vector(2.0, -4.0, 6.0));
+    }
+
+    #[test]
+    fn test_assign_operators() {
+        let mut p = Tuple::point(1.0, 2.0, 3.0);
+        p += Tuple::vector(1.0, 1.0, 1.0);
+        assert_eq!(p, Tuple::point(2.0, 3.0, 4.0));
+        p -= &Tuple::vector(2.0, 0.0, 1.0);
+        assert_eq!(p, Tuple::point(0.0, 3.0, 3.0));
+        p *= 2.0;
+        assert_eq!(p, Tuple::point(0.0, 6.0, 6.0));
+    }
+
+    #[test]
+    fn test_cross() {
+        let v1 = Tuple::vector(1.0, 2.0, 3.0);
+        let v2 = Tuple::vector(2.0, 3.0, 4.0);
+        assert_eq!(v1.cross(&v2), Tuple::vector(-1.0, 2.0, -1.0));
+        assert_eq!(v2.cross(&v1), Tuple::vector(1.0, -2.0, 1.0));
+        assert_eq!(v1.cross(&v2), v1 * v2);
+    }
+
+    #[test]
+    fn test_lerp() {
+        let a = Tuple::point(0.0, 2.0, -4.0);
+        let b = Tuple::point(4.0, 2.0, 0.0);
+        assert_eq!(a.lerp(&b, 0.0), a);
+        assert_eq!(a.lerp(&b, 1.0), b);
+        assert_eq!(a.lerp(&b, 0.25), Tuple::point(1.0, 2.0, -3.0));
+    }
+
+    #[test]
+    fn test_angle_between() {
+        let x = Tuple::vector(1.0, 0.0, 0.0);
+        let y = Tuple::vector(0.0, 3.0, 0.0);
+        assert!(utils::is_equal(x.angle_between(&y), std::f32::consts::FRAC_PI_2));
+        assert!(utils::is_equal(x.angle_between(&(x * 2.0)), 0.0));
+        assert!(utils::is_equal(x.angle_between(&-x), std::f32::consts::PI));
+        assert!(utils::is_equal(x.angle_between(&Tuple::vector(1.0, 1.0, 0.0)), std::f32::consts::FRAC_PI_4));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,83 @@
+use super::camera::Camera;
+use super::canvas::Canvas;
+use super::color::Color;
+use super::matrix4::Matrix4;
+use super::ray::Ray;
+use super::tuple::Tuple;
+use std::f32::consts::PI;
+
+// camera transforms for `frames` evenly spaced views orbiting `pivot` at
+// `radius`, raised by `elevation` radians above the horizontal. Frame 0
+// looks down +z from the -z side, like the demo scenes, and the orbit
+// carries on towards +x. Elevations of +/- pi/2 look straight along the up
+// vector and have no defined orientation.
+pub fn turntable_views(pivot: &Tuple, radius: f32, elevation: f32, frames: usize) -> Vec<Matrix4> {
+    let up = Tuple::vector(0.0, 1.0, 0.0);
+    let mut views = Vec::with_capacity(frames);
+    for frame in 0..frames {
+        let angle = 2.0 * PI * frame as f32 / frames as f32;
+        let ground = radius * elevation.cos();
+        let eye = pivot + Tuple::vector(ground * angle.sin(), radius * elevation.sin(), -ground * angle.cos());
+        views.push(Matrix4::view_transform(&eye, pivot, &up));
+    }
+    return views;
+}
+
+// renders every turntable view with the camera's other settings, handing
+// each finished frame to `on_frame` (e.g. to save it with frame_path)
+pub fn render_turntable<F, P>(camera: &Camera, pivot: &Tuple, radius: f32, elevation: f32, frames: usize,
+                              color_at: F, mut on_frame: P)
+where F: Fn(&Ray) -> Color, P: FnMut(usize, &Canvas) {
+    let mut camera = camera.clone();
+    for (frame, view) in turntable_views(pivot, radius, elevation, frames).into_iter().enumerate() {
+        camera.set_transform(view);
+        let image = camera.render(&color_at);
+        on_frame(frame, &image);
+    }
+}
+
+// "frame_0007.png" style names that sort in frame order
+pub fn frame_path(prefix: &str, frame: usize, extension: &str) -> String {
+    format!("{}_{:04}.{}", prefix, frame, extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_first_view_matches_demo_camera() {
+        let views = turntable_views(&Tuple::point(0.0, 0.0, 0.0), 5.0, 0.0, 4);
+        assert_eq!(views.len(), 4);
+        assert_eq!(views[0], Matrix4::view_transform(&Tuple::point(0.0, 0.0, -5.0),
+            &Tuple::point(0.0, 0.0, 0.0), &Tuple::vector(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_views_orbit_the_pivot() {
+        let pivot = Tuple::point(1.0, 2.0, 3.0);
+        let elevation = 0.5;
+        for view in turntable_views(&pivot, 4.0, elevation, 8) {
+            // the pivot is straight ahead at the orbit radius
+            let p = view * pivot;
+            assert_eq!(p, Tuple::point(0.0, 0.0, -4.0));
+            let eye = view.inverse().unwrap() * Tuple::point(0.0, 0.0, 0.0);
+            assert!((eye.y() - (2.0 + 4.0 * elevation.sin())).abs() < 1e-4);
+        }
+        // a quarter of the way round the eye is on the +x side
+        let eye = turntable_views(&pivot, 4.0, 0.0, 4)[1].inverse().unwrap() * Tuple::point(0.0, 0.0, 0.0);
+        assert_eq!(eye, Tuple::point(5.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn test_render_turntable() {
+        let camera = Camera::new(4, 3, FRAC_PI_2);
+        let mut frames = Vec::new();
+        render_turntable(&camera, &Tuple::point(0.0, 0.0, 0.0), 5.0, 0.2, 3, |_| Color::WHITE, |frame, image| {
+            frames.push((frame, image.width(), image.height()));
+        });
+        assert_eq!(frames, vec![(0, 4, 3), (1, 4, 3), (2, 4, 3)]);
+        assert_eq!(frame_path("frame", 7, "png"), "frame_0007.png");
+    }
+}
\ No newline at end of file
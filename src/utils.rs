@@ -3,4 +3,56 @@ pub fn is_equal(v1: f32, v2: f32) -> bool {
         return true;
     }
     return false;
+}
+
+// real roots of a*x^2 + b*x + c = 0 in ascending order (equal for a double
+// root or a linear equation). The textbook (-b +/- sqrt(d)) / 2a loses all
+// precision in the root where b and sqrt(d) nearly cancel, which shows up as
+// speckles on large or distant spheres, so that root is found as c / q instead.
+pub fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    let (a, b, c) = (a as f64, b as f64, c as f64);
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let x = (-c / b) as f32;
+        return Some((x, x));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        // b and c are both zero
+        return Some((0.0, 0.0));
+    }
+    let (x1, x2) = ((q / a) as f32, (c / q) as f32);
+    if x1 <= x2 {
+        return Some((x1, x2));
+    }
+    Some((x2, x1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve_quadratic() {
+        assert_eq!(solve_quadratic(1.0, -3.0, 2.0), Some((1.0, 2.0)));
+        assert_eq!(solve_quadratic(1.0, 2.0, 1.0), Some((-1.0, -1.0)));
+        assert_eq!(solve_quadratic(1.0, 0.0, 1.0), None);
+        assert_eq!(solve_quadratic(1.0, 0.0, 0.0), Some((0.0, 0.0)));
+        assert_eq!(solve_quadratic(0.0, 2.0, -4.0), Some((2.0, 2.0)));
+        assert_eq!(solve_quadratic(0.0, 0.0, 1.0), None);
+    }
+
+    #[test]
+    fn test_solve_quadratic_without_cancellation() {
+        // roots 1e-4 and 1e4; the naive formula returns 0 for the small one
+        let (small, large) = solve_quadratic(1.0, -10000.0001, 1.0).unwrap();
+        assert!((small - 1e-4).abs() / 1e-4 < 1e-3);
+        assert!((large - 1e4).abs() / 1e4 < 1e-6);
+    }
 }
\ No newline at end of file
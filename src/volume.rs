@@ -0,0 +1,650 @@
+use std::io::{BufRead, Error, ErrorKind, Read, Result};
+use super::ray::Ray;
+use super::tuple::Tuple;
+use super::color::Color;
+use super::matrix4::Matrix4;
+use super::voxel;
+use super::sphere::Sphere;
+use super::intersection::Intersect;
+use super::light::PointLight;
+use super::sampler::Rng;
+
+// Maps a density sample to how strongly it absorbs light and what color it
+// emits. Colors are interpolated linearly between the (density, color) stops.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TransferFunction {
+    pub absorption: f32,
+    stops: Vec<(f32, Color)>
+}
+
+impl TransferFunction {
+    pub fn new(absorption: f32, low: Color, high: Color) -> Self {
+        Self {absorption, stops: vec![(0.0, low), (1.0, high)]}
+    }
+
+    pub fn add_stop(&mut self, density: f32, color: Color) {
+        let pos = self.stops.iter().position(|(d, _)| *d > density).unwrap_or(self.stops.len());
+        self.stops.insert(pos, (density, color));
+    }
+
+    pub fn color_at(&self, density: f32) -> Color {
+        let first = &self.stops[0];
+        if density <= first.0 {
+            return first.1.clone();
+        }
+        for pair in self.stops.windows(2) {
+            let (d0, c0) = &pair[0];
+            let (d1, c1) = &pair[1];
+            if density <= *d1 {
+                let frac = (density - d0) / (d1 - d0);
+                return c0 * (1.0 - frac) + c1 * frac;
+            }
+        }
+        return self.stops[self.stops.len() - 1].1.clone();
+    }
+}
+
+// edge length, in voxels, of the bricks used by bricked grids
+const BRICK_SIZE: usize = 8;
+// largest dense grid, so a corrupt file header can't demand gigabytes
+const MAX_VOXELS: usize = 1 << 28;
+
+// None for empty grids and ones too large to store densely
+fn voxel_count(width: usize, height: usize, depth: usize) -> Option<usize> {
+    let count = width.checked_mul(height)?.checked_mul(depth)?;
+    if count == 0 || count > MAX_VOXELS {
+        return None;
+    }
+    Some(count)
+}
+
+fn invalid_size() -> Error {
+    Error::new(ErrorKind::InvalidData, "density grid dimensions are zero or too large")
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum DensityStorage {
+    Dense(Vec<f32>),
+    // one slot per brick, only allocated once a voxel in it becomes non-zero
+    Bricked(Vec<Option<Vec<f32>>>)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct VolumeSample {
+    pub color: Color,
+    pub transmittance: f32
+}
+
+// Scalar densities sampled at voxel centers over the box from (0, 0, 0) to
+// (width, height, depth) in object space, rendered by ray marching.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DensityGrid {
+    transform: Matrix4,
+    dims: (usize, usize, usize),
+    densities: DensityStorage,
+    pub transfer: TransferFunction
+}
+
+impl DensityGrid {
+    pub fn new(width: usize, height: usize, depth: usize) -> Self {
+        let count = voxel_count(width, height, depth).expect("invalid density grid dimensions");
+        Self::from_densities(width, height, depth, vec![0.0; count]).unwrap()
+    }
+
+    // None unless there is one density per voxel of a non-empty grid
+    pub fn from_densities(width: usize, height: usize, depth: usize, densities: Vec<f32>) -> Option<Self> {
+        if voxel_count(width, height, depth)? != densities.len() {
+            return None;
+        }
+        Some(Self {transform: Matrix4::identity(), dims: (width, height, depth),
+            densities: DensityStorage::Dense(densities),
+            transfer: TransferFunction::new(1.0, Color::black(), Color::WHITE)})
+    }
+
+    // Two-level storage for large, mostly empty volumes: memory grows with the
+    // occupied bricks and marching skips over the empty ones.
+    pub fn new_bricked(width: usize, height: usize, depth: usize) -> Self {
+        assert!(width > 0 && height > 0 && depth > 0, "invalid density grid dimensions");
+        let mut grid = Self::new(1, 1, 1);
+        grid.dims = (width, height, depth);
+        let (bx, by, bz) = grid.brick_dims();
+        grid.densities = DensityStorage::Bricked(vec![None; bx * by * bz]);
+        grid
+    }
+
+    pub fn to_bricked(&self) -> Self {
+        let (width, height, depth) = self.dims;
+        let mut bricked = Self::new_bricked(width, height, depth);
+        bricked.transform = self.transform;
+        bricked.transfer = self.transfer.clone();
+        for z in 0..depth {
+            for y in 0..height {
+                for x in 0..width {
+                    bricked.set(x, y, z, self.get(x, y, z));
+                }
+            }
+        }
+        bricked
+    }
+
+    fn brick_dims(&self) -> (usize, usize, usize) {
+        let (width, height, depth) = self.dims;
+        let bricks = |n: usize| n.div_ceil(BRICK_SIZE);
+        (bricks(width), bricks(height), bricks(depth))
+    }
+
+    // brick index and the voxel offset inside that brick
+    fn brick_index(&self, x: usize, y: usize, z: usize) -> (usize, usize) {
+        let (bx, by, _) = self.brick_dims();
+        let brick = ((z / BRICK_SIZE) * by + y / BRICK_SIZE) * bx + x / BRICK_SIZE;
+        let offset = ((z % BRICK_SIZE) * BRICK_SIZE + y % BRICK_SIZE) * BRICK_SIZE + x % BRICK_SIZE;
+        (brick, offset)
+    }
+
+    pub fn allocated_bricks(&self) -> usize {
+        match &self.densities {
+            DensityStorage::Dense(_) => 0,
+            DensityStorage::Bricked(bricks) => bricks.iter().filter(|b| b.is_some()).count()
+        }
+    }
+
+    // Headerless 8-bit data in x-fastest order, mapped to densities in [0, 1]
+    pub fn from_raw<R: Read>(mut reader: R, width: usize, height: usize, depth: usize) -> Result<Self> {
+        let count = voxel_count(width, height, depth).ok_or_else(invalid_size)?;
+        let mut bytes = vec![0u8; count];
+        reader.read_exact(&mut bytes)?;
+        log::debug!("read {}x{}x{} 8-bit density grid", width, height, depth);
+        let densities = bytes.iter().map(|b| *b as f32 / 255.0).collect();
+        Ok(Self::from_densities(width, height, depth, densities).unwrap())
+    }
+
+    // Supports the subset of NRRD that volume tools commonly write: three
+    // dimensional uchar or float data with raw encoding.
+    pub fn from_nrrd<R: BufRead>(mut reader: R) -> Result<Self> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with("NRRD") {
+            return Err(Error::new(ErrorKind::InvalidData, "missing NRRD magic"));
+        }
+        let mut sizes = Vec::new();
+        let mut kind = String::new();
+        let mut big_endian = false;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "NRRD header is not terminated"));
+            }
+            let field = line.trim_end();
+            if field.is_empty() {
+                break;
+            }
+            if field.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match field.split_once(':') {
+                None => continue,
+                Some((k, v)) => (k.trim(), v.trim_start_matches('=').trim())
+            };
+            match key {
+                "sizes" => {
+                    sizes = value.split_whitespace().map(|s| s.parse::<usize>()).collect::<std::result::Result<_, _>>()
+                        .map_err(|_| Error::new(ErrorKind::InvalidData, "invalid NRRD sizes"))?;
+                },
+                "type" => kind = value.to_string(),
+                "endian" => big_endian = value == "big",
+                "encoding" if value != "raw" => {
+                    return Err(Error::new(ErrorKind::InvalidData, "only raw NRRD encoding is supported"));
+                },
+                "dimension" if value != "3" => {
+                    return Err(Error::new(ErrorKind::InvalidData, "only 3D NRRD volumes are supported"));
+                },
+                _ => {}
+            }
+        }
+        if sizes.len() != 3 {
+            return Err(Error::new(ErrorKind::InvalidData, "NRRD sizes must have three entries"));
+        }
+        let (width, height, depth) = (sizes[0], sizes[1], sizes[2]);
+        log::debug!("NRRD header: {}x{}x{} {}, {} endian", width, height, depth, kind, if big_endian { "big" } else { "little" });
+        match kind.as_str() {
+            "uchar" | "unsigned char" | "uint8" | "uint8_t" => Self::from_raw(reader, width, height, depth),
+            "float" => {
+                let count = voxel_count(width, height, depth).ok_or_else(invalid_size)?;
+                let mut bytes = vec![0u8; count * 4];
+                reader.read_exact(&mut bytes)?;
+                let densities = bytes.chunks(4).map(|b| {
+                    let word = [b[0], b[1], b[2], b[3]];
+                    if big_endian { f32::from_be_bytes(word) } else { f32::from_le_bytes(word) }
+                }).collect();
+                Ok(Self::from_densities(width, height, depth, densities).unwrap())
+            },
+            _ => Err(Error::new(ErrorKind::InvalidData, format!("unsupported NRRD type '{}'", kind)))
+        }
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    pub fn dims(&self) -> (usize, usize, usize) {
+        self.dims
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, density: f32) {
+        let (width, height, depth) = self.dims;
+        if x >= width || y >= height || z >= depth {
+            return;
+        }
+        let (brick, offset) = self.brick_index(x, y, z);
+        match &mut self.densities {
+            DensityStorage::Dense(densities) => densities[(z * height + y) * width + x] = density,
+            DensityStorage::Bricked(bricks) => {
+                if bricks[brick].is_none() && density == 0.0 {
+                    return;
+                }
+                let cells = bricks[brick].get_or_insert_with(|| vec![0.0; BRICK_SIZE * BRICK_SIZE * BRICK_SIZE]);
+                cells[offset] = density;
+            }
+        }
+    }
+
+    // outside the grid is empty space, so out of range reads are zero just as
+    // out of range writes are dropped
+    pub fn get(&self, x: usize, y: usize, z: usize) -> f32 {
+        let (width, height, depth) = self.dims;
+        if x >= width || y >= height || z >= depth {
+            return 0.0;
+        }
+        match &self.densities {
+            DensityStorage::Dense(densities) => densities[(z * height + y) * width + x],
+            DensityStorage::Bricked(bricks) => {
+                let (brick, offset) = self.brick_index(x, y, z);
+                match &bricks[brick] {
+                    None => 0.0,
+                    Some(cells) => cells[offset]
+                }
+            }
+        }
+    }
+
+    // If p lies in an unallocated brick, far enough from its faces that the
+    // trilinear lookup cannot reach a neighbouring brick, returns the t at
+    // which the ray leaves that empty region.
+    fn empty_space_exit(&self, ray: &Ray, p: &Tuple) -> Option<f32> {
+        let bricks = match &self.densities {
+            DensityStorage::Dense(_) => return None,
+            DensityStorage::Bricked(bricks) => bricks
+        };
+        let coords = [p.x(), p.y(), p.z()];
+        let n = [self.dims.0, self.dims.1, self.dims.2];
+        let mut voxel = [0usize; 3];
+        let mut lo = [0.0f32; 3];
+        let mut hi = [0.0f32; 3];
+        for i in 0..3 {
+            voxel[i] = (coords[i].max(0.0) as usize).min(n[i] - 1);
+            let start = voxel[i] / BRICK_SIZE * BRICK_SIZE;
+            let end = start + BRICK_SIZE;
+            lo[i] = if start == 0 { 0.0 } else { (start + 1) as f32 };
+            hi[i] = if end >= n[i] { n[i] as f32 } else { (end - 1) as f32 };
+            if coords[i] < lo[i] || coords[i] > hi[i] {
+                return None;
+            }
+        }
+        let (brick, _) = self.brick_index(voxel[0], voxel[1], voxel[2]);
+        if bricks[brick].is_some() {
+            return None;
+        }
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let o = [origin.x() - lo[0], origin.y() - lo[1], origin.z() - lo[2]];
+        let d = [direction.x(), direction.y(), direction.z()];
+        let extent = [hi[0] - lo[0], hi[1] - lo[1], hi[2] - lo[2]];
+        voxel::clip_to_grid(&o, &d, &extent).map(|(_, t_exit)| t_exit)
+    }
+
+    // trilinear interpolation between the surrounding voxel centers
+    pub fn density_at(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (width, height, depth) = self.dims;
+        let lookup = |v: f32, n: usize| {
+            let v = (v - 0.5).clamp(0.0, (n - 1) as f32);
+            let i0 = v.floor() as usize;
+            let i1 = (i0 + 1).min(n - 1);
+            (i0, i1, v - i0 as f32)
+        };
+        let (x0, x1, fx) = lookup(x, width);
+        let (y0, y1, fy) = lookup(y, height);
+        let (z0, z1, fz) = lookup(z, depth);
+        let lerp = |a: f32, b: f32, f: f32| a + (b - a) * f;
+        let c00 = lerp(self.get(x0, y0, z0), self.get(x1, y0, z0), fx);
+        let c10 = lerp(self.get(x0, y1, z0), self.get(x1, y1, z0), fx);
+        let c01 = lerp(self.get(x0, y0, z1), self.get(x1, y0, z1), fx);
+        let c11 = lerp(self.get(x0, y1, z1), self.get(x1, y1, z1), fx);
+        lerp(lerp(c00, c10, fy), lerp(c01, c11, fy), fz)
+    }
+
+    // Front-to-back emission/absorption integration along the ray. step is
+    // the marching distance in object space units.
+    pub fn march(&self, ray: &Ray, step: f32) -> VolumeSample {
+        let transformed_ray = ray.transform(&self.transform.inverse().unwrap());
+        let origin = transformed_ray.origin();
+        let direction = transformed_ray.direction();
+        let o = [origin.x(), origin.y(), origin.z()];
+        let d = [direction.x(), direction.y(), direction.z()];
+        let n = [self.dims.0 as f32, self.dims.1 as f32, self.dims.2 as f32];
+        let mut sample = VolumeSample {color: Color::black(), transmittance: 1.0};
+        let (t_enter, t_exit) = match voxel::clip_to_grid(&o, &d, &n) {
+            Some(range) if step > 0.0 => range,
+            _ => return sample
+        };
+        let dt = step / direction.magnitude();
+        let t_start = t_enter.max(0.0) + dt / 2.0;
+        let mut i = 0;
+        loop {
+            let t = t_start + i as f32 * dt;
+            if t >= t_exit {
+                break;
+            }
+            let p: Tuple = transformed_ray.position(t);
+            if let Some(t_skip) = self.empty_space_exit(&transformed_ray, &p) {
+                // every sample up to t_skip would read zero density
+                let skipped = ((t_skip - t_start) / dt).ceil() as i64;
+                if skipped > i {
+                    i = skipped;
+                    continue;
+                }
+            }
+            let density = self.density_at(p.x(), p.y(), p.z());
+            let alpha = 1.0 - (-density * self.transfer.absorption * step).exp();
+            sample.color += self.transfer.color_at(density) * (sample.transmittance * alpha);
+            sample.transmittance *= 1.0 - alpha;
+            if sample.transmittance < 1e-3 {
+                break;
+            }
+            i += 1;
+        }
+        return sample;
+    }
+}
+
+// A homogeneous participating medium (smoke, haze, murky glass) filling a
+// sphere. Without an integrator to hand it to, callers composite what it
+// returns: surface * transmittance + in-scattered colour.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConstantMedium {
+    boundary: Sphere,
+    pub density: f32,
+    pub albedo: Color
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Sphere, density: f32, albedo: Color) -> Self {
+        Self {boundary, density, albedo}
+    }
+
+    pub fn boundary(&self) -> &Sphere {
+        &self.boundary
+    }
+
+    // the part of [0, t_max] the ray spends inside the boundary
+    fn span(&self, ray: &Ray, t_max: f32) -> Option<(f32, f32)> {
+        let xs = self.boundary.intersect(ray);
+        if xs.len() < 2 {
+            return None;
+        }
+        let t0 = xs[0].point().max(0.0);
+        let t1 = xs[1].point().min(t_max);
+        if t0 >= t1 {
+            return None;
+        }
+        return Some((t0, t1));
+    }
+
+    // fraction of light surviving from the ray origin to t_max (e.g. the
+    // surface hit behind the medium)
+    pub fn transmittance(&self, ray: &Ray, t_max: f32) -> f32 {
+        match self.span(ray, t_max) {
+            Some((t0, t1)) => (-self.density * (t1 - t0) * ray.direction().magnitude()).exp(),
+            None => 1.0
+        }
+    }
+
+    // probabilistic scattering: the t at which the ray scatters inside the
+    // medium, or None if it passes through to t_max
+    pub fn sample_scatter(&self, ray: &Ray, t_max: f32, rng: &mut Rng) -> Option<f32> {
+        let (t0, t1) = self.span(ray, t_max)?;
+        if self.density <= 0.0 {
+            return None;
+        }
+        let distance = -(1.0 - rng.next_f32()).ln() / self.density;
+        let t = t0 + distance / ray.direction().magnitude();
+        if t >= t1 {
+            return None;
+        }
+        return Some(t);
+    }
+
+    // new direction after an isotropic scattering event
+    pub fn scatter_direction(rng: &mut Rng) -> Tuple {
+        let z = 1.0 - 2.0 * rng.next_f32();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * rng.next_f32();
+        return Tuple::vector(r * phi.cos(), r * phi.sin(), z);
+    }
+
+    // Single scattering of `light` towards the ray origin, ray marched in
+    // world-space steps of `step` (shafts of light through smoke). Light is
+    // attenuated by the medium on its way in but nothing else shadows it.
+    pub fn in_scatter(&self, ray: &Ray, t_max: f32, light: &PointLight, step: f32) -> VolumeSample {
+        let mut sample = VolumeSample {color: Color::black(), transmittance: 1.0};
+        let (t0, t1) = match self.span(ray, t_max) {
+            Some(span) if step > 0.0 => span,
+            _ => return sample
+        };
+        let speed = ray.direction().magnitude();
+        let dt = step / speed;
+        let mut start = t0;
+        while start < t1 {
+            // the last segment may be shorter than the rest
+            let end = (start + dt).min(t1);
+            let p = ray.position((start + end) / 2.0);
+            let to_light = Ray::new(p, light.position() - p);
+            let lit = light.intensity() * self.transmittance(&to_light, 1.0);
+            let alpha = 1.0 - (-self.density * (end - start) * speed).exp();
+            sample.color += &lit * &self.albedo * (sample.transmittance * alpha);
+            sample.transmittance *= 1.0 - alpha;
+            start = end;
+        }
+        return sample;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::utils;
+
+    #[test]
+    fn test_transfer_function_ramp() {
+        let mut tf = TransferFunction::new(1.0, Color::black(), Color::new(1.0, 1.0, 1.0));
+        tf.add_stop(0.5, Color::new(1.0, 0.0, 0.0));
+        assert_eq!(tf.color_at(-1.0), Color::black());
+        assert_eq!(tf.color_at(0.25), Color::new(0.5, 0.0, 0.0));
+        assert_eq!(tf.color_at(0.75), Color::new(1.0, 0.5, 0.5));
+        assert_eq!(tf.color_at(2.0), Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_trilinear_density() {
+        let mut grid = DensityGrid::new(2, 1, 1);
+        grid.set(1, 0, 0, 1.0);
+        assert_eq!(grid.density_at(0.5, 0.5, 0.5), 0.0);
+        assert_eq!(grid.density_at(1.0, 0.5, 0.5), 0.5);
+        assert_eq!(grid.density_at(1.5, 0.5, 0.5), 1.0);
+        assert_eq!(grid.density_at(2.0, 0.5, 0.5), 1.0);
+    }
+
+    #[test]
+    fn test_empty_volume_is_transparent() {
+        let grid = DensityGrid::new(4, 4, 4);
+        let r = Ray::new(Tuple::point(2.0, 2.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let sample = grid.march(&r, 0.1);
+        assert_eq!(sample.transmittance, 1.0);
+        assert_eq!(sample.color, Color::black());
+    }
+
+    #[test]
+    fn test_constant_density_absorption() {
+        let grid = DensityGrid::from_densities(2, 2, 2, vec![0.5; 8]).unwrap();
+        let r = Ray::new(Tuple::point(1.0, 1.0, -5.0), Tuple::vector(0.0, 0.0, 2.0));
+        let sample = grid.march(&r, 0.25);
+        // Beer-Lambert over a path length of 2
+        assert!(utils::is_equal(sample.transmittance, (-0.5f32 * 2.0).exp()));
+        let gray = 0.5 * (1.0 - sample.transmittance);
+        assert_eq!(sample.color, Color::new(gray, gray, gray));
+    }
+
+    #[test]
+    fn test_ray_missing_volume() {
+        let grid = DensityGrid::from_densities(1, 1, 1, vec![1.0]).unwrap();
+        let r = Ray::new(Tuple::point(5.0, 0.5, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(grid.march(&r, 0.1).transmittance, 1.0);
+    }
+
+    #[test]
+    fn test_march_rejects_non_positive_step() {
+        let grid = DensityGrid::from_densities(1, 1, 1, vec![1.0]).unwrap();
+        let r = Ray::new(Tuple::point(0.5, 0.5, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        for step in [0.0, -0.1, f32::NAN] {
+            assert_eq!(grid.march(&r, step).transmittance, 1.0);
+        }
+    }
+
+    #[test]
+    fn test_bricked_storage() {
+        let mut grid = DensityGrid::new_bricked(100, 100, 100);
+        assert_eq!(grid.allocated_bricks(), 0);
+        grid.set(50, 50, 50, 0.0);
+        assert_eq!(grid.allocated_bricks(), 0);
+        grid.set(50, 50, 50, 0.5);
+        grid.set(51, 47, 50, 0.25);
+        assert_eq!(grid.allocated_bricks(), 2);
+        assert_eq!(grid.get(50, 50, 50), 0.5);
+        assert_eq!(grid.get(51, 47, 50), 0.25);
+        assert_eq!(grid.get(99, 99, 99), 0.0);
+    }
+
+    #[test]
+    fn test_bricked_march_matches_dense() {
+        let mut dense = DensityGrid::new(40, 20, 20);
+        for x in 17..23 {
+            for y in 6..10 {
+                for z in 8..9 {
+                    dense.set(x, y, z, 0.1 * (x - 16) as f32);
+                }
+            }
+        }
+        let bricked = dense.to_bricked();
+        assert_eq!(bricked.allocated_bricks(), 2);
+        let rays = [Ray::new(Tuple::point(-1.0, 7.5, 8.5), Tuple::vector(1.0, 0.0, 0.0)),
+            Ray::new(Tuple::point(-3.0, 2.0, 1.0), Tuple::vector(2.0, 0.5, 0.7)),
+            Ray::new(Tuple::point(20.0, 8.0, -4.0), Tuple::vector(0.0, 0.0, 1.0))];
+        for r in rays.iter() {
+            let expected = dense.march(r, 0.1);
+            let actual = bricked.march(r, 0.1);
+            assert!(utils::is_equal(expected.transmittance, actual.transmittance));
+            assert_eq!(expected.color, actual.color);
+        }
+        assert!(bricked.march(&rays[0], 0.1).transmittance < 1.0);
+    }
+
+    #[test]
+    fn test_from_raw() {
+        let bytes: &[u8] = &[0, 255, 51, 0];
+        let grid = DensityGrid::from_raw(bytes, 2, 2, 1).unwrap();
+        assert_eq!(grid.get(1, 0, 0), 1.0);
+        assert_eq!(grid.get(0, 1, 0), 0.2);
+        assert!(DensityGrid::from_raw(bytes, 2, 2, 2).is_err());
+        assert_eq!(grid.get(2, 0, 0), 0.0);
+    }
+
+    #[test]
+    fn test_rejects_bad_dimensions() {
+        let bytes: &[u8] = &[0, 255];
+        assert_eq!(DensityGrid::from_raw(bytes, 0, 2, 1).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(DensityGrid::from_raw(bytes, usize::MAX, 2, 1).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(DensityGrid::from_densities(0, 1, 1, Vec::new()).is_none());
+        let data: &[u8] = b"NRRD0004\ntype: float\ndimension: 3\nsizes: 100000 100000 100000\nencoding: raw\n\n";
+        assert_eq!(DensityGrid::from_nrrd(data).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_from_nrrd_float() {
+        let mut data = b"NRRD0004\n# Complete NRRD file format specification at:\ntype: float\ndimension: 3\nsizes: 2 1 1\nendian: little\nencoding: raw\n\n".to_vec();
+        data.extend_from_slice(&0.25f32.to_le_bytes());
+        data.extend_from_slice(&0.75f32.to_le_bytes());
+        let grid = DensityGrid::from_nrrd(data.as_slice()).unwrap();
+        assert_eq!(grid.dims(), (2, 1, 1));
+        assert_eq!(grid.get(0, 0, 0), 0.25);
+        assert_eq!(grid.get(1, 0, 0), 0.75);
+    }
+
+    #[test]
+    fn test_from_nrrd_rejects_compressed() {
+        let data: &[u8] = b"NRRD0004\ntype: uchar\ndimension: 3\nsizes: 1 1 1\nencoding: gzip\n\n\x00";
+        assert_eq!(DensityGrid::from_nrrd(data).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    fn smoke(density: f32) -> ConstantMedium {
+        let mut boundary = Sphere::new(1);
+        boundary.set_transform(Matrix4::scaling(2.0, 2.0, 2.0));
+        ConstantMedium::new(boundary, density, Color::new(0.8, 0.8, 0.8))
+    }
+
+    #[test]
+    fn test_constant_medium_transmittance() {
+        let medium = smoke(0.5);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        // four units of smoke along the diameter
+        assert!(utils::is_equal(medium.transmittance(&r, f32::INFINITY), (-2.0f32).exp()));
+        // a surface halfway through only has two units in front of it
+        assert!(utils::is_equal(medium.transmittance(&r, 5.0), (-1.0f32).exp()));
+        // starting inside only counts what lies ahead
+        let inside = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 2.0));
+        assert!(utils::is_equal(medium.transmittance(&inside, f32::INFINITY), (-1.0f32).exp()));
+        let miss = Ray::new(Tuple::point(3.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(medium.transmittance(&miss, f32::INFINITY), 1.0);
+    }
+
+    #[test]
+    fn test_constant_medium_scatter_rate() {
+        let medium = smoke(0.25);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut rng = Rng::new(7);
+        let trials = 4000;
+        let mut scattered = 0;
+        for _ in 0..trials {
+            if let Some(t) = medium.sample_scatter(&r, f32::INFINITY, &mut rng) {
+                assert!((3.0..7.0).contains(&t));
+                scattered += 1;
+            }
+        }
+        // matches 1 - transmittance on average
+        let expected = 1.0 - medium.transmittance(&r, f32::INFINITY);
+        assert!((scattered as f32 / trials as f32 - expected).abs() < 0.03);
+        for _ in 0..10 {
+            assert!(utils::is_equal(ConstantMedium::scatter_direction(&mut rng).magnitude(), 1.0));
+        }
+    }
+
+    #[test]
+    fn test_constant_medium_in_scatter() {
+        let medium = smoke(0.5);
+        let light = PointLight::new(Color::WHITE, Tuple::point(0.0, 10.0, 0.0));
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let sample = medium.in_scatter(&r, f32::INFINITY, &light, 0.05);
+        assert!((sample.transmittance - medium.transmittance(&r, f32::INFINITY)).abs() < 1e-3);
+        assert!(sample.color.red() > 0.0 && sample.color.red() < 0.8 * (1.0 - sample.transmittance));
+        let miss = Ray::new(Tuple::point(3.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(medium.in_scatter(&miss, f32::INFINITY, &light, 0.05).color, Color::black());
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use super::ray::Ray;
+use super::tuple::Tuple;
+use super::matrix4::Matrix4;
+use super::material::Material;
+use super::intersection::{Intersect, Intersection, Intersections, Shape};
+use super::sampler;
+use super::watchdog;
+
+const EPSILON: f32 = 1e-4;
+
+// t range over which a ray (origin o, direction d) is inside the box from
+// the origin to n, or None if it misses the box entirely
+pub(crate) fn clip_to_grid(o: &[f32; 3], d: &[f32; 3], n: &[f32; 3]) -> Option<(f32, f32)> {
+    let mut t_enter = f32::NEG_INFINITY;
+    let mut t_exit = f32::INFINITY;
+    for i in 0..3 {
+        if d[i].abs() < EPSILON {
+            if o[i] < 0.0 || o[i] > n[i] {
+                return None;
+            }
+            continue;
+        }
+        let mut t0 = -o[i] / d[i];
+        let mut t1 = (n[i] - o[i]) / d[i];
+        if t0 > t1 {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+        t_enter = t_enter.max(t0);
+        t_exit = t_exit.min(t1);
+    }
+    if t_enter > t_exit {
+        return None;
+    }
+    Some((t_enter, t_exit))
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum VoxelStorage {
+    Dense(Vec<Option<Material>>),
+    Sparse(HashMap<(usize, usize, usize), Material>)
+}
+
+// A grid of unit cubes spanning (0, 0, 0) to (width, height, depth) in object
+// space. Each occupied voxel carries its own material.
+#[derive(Debug, PartialEq, Clone)]
+pub struct VoxelGrid {
+    transform: Matrix4,
+    dims: (usize, usize, usize),
+    voxels: VoxelStorage,
+    id: i32
+}
+
+impl VoxelGrid {
+    // panics on a zero dimension, which would leave the grid without a voxel
+    // for rays to land in
+    pub fn new(id: i32, width: usize, height: usize, depth: usize) -> Self {
+        assert!(width > 0 && height > 0 && depth > 0, "voxel grid dimensions must be non-zero");
+        let voxels = VoxelStorage::Dense(vec![None; width * height * depth]);
+        Self {id, transform: Matrix4::identity(), dims: (width, height, depth), voxels}
+    }
+
+    // Only the occupied voxels are stored, for large and mostly empty grids
+    pub fn new_sparse(id: i32, width: usize, height: usize, depth: usize) -> Self {
+        assert!(width > 0 && height > 0 && depth > 0, "voxel grid dimensions must be non-zero");
+        Self {id, transform: Matrix4::identity(), dims: (width, height, depth),
+            voxels: VoxelStorage::Sparse(HashMap::new())}
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+    }
+
+    pub fn transform(&self) -> &Matrix4 {
+        &self.transform
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    // per-instance value in [0, 1) for varying materials and textures
+    pub fn random_value(&self, seed: u64) -> f32 {
+        sampler::instance_random(self.id, seed)
+    }
+
+    // object space bounding box
+    pub fn bounds(&self) -> (Tuple, Tuple) {
+        (Tuple::point(0.0, 0.0, 0.0), Tuple::point(self.dims.0 as f32, self.dims.1 as f32, self.dims.2 as f32))
+    }
+
+    pub fn dims(&self) -> (usize, usize, usize) {
+        self.dims
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> Option<usize> {
+        let (width, height, depth) = self.dims;
+        if x >= width || y >= height || z >= depth {
+            return None;
+        }
+        Some((z * height + y) * width + x)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, material: Option<Material>) {
+        let idx = match self.index(x, y, z) {
+            None => return,
+            Some(idx) => idx
+        };
+        match &mut self.voxels {
+            VoxelStorage::Dense(cells) => cells[idx] = material,
+            VoxelStorage::Sparse(cells) => {
+                match material {
+                    Some(m) => cells.insert((x, y, z), m),
+                    None => cells.remove(&(x, y, z))
+                };
+            }
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<&Material> {
+        let idx = self.index(x, y, z)?;
+        match &self.voxels {
+            VoxelStorage::Dense(cells) => cells[idx].as_ref(),
+            VoxelStorage::Sparse(cells) => cells.get(&(x, y, z))
+        }
+    }
+
+    fn get_signed(&self, x: i64, y: i64, z: i64) -> Option<&Material> {
+        if x < 0 || y < 0 || z < 0 {
+            return None;
+        }
+        self.get(x as usize, y as usize, z as usize)
+    }
+
+    fn object_point(&self, point: &Tuple) -> Tuple {
+        &self.transform.inverse().unwrap() * point
+    }
+
+    // the voxel the surface point belongs to, found by stepping back inside
+    // along the object space normal
+    fn voxel_at(&self, object_point: &Tuple) -> Option<(i64, i64, i64)> {
+        let normal = self.object_normal(object_point);
+        let inside = *object_point - normal * EPSILON * 10.0;
+        let cell = (inside.x().floor() as i64, inside.y().floor() as i64, inside.z().floor() as i64);
+        self.get_signed(cell.0, cell.1, cell.2).map(|_| cell)
+    }
+
+    fn object_normal(&self, object_point: &Tuple) -> Tuple {
+        let coords = [object_point.x(), object_point.y(), object_point.z()];
+        // the face the point lies on is the one along the axis where it is
+        // closest to a voxel boundary
+        let mut axis = 0;
+        let mut min_dist = f32::MAX;
+        for (i, c) in coords.iter().enumerate() {
+            let dist = (c - c.round()).abs();
+            if dist < min_dist {
+                min_dist = dist;
+                axis = i;
+            }
+        }
+        let boundary = coords[axis].round() as i64;
+        let mut below = [coords[0].floor() as i64, coords[1].floor() as i64, coords[2].floor() as i64];
+        let mut above = below;
+        below[axis] = boundary - 1;
+        above[axis] = boundary;
+        let below_occupied = self.get_signed(below[0], below[1], below[2]).is_some();
+        let above_occupied = self.get_signed(above[0], above[1], above[2]).is_some();
+        let sign = if below_occupied && !above_occupied { 1.0 } else { -1.0 };
+        let mut normal = [0.0; 3];
+        normal[axis] = sign;
+        Tuple::vector(normal[0], normal[1], normal[2])
+    }
+
+    pub fn material_at(&self, point: &Tuple) -> Option<&Material> {
+        let (x, y, z) = self.voxel_at(&self.object_point(point))?;
+        self.get_signed(x, y, z)
+    }
+
+    // intersect_range for a ray already in object space
+    fn intersect_object(&self, transformed_ray: &Ray, t_min: f32, t_end: f32) -> Intersections<'_, Self> {
+        let origin = transformed_ray.origin();
+        let direction = transformed_ray.direction();
+        let o = [origin.x(), origin.y(), origin.z()];
+        let d = [direction.x(), direction.y(), direction.z()];
+        let n = [self.dims.0 as f32, self.dims.1 as f32, self.dims.2 as f32];
+
+        let (t_enter, t_exit) = match clip_to_grid(&o, &d, &n) {
+            None => return Intersections::new_empty(),
+            Some((t_enter, t_exit)) => (t_enter.max(t_min), t_exit.min(t_end))
+        };
+        if !watchdog::check_t("voxel grid", self.id, t_enter) || !watchdog::check_t("voxel grid", self.id, t_exit) {
+            return Intersections::new_empty();
+        }
+        if t_enter > t_exit {
+            return Intersections::new_empty();
+        }
+
+        // 3D DDA (Amanatides & Woo) walking the voxels along the ray
+        let mut cell = [0i64; 3];
+        let mut step = [0i64; 3];
+        let mut t_max = [f32::INFINITY; 3];
+        let mut t_delta = [f32::INFINITY; 3];
+        for i in 0..3 {
+            let start = o[i] + d[i] * t_enter;
+            cell[i] = (start.floor() as i64).clamp(0, n[i] as i64 - 1);
+            if d[i] > EPSILON {
+                step[i] = 1;
+                t_max[i] = ((cell[i] + 1) as f32 - o[i]) / d[i];
+                t_delta[i] = 1.0 / d[i];
+            } else if d[i] < -EPSILON {
+                step[i] = -1;
+                t_max[i] = (cell[i] as f32 - o[i]) / d[i];
+                t_delta[i] = -1.0 / d[i];
+            }
+        }
+
+        let mut xs = Intersections::new_empty();
+        let mut t = t_enter;
+        loop {
+            let axis = if t_max[0] < t_max[1] {
+                if t_max[0] < t_max[2] { 0 } else { 2 }
+            } else if t_max[1] < t_max[2] { 1 } else { 2 };
+            let t_next = t_max[axis].min(t_exit);
+            if self.get_signed(cell[0], cell[1], cell[2]).is_some() {
+                xs.add_point(Intersection::new(self, t));
+                xs.add_point(Intersection::new(self, t_next));
+            }
+            if t_max[axis] >= t_exit {
+                break;
+            }
+            t = t_max[axis];
+            cell[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+            if cell[axis] < 0 || cell[axis] >= n[axis] as i64 {
+                break;
+            }
+        }
+        return xs;
+    }
+}
+
+impl Shape for VoxelGrid {
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl Intersect<Self> for VoxelGrid {
+    fn intersect_range(&self, ray: &Ray, t_min: f32, t_end: f32) -> Intersections<'_, Self> {
+        self.intersect_object(&ray.transform(&self.transform.inverse().unwrap()), t_min, t_end)
+    }
+
+    fn intersect_batch(&self, rays: &[Ray]) -> Vec<Intersections<'_, Self>> {
+        let inverse = self.transform.inverse().unwrap();
+        rays.iter().map(|ray| self.intersect_object(&ray.transform(&inverse), f32::NEG_INFINITY, f32::INFINITY)).collect()
+    }
+
+    fn normal_at(&self, point: Tuple) -> Tuple {
+        let transform_inverse = self.transform.inverse().unwrap();
+        let object_point = transform_inverse * point;
+        let object_normal = self.object_normal(&object_point);
+        let world_normal = transform_inverse.transpose() * object_normal;
+        let world_normal_vector = Tuple::vector(world_normal.x(), world_normal.y(), world_normal.z());
+        return world_normal_vector.normalize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::color::Color;
+    use super::super::transform::TransformBuilder;
+
+    fn filled(id: i32, width: usize, height: usize, depth: usize) -> VoxelGrid {
+        let mut grid = VoxelGrid::new(id, width, height, depth);
+        for x in 0..width {
+            for y in 0..height {
+                for z in 0..depth {
+                    grid.set(x, y, z, Some(Material::new()));
+                }
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn test_single_voxel_intersection() {
+        let grid = filled(1, 1, 1, 1);
+        let r = Ray::new(Tuple::point(0.5, 0.5, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = grid.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].point(), 5.0);
+        assert_eq!(xs[1].point(), 6.0);
+    }
+
+    #[test]
+    fn test_ray_misses_grid() {
+        let grid = filled(1, 2, 2, 2);
+        let r = Ray::new(Tuple::point(3.0, 0.5, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(grid.intersect(&r).len(), 0);
+    }
+
+    #[test]
+    fn test_skips_empty_voxels() {
+        let mut grid = VoxelGrid::new(1, 1, 1, 3);
+        grid.set(0, 0, 2, Some(Material::new()));
+        let r = Ray::new(Tuple::point(0.5, 0.5, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = grid.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].point(), 7.0);
+        assert_eq!(xs[1].point(), 8.0);
+        assert_eq!(xs.hit().unwrap().point(), 7.0);
+    }
+
+    #[test]
+    fn test_intersect_range() {
+        let mut grid = VoxelGrid::new(1, 1, 1, 3);
+        grid.set(0, 0, 0, Some(Material::new()));
+        grid.set(0, 0, 2, Some(Material::new()));
+        let r = Ray::new(Tuple::point(0.5, 0.5, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        // a shadow ray towards something at t = 6.5 must not report the far voxel
+        let xs = grid.intersect_range(&r, 0.0, 6.5);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].point(), 5.0);
+        assert_eq!(xs[1].point(), 6.0);
+        // starting inside the near voxel clips its entry to t_min
+        let xs = grid.intersect_range(&r, 5.5, f32::INFINITY);
+        assert_eq!(xs.len(), 4);
+        assert_eq!(xs[0].point(), 5.5);
+        assert_eq!(xs[2].point(), 7.0);
+        assert_eq!(grid.intersect_range(&r, 0.0, 4.0).len(), 0);
+        assert_eq!(grid.intersect_range(&r, 6.2, 6.8).len(), 0);
+    }
+
+    #[test]
+    fn test_diagonal_traversal() {
+        let mut grid = VoxelGrid::new(1, 3, 3, 1);
+        grid.set(2, 2, 0, Some(Material::new()));
+        let r = Ray::new(Tuple::point(-1.0, -1.0, 0.5), Tuple::vector(1.0, 1.0, 0.0));
+        let xs = grid.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].point(), 3.0);
+        assert_eq!(xs[1].point(), 4.0);
+    }
+
+    #[test]
+    fn test_transformed_grid() {
+        let mut grid = filled(1, 2, 2, 2);
+        grid.set_transform(TransformBuilder::new4().translate(-1.0, -1.0, -1.0).build());
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = grid.intersect(&r);
+        assert_eq!(xs.hit().unwrap().point(), 4.0);
+    }
+
+    #[test]
+    fn test_normal_at_faces() {
+        let grid = filled(1, 1, 1, 1);
+        assert_eq!(grid.normal_at(Tuple::point(0.5, 0.5, 0.0)), Tuple::vector(0.0, 0.0, -1.0));
+        assert_eq!(grid.normal_at(Tuple::point(0.5, 0.5, 1.0)), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(grid.normal_at(Tuple::point(1.0, 0.3, 0.6)), Tuple::vector(1.0, 0.0, 0.0));
+        assert_eq!(grid.normal_at(Tuple::point(0.2, 0.0, 0.7)), Tuple::vector(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn test_per_voxel_material() {
+        let mut grid = VoxelGrid::new(1, 2, 1, 1);
+        let mut red = Material::new();
+        red.color = Color::new(1.0, 0.0, 0.0);
+        grid.set(0, 0, 0, Some(Material::new()));
+        grid.set(1, 0, 0, Some(red.clone()));
+        assert_eq!(grid.material_at(&Tuple::point(1.5, 0.5, 0.0)), Some(&red));
+        assert_eq!(grid.material_at(&Tuple::point(0.5, 0.5, 0.0)), Some(&Material::new()));
+    }
+
+    #[test]
+    fn test_sparse_storage() {
+        let mut grid = VoxelGrid::new_sparse(1, 100, 100, 100);
+        grid.set(50, 50, 99, Some(Material::new()));
+        assert!(grid.get(50, 50, 99).is_some());
+        assert!(grid.get(0, 0, 0).is_none());
+        let r = Ray::new(Tuple::point(50.5, 50.5, -1.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = grid.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].point(), 100.0);
+        grid.set(50, 50, 99, None);
+        assert_eq!(grid.intersect(&r).len(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_dimension() {
+        VoxelGrid::new_sparse(1, 4, 0, 4);
+    }
+}
\ No newline at end of file
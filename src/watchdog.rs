@@ -0,0 +1,62 @@
+use super::color::Color;
+
+// NaN/Inf detection for debugging black spots. With the `watchdog` feature
+// the renderer reports every non-finite t value or colour, naming the object
+// or pixel that produced it; without it the checks compile to nothing.
+
+pub fn is_finite_color(color: &Color) -> bool {
+    color.red().is_finite() && color.green().is_finite() && color.blue().is_finite()
+}
+
+#[cfg(feature = "watchdog")]
+pub(crate) fn check_t(shape: &str, id: i32, t: f32) -> bool {
+    if t.is_finite() {
+        return true;
+    }
+    log::warn!("watchdog: {} {} produced non-finite t = {}", shape, id, t);
+    false
+}
+
+#[cfg(not(feature = "watchdog"))]
+#[inline(always)]
+pub(crate) fn check_t(_shape: &str, _id: i32, _t: f32) -> bool {
+    true
+}
+
+#[cfg(feature = "watchdog")]
+pub(crate) fn check_color(x: usize, y: usize, color: &Color) -> bool {
+    if is_finite_color(color) {
+        return true;
+    }
+    log::warn!("watchdog: pixel ({}, {}) produced non-finite colour {:?}",
+        x, y, (color.red(), color.green(), color.blue()));
+    false
+}
+
+#[cfg(not(feature = "watchdog"))]
+#[inline(always)]
+pub(crate) fn check_color(_x: usize, _y: usize, _color: &Color) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_finite_color() {
+        assert!(is_finite_color(&Color::new(0.0, 1.0, 100.0)));
+        assert!(!is_finite_color(&Color::new(f32::NAN, 0.0, 0.0)));
+        assert!(!is_finite_color(&Color::new(0.0, f32::INFINITY, 0.0)));
+        assert!(!is_finite_color(&Color::new(0.0, 0.0, f32::NEG_INFINITY)));
+    }
+
+    #[cfg(feature = "watchdog")]
+    #[test]
+    fn test_checks_report_non_finite_values() {
+        assert!(check_t("sphere", 1, 4.0));
+        assert!(!check_t("sphere", 1, f32::NAN));
+        assert!(check_color(0, 0, &Color::black()));
+        assert!(!check_color(3, 4, &Color::new(f32::NAN, 0.0, 0.0)));
+    }
+}
\ No newline at end of file